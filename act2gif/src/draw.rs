@@ -2,13 +2,12 @@ use crate::font::FontHandle;
 use crate::term_image::TermImage;
 use crate::theme::Theme;
 use image::gif::{DisposalMethod, Encoder};
-use rogue_gym_core::{error::*, input::InputCode, GameConfig, Reaction};
+use rogue_gym_core::{error::*, replay::Replay, Reaction};
 use rogue_gym_uilib::process_reaction;
 use std::fs::File;
 use std::io::BufWriter;
 
 pub struct GifEncoder<'a> {
-    config: GameConfig,
     font: FontHandle<'a>,
     scale: u32,
     theme: Theme,
@@ -16,36 +15,33 @@ pub struct GifEncoder<'a> {
 }
 
 impl<'a> GifEncoder<'a> {
-    pub fn new(
-        config: GameConfig,
-        font: FontHandle<'a>,
-        scale: u32,
-        theme: Theme,
-        interval: u32,
-    ) -> Self {
+    pub fn new(font: FontHandle<'a>, scale: u32, theme: Theme, interval: u32) -> Self {
         GifEncoder {
-            config,
             font,
             scale,
             theme,
             interval,
         }
     }
-    pub fn exec(&mut self, inputs: Vec<InputCode>, filename: &str) -> GameResult<()> {
-        let mut runtime = self.config.clone().build()?;
+    /// encode first `max` steps of the replay
+    pub fn exec(&mut self, replay: &Replay, max: usize, filename: &str) -> GameResult<()> {
+        let config = &replay.config;
+        let mut runtime = replay.build_runtime()?;
         let file = File::create(filename).into_chained(|| "Failed to crate file")?;
         let writer = BufWriter::new(file);
         let mut encoder = Encoder::new(writer);
         let mut term = TermImage::new(
-            self.config.width.into(),
-            self.config.height.into(),
+            config.width.into(),
+            config.height.into(),
             self.scale,
             self.theme.back,
             self.theme.font,
             &mut self.font,
         );
-        for i in inputs {
-            let reaction = match runtime.react_to_input(i) {
+        for (step, &i) in replay.inputs.iter().take(max).enumerate() {
+            let reaction = runtime.react_to_input(i);
+            replay.check_step(step + 1, &runtime)?;
+            let reaction = match reaction {
                 Ok(r) => r,
                 Err(e) => {
                     eprintln!("error: {}", e);
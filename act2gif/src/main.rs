@@ -7,7 +7,7 @@ mod term_image;
 mod theme;
 use self::draw::GifEncoder;
 use clap::{self, ArgMatches};
-use rogue_gym_core::{error::*, input::InputCode, json_to_inputs, read_file, GameConfig};
+use rogue_gym_core::{error::*, json_to_inputs, read_file, replay::Replay, GameConfig};
 const UBUNTU_MONO: &[u8; 205748] = include_bytes!("../../data/fonts/UbuntuMono-R.ttf");
 use self::font::FontHandle;
 use self::theme::Theme;
@@ -31,7 +31,7 @@ fn parse_args<'a>() -> ArgMatches<'a> {
                 .long("actions")
                 .required(true)
                 .value_name("ACTIONS")
-                .help("replay json file")
+                .help("replay json file, saved by rogue-gym-devui or RogueEnv.save_actions")
                 .takes_value(true),
         )
         .arg(
@@ -87,21 +87,27 @@ fn parse_args<'a>() -> ArgMatches<'a> {
         .get_matches()
 }
 
-fn get_config(args: &ArgMatches) -> GameResult<GameConfig> {
+fn get_config(args: &ArgMatches) -> GameResult<Option<GameConfig>> {
     let file_name = match args.value_of("config") {
         Some(fname) => fname,
-        None => {
-            return Ok(GameConfig::default());
-        }
+        None => return Ok(None),
     };
-    let f = read_file(file_name).into_chained(|| "in get_config")?;
-    GameConfig::from_json(&f)
+    GameConfig::from_file(file_name).map(Some)
 }
 
-fn get_replay(args: &ArgMatches) -> GameResult<Vec<InputCode>> {
+fn get_replay(args: &ArgMatches, config: Option<&GameConfig>) -> GameResult<Replay> {
     let fname = args.value_of("actions").unwrap();
     let replay = read_file(fname).into_chained(|| "Failed to read replay file!")?;
-    json_to_inputs(&replay)
+    if !replay.trim_start().starts_with('[') {
+        return Replay::from_json(&replay);
+    }
+    // an old input list, played with the given config
+    let config = match config {
+        Some(c) => c.clone(),
+        None => bail!("--config is needed to replay an input list"),
+    };
+    let inputs = json_to_inputs(&replay)?;
+    Replay::record(config, &inputs)
 }
 
 fn get_arg<T: ::std::str::FromStr>(args: &ArgMatches, value: &str) -> Option<T> {
@@ -121,29 +127,38 @@ fn get_out_file(args: &ArgMatches) -> GameResult<String> {
     Ok(res)
 }
 
-fn setup<'a>() -> GameResult<(GifEncoder<'a>, Vec<InputCode>, String)> {
+fn setup<'a>() -> GameResult<(GifEncoder<'a>, Replay, usize, String)> {
     let args = parse_args();
     let mut config = get_config(&args)?;
-    let mut replay = get_replay(&args)?;
+    let seed = get_arg(&args, "seed");
+    if let (Some(config), Some(_)) = (config.as_mut(), seed) {
+        config.seed = seed;
+    }
+    let replay = get_replay(&args, config.as_ref())?;
     let interval = get_arg(&args, "interval").unwrap();
     let scale = get_arg(&args, "fontsize").unwrap();
     let max = get_arg(&args, "max_actions").unwrap();
-    if let Some(seed) = get_arg(&args, "seed") {
-        config.seed = Some(seed);
+    // the replay has its own config, but we check the given one is the same
+    if config.is_some() || seed.is_some() {
+        let mut config = config.unwrap_or_default();
+        if seed.is_some() {
+            config.seed = seed;
+        }
+        replay.check_config(&config)?;
     }
-    replay.truncate(max);
     let theme = args.value_of("theme").unwrap_or("solarized-dark");
     let theme = Theme::from_str(theme).expect("Unknown theme was specified");
     let font = FontHandle::new(&UBUNTU_MONO[..], scale);
     let out_file = get_out_file(&args)?;
     Ok((
-        GifEncoder::new(config, font, scale, theme, interval),
+        GifEncoder::new(font, scale, theme, interval),
         replay,
+        max,
         out_file,
     ))
 }
 
 fn main() -> GameResult<()> {
-    let (mut encoder, inputs, out_file) = setup()?;
-    encoder.exec(inputs, &out_file)
+    let (mut encoder, replay, max, out_file) = setup()?;
+    encoder.exec(&replay, max, &out_file)
 }
@@ -0,0 +1,23 @@
+//! regenerates include/rogue_gym.h when the `header` feature is enabled
+
+#[cfg(feature = "header")]
+fn main() {
+    use std::env;
+    use std::path::PathBuf;
+    let crate_dir = PathBuf::from(env::var("CARGO_MANIFEST_DIR").unwrap());
+    let config = cbindgen::Config::from_file(crate_dir.join("cbindgen.toml"))
+        .expect("failed to read cbindgen.toml");
+    cbindgen::Builder::new()
+        .with_crate(&crate_dir)
+        .with_config(config)
+        .generate()
+        .expect("failed to generate the header")
+        .write_to_file(crate_dir.join("include/rogue_gym.h"));
+    println!("cargo:rerun-if-changed=src/lib.rs");
+    println!("cargo:rerun-if-changed=cbindgen.toml");
+}
+
+#[cfg(not(feature = "header"))]
+fn main() {
+    println!("cargo:rerun-if-changed=build.rs");
+}
@@ -0,0 +1,38 @@
+/* Walks around a bit and prints the screen. */
+#include <stdio.h>
+#include <stdlib.h>
+#include "rogue_gym.h"
+
+int main(void) {
+    RogueGymConfig *config = rogue_gym_config_from_json("{\"seed\": 1}");
+    if (config == NULL) {
+        fprintf(stderr, "%s\n", rogue_gym_last_error());
+        return 1;
+    }
+    RogueGymRuntime *runtime = rogue_gym_build(config);
+    rogue_gym_config_free(config);
+    if (runtime == NULL) {
+        fprintf(stderr, "%s\n", rogue_gym_last_error());
+        return 1;
+    }
+    const char *keys = "hhjjkkll";
+    for (const char *k = keys; *k; k++) {
+        if (rogue_gym_react_to_key(runtime, ROGUE_GYM_KEY_CHAR, (uint32_t)*k) < 0) {
+            fprintf(stderr, "%s\n", rogue_gym_last_error());
+        }
+    }
+    int32_t width, height;
+    rogue_gym_screen_size(runtime, &width, &height);
+    uint8_t *screen = malloc((size_t)(width * height));
+    rogue_gym_draw_screen(runtime, screen, (size_t)(width * height));
+    for (int32_t y = 0; y < height; y++) {
+        printf("%.*s\n", (int)width, (const char *)screen + y * width);
+    }
+    RogueGymStatus status;
+    rogue_gym_player_status(runtime, &status);
+    printf("Level: %u Gold: %u Hp: %u(%u)\n", status.dungeon_level, status.gold,
+           status.hp_current, status.hp_max);
+    free(screen);
+    rogue_gym_runtime_free(runtime);
+    return 0;
+}
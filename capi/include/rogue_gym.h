@@ -0,0 +1,166 @@
+#ifndef ROGUE_GYM_H
+#define ROGUE_GYM_H
+
+/* Generated by cbindgen from src/lib.rs. Don't edit by hand. */
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+/**
+ * the input was ignored by the game, which isn't an error of the caller
+ */
+#define ROGUE_GYM_IGNORED 1
+
+/**
+ * some error occured
+ */
+#define ROGUE_GYM_ERROR -1
+
+/**
+ * kinds of keys for `rogue_gym_react_to_key`.
+ * `ROGUE_GYM_KEY_CHAR`, `ROGUE_GYM_KEY_ALT` and `ROGUE_GYM_KEY_CTRL` take a unicode scalar
+ * value, `ROGUE_GYM_KEY_F` takes the number of the function key and others take no value.
+ */
+#define ROGUE_GYM_KEY_CHAR 0
+
+#define ROGUE_GYM_KEY_ALT 1
+
+#define ROGUE_GYM_KEY_CTRL 2
+
+#define ROGUE_GYM_KEY_F 3
+
+#define ROGUE_GYM_KEY_ESC 4
+
+#define ROGUE_GYM_KEY_BACKSPACE 5
+
+#define ROGUE_GYM_KEY_LEFT 6
+
+#define ROGUE_GYM_KEY_RIGHT 7
+
+#define ROGUE_GYM_KEY_UP 8
+
+#define ROGUE_GYM_KEY_DOWN 9
+
+#define ROGUE_GYM_KEY_HOME 10
+
+#define ROGUE_GYM_KEY_END 11
+
+#define ROGUE_GYM_KEY_PAGE_UP 12
+
+#define ROGUE_GYM_KEY_PAGE_DOWN 13
+
+#define ROGUE_GYM_KEY_DELETE 14
+
+#define ROGUE_GYM_KEY_INSERT 15
+
+/**
+ * game configuration
+ */
+typedef struct RogueGymConfig RogueGymConfig;
+
+/**
+ * a running game
+ */
+typedef struct RogueGymRuntime RogueGymRuntime;
+
+/**
+ * status of the player
+ */
+typedef struct RogueGymStatus {
+  uint32_t dungeon_level;
+  uint32_t gold;
+  uint32_t hp_current;
+  uint32_t hp_max;
+  uint32_t strength_current;
+  uint32_t strength_max;
+  uint32_t defense;
+  uint32_t player_level;
+  uint32_t exp;
+  uint32_t hunger_level;
+} RogueGymStatus;
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+/**
+ * message of the error in the last call of a rogue-gym function in this thread,
+ * or NULL if the call succeeded.
+ * The pointer is valid until the next call of a rogue-gym function in this thread.
+ */
+const char *rogue_gym_last_error(void);
+
+/**
+ * parse a config in JSON. The result must be freed by `rogue_gym_config_free`.
+ *
+ * # Safety
+ * `json` must be a NUL-terminated string.
+ */
+struct RogueGymConfig *rogue_gym_config_from_json(const char *json);
+
+/**
+ * # Safety
+ * `config` must be NULL or a pointer returned by `rogue_gym_config_from_json`.
+ */
+void rogue_gym_config_free(struct RogueGymConfig *config);
+
+/**
+ * start a game. The result must be freed by `rogue_gym_runtime_free`.
+ *
+ * # Safety
+ * `config` must be a valid config. It's not consumed.
+ */
+struct RogueGymRuntime *rogue_gym_build(const struct RogueGymConfig *config);
+
+/**
+ * # Safety
+ * `runtime` must be NULL or a pointer returned by `rogue_gym_build`.
+ */
+void rogue_gym_runtime_free(struct RogueGymRuntime *runtime);
+
+/**
+ * press a key of `kind`, one of `ROGUE_GYM_KEY_*`, like `(ROGUE_GYM_KEY_CHAR, 'h')`
+ * or `(ROGUE_GYM_KEY_ESC, 0)`.
+ * Returns `ROGUE_GYM_IGNORED` if the game ignored the key.
+ *
+ * # Safety
+ * `runtime` must be a valid runtime.
+ */
+int32_t rogue_gym_react_to_key(struct RogueGymRuntime *runtime, uint32_t kind, uint32_t value);
+
+/**
+ * write the width and height of the screen
+ *
+ * # Safety
+ * `runtime` must be a valid runtime, and `width` and `height` must be writable.
+ */
+int32_t rogue_gym_screen_size(const struct RogueGymRuntime *runtime,
+                              int32_t *width,
+                              int32_t *height);
+
+/**
+ * draw the screen into `buf` as ASCII bytes, row by row.
+ * `len` must be at least width * height of the screen.
+ *
+ * # Safety
+ * `runtime` must be a valid runtime, and `buf` must be writable for `len` bytes.
+ */
+int32_t rogue_gym_draw_screen(const struct RogueGymRuntime *runtime, uint8_t *buf, size_t len);
+
+/**
+ * write the status of the player
+ *
+ * # Safety
+ * `runtime` must be a valid runtime, and `status` must be writable.
+ */
+int32_t rogue_gym_player_status(const struct RogueGymRuntime *runtime,
+                                struct RogueGymStatus *status);
+
+#ifdef __cplusplus
+} // extern "C"
+#endif // __cplusplus
+
+#endif /* ROGUE_GYM_H */
@@ -0,0 +1,352 @@
+//! C API of rogue-gym
+//!
+//! Functions returning `int32_t` return 0 on success and a negative value on failure.
+//! Functions returning pointers return NULL on failure.
+//! In both cases, `rogue_gym_last_error` tells what happened.
+use rogue_gym_core::dungeon::Positioned;
+use rogue_gym_core::error::{ErrorId, FailExt, GameResult};
+use rogue_gym_core::input::Key;
+use rogue_gym_core::{GameConfig, RunTime};
+use std::cell::RefCell;
+use std::convert::TryFrom;
+use std::ffi::{CStr, CString};
+use std::os::raw::c_char;
+use std::panic::{self, AssertUnwindSafe};
+use std::ptr;
+
+/// the input was ignored by the game, which isn't an error of the caller
+pub const ROGUE_GYM_IGNORED: i32 = 1;
+/// some error occured
+pub const ROGUE_GYM_ERROR: i32 = -1;
+
+/// kinds of keys for `rogue_gym_react_to_key`.
+/// `ROGUE_GYM_KEY_CHAR`, `ROGUE_GYM_KEY_ALT` and `ROGUE_GYM_KEY_CTRL` take a unicode scalar
+/// value, `ROGUE_GYM_KEY_F` takes the number of the function key and others take no value.
+pub const ROGUE_GYM_KEY_CHAR: u32 = 0;
+pub const ROGUE_GYM_KEY_ALT: u32 = 1;
+pub const ROGUE_GYM_KEY_CTRL: u32 = 2;
+pub const ROGUE_GYM_KEY_F: u32 = 3;
+pub const ROGUE_GYM_KEY_ESC: u32 = 4;
+pub const ROGUE_GYM_KEY_BACKSPACE: u32 = 5;
+pub const ROGUE_GYM_KEY_LEFT: u32 = 6;
+pub const ROGUE_GYM_KEY_RIGHT: u32 = 7;
+pub const ROGUE_GYM_KEY_UP: u32 = 8;
+pub const ROGUE_GYM_KEY_DOWN: u32 = 9;
+pub const ROGUE_GYM_KEY_HOME: u32 = 10;
+pub const ROGUE_GYM_KEY_END: u32 = 11;
+pub const ROGUE_GYM_KEY_PAGE_UP: u32 = 12;
+pub const ROGUE_GYM_KEY_PAGE_DOWN: u32 = 13;
+pub const ROGUE_GYM_KEY_DELETE: u32 = 14;
+pub const ROGUE_GYM_KEY_INSERT: u32 = 15;
+
+/// game configuration
+pub struct RogueGymConfig(GameConfig);
+
+/// a running game
+pub struct RogueGymRuntime(RunTime);
+
+/// status of the player
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct RogueGymStatus {
+    pub dungeon_level: u32,
+    pub gold: u32,
+    pub hp_current: u32,
+    pub hp_max: u32,
+    pub strength_current: u32,
+    pub strength_max: u32,
+    pub defense: u32,
+    pub player_level: u32,
+    pub exp: u32,
+    pub hunger_level: u32,
+}
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+fn set_error(msg: String) {
+    let msg = CString::new(msg).unwrap_or_else(|_| CString::new("invalid error message").unwrap());
+    LAST_ERROR.with(|e| *e.borrow_mut() = Some(msg));
+}
+
+/// run `f`, storing errors and panics as the last error
+fn guard<T>(default: T, f: impl FnOnce() -> GameResult<T>) -> T {
+    LAST_ERROR.with(|e| *e.borrow_mut() = None);
+    match panic::catch_unwind(AssertUnwindSafe(f)) {
+        Ok(Ok(t)) => t,
+        Ok(Err(e)) => {
+            let causes: Vec<_> = e.iter_chain().map(|c| c.to_string()).collect();
+            set_error(causes.join(": "));
+            default
+        }
+        Err(_) => {
+            set_error("rogue-gym panicked".to_owned());
+            default
+        }
+    }
+}
+
+fn null_error(name: &str) -> failure::Error {
+    ErrorId::InvalidSetting.into_with(|| format!("{} is NULL", name))
+}
+
+fn to_key(kind: u32, value: u32) -> GameResult<Key> {
+    let invalid = move |what: &'static str| {
+        ErrorId::InvalidConversion.into_with(move || format!("invalid {} {}", what, value))
+    };
+    let ch = || std::char::from_u32(value).ok_or_else(|| invalid("key"));
+    let key = match kind {
+        ROGUE_GYM_KEY_CHAR => Key::Char(ch()?),
+        ROGUE_GYM_KEY_ALT => Key::Alt(ch()?),
+        ROGUE_GYM_KEY_CTRL => Key::Ctrl(ch()?),
+        ROGUE_GYM_KEY_F => Key::F(u8::try_from(value).map_err(|_| invalid("function key"))?),
+        ROGUE_GYM_KEY_ESC => Key::Esc,
+        ROGUE_GYM_KEY_BACKSPACE => Key::Backspace,
+        ROGUE_GYM_KEY_LEFT => Key::Left,
+        ROGUE_GYM_KEY_RIGHT => Key::Right,
+        ROGUE_GYM_KEY_UP => Key::Up,
+        ROGUE_GYM_KEY_DOWN => Key::Down,
+        ROGUE_GYM_KEY_HOME => Key::Home,
+        ROGUE_GYM_KEY_END => Key::End,
+        ROGUE_GYM_KEY_PAGE_UP => Key::PageUp,
+        ROGUE_GYM_KEY_PAGE_DOWN => Key::PageDown,
+        ROGUE_GYM_KEY_DELETE => Key::Delete,
+        ROGUE_GYM_KEY_INSERT => Key::Insert,
+        _ => {
+            return Err(
+                ErrorId::InvalidConversion.into_with(move || format!("invalid key kind {}", kind))
+            )
+        }
+    };
+    Ok(key)
+}
+
+/// message of the error in the last call of a rogue-gym function in this thread,
+/// or NULL if the call succeeded.
+/// The pointer is valid until the next call of a rogue-gym function in this thread.
+#[no_mangle]
+pub extern "C" fn rogue_gym_last_error() -> *const c_char {
+    LAST_ERROR.with(|e| e.borrow().as_ref().map_or(ptr::null(), |s| s.as_ptr()))
+}
+
+/// parse a config in JSON. The result must be freed by `rogue_gym_config_free`.
+///
+/// # Safety
+/// `json` must be a NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn rogue_gym_config_from_json(json: *const c_char) -> *mut RogueGymConfig {
+    guard(ptr::null_mut(), || {
+        if json.is_null() {
+            return Err(null_error("json"));
+        }
+        let json = CStr::from_ptr(json)
+            .to_str()
+            .map_err(|e| ErrorId::InvalidSetting.into_with(move || e.to_string()))?;
+        let config = GameConfig::from_json(json)?;
+        Ok(Box::into_raw(Box::new(RogueGymConfig(config))))
+    })
+}
+
+/// # Safety
+/// `config` must be NULL or a pointer returned by `rogue_gym_config_from_json`.
+#[no_mangle]
+pub unsafe extern "C" fn rogue_gym_config_free(config: *mut RogueGymConfig) {
+    if !config.is_null() {
+        drop(Box::from_raw(config));
+    }
+}
+
+/// start a game. The result must be freed by `rogue_gym_runtime_free`.
+///
+/// # Safety
+/// `config` must be a valid config. It's not consumed.
+#[no_mangle]
+pub unsafe extern "C" fn rogue_gym_build(config: *const RogueGymConfig) -> *mut RogueGymRuntime {
+    guard(ptr::null_mut(), || {
+        let config = config.as_ref().ok_or_else(|| null_error("config"))?;
+        let runtime = config.0.clone().build()?;
+        Ok(Box::into_raw(Box::new(RogueGymRuntime(runtime))))
+    })
+}
+
+/// # Safety
+/// `runtime` must be NULL or a pointer returned by `rogue_gym_build`.
+#[no_mangle]
+pub unsafe extern "C" fn rogue_gym_runtime_free(runtime: *mut RogueGymRuntime) {
+    if !runtime.is_null() {
+        drop(Box::from_raw(runtime));
+    }
+}
+
+/// press a key of `kind`, one of `ROGUE_GYM_KEY_*`, like `(ROGUE_GYM_KEY_CHAR, 'h')`
+/// or `(ROGUE_GYM_KEY_ESC, 0)`.
+/// Returns `ROGUE_GYM_IGNORED` if the game ignored the key.
+///
+/// # Safety
+/// `runtime` must be a valid runtime.
+#[no_mangle]
+pub unsafe extern "C" fn rogue_gym_react_to_key(
+    runtime: *mut RogueGymRuntime,
+    kind: u32,
+    value: u32,
+) -> i32 {
+    guard(ROGUE_GYM_ERROR, || {
+        let runtime = runtime.as_mut().ok_or_else(|| null_error("runtime"))?;
+        let key = to_key(kind, value)?;
+        match runtime.0.react_to_key(key) {
+            Ok(_) => Ok(0),
+            Err(e) => match e.find_root_cause().downcast_ref::<ErrorId>() {
+                Some(id) if id.can_allow() => Ok(ROGUE_GYM_IGNORED),
+                _ => Err(e),
+            },
+        }
+    })
+}
+
+/// write the width and height of the screen
+///
+/// # Safety
+/// `runtime` must be a valid runtime, and `width` and `height` must be writable.
+#[no_mangle]
+pub unsafe extern "C" fn rogue_gym_screen_size(
+    runtime: *const RogueGymRuntime,
+    width: *mut i32,
+    height: *mut i32,
+) -> i32 {
+    guard(ROGUE_GYM_ERROR, || {
+        let runtime = runtime.as_ref().ok_or_else(|| null_error("runtime"))?;
+        let (w, h) = runtime.0.screen_size();
+        *width.as_mut().ok_or_else(|| null_error("width"))? = w.0;
+        *height.as_mut().ok_or_else(|| null_error("height"))? = h.0;
+        Ok(0)
+    })
+}
+
+/// draw the screen into `buf` as ASCII bytes, row by row.
+/// `len` must be at least width * height of the screen.
+///
+/// # Safety
+/// `runtime` must be a valid runtime, and `buf` must be writable for `len` bytes.
+#[no_mangle]
+pub unsafe extern "C" fn rogue_gym_draw_screen(
+    runtime: *const RogueGymRuntime,
+    buf: *mut u8,
+    len: usize,
+) -> i32 {
+    guard(ROGUE_GYM_ERROR, || {
+        let runtime = runtime.as_ref().ok_or_else(|| null_error("runtime"))?;
+        if buf.is_null() {
+            return Err(null_error("buf"));
+        }
+        let (w, h) = runtime.0.screen_size();
+        let (w, h) = (w.0 as usize, h.0 as usize);
+        if len < w * h {
+            return Err(ErrorId::InvalidSetting.into_with(move || {
+                format!("buffer of {} bytes is smaller than {}x{}", len, w, h)
+            }));
+        }
+        let buf = std::slice::from_raw_parts_mut(buf, len);
+        for b in &mut buf[..w * h] {
+            *b = b' ';
+        }
+        runtime.0.draw_screen(|Positioned(cd, tile)| {
+            let (x, y) = (cd.x.0 as usize, cd.y.0 as usize);
+            if x < w && y < h {
+                buf[y * w + x] = tile.to_byte();
+            }
+            Ok(())
+        })?;
+        Ok(0)
+    })
+}
+
+/// write the status of the player
+///
+/// # Safety
+/// `runtime` must be a valid runtime, and `status` must be writable.
+#[no_mangle]
+pub unsafe extern "C" fn rogue_gym_player_status(
+    runtime: *const RogueGymRuntime,
+    status: *mut RogueGymStatus,
+) -> i32 {
+    guard(ROGUE_GYM_ERROR, || {
+        let runtime = runtime.as_ref().ok_or_else(|| null_error("runtime"))?;
+        let out = status.as_mut().ok_or_else(|| null_error("status"))?;
+        let s = runtime.0.player_status();
+        *out = RogueGymStatus {
+            dungeon_level: s.dungeon_level,
+            gold: s.gold,
+            hp_current: s.hp.current.0 as u32,
+            hp_max: s.hp.max.0 as u32,
+            strength_current: s.strength.current.0 as u32,
+            strength_max: s.strength.max.0 as u32,
+            defense: s.defense.0 as u32,
+            player_level: s.player_level,
+            exp: s.exp.0,
+            hunger_level: s.hunger_level.to_u32(),
+        };
+        Ok(0)
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    #[test]
+    fn play() {
+        let json = CString::new(r#"{"seed": 1}"#).unwrap();
+        unsafe {
+            let config = rogue_gym_config_from_json(json.as_ptr());
+            assert!(!config.is_null());
+            let runtime = rogue_gym_build(config);
+            rogue_gym_config_free(config);
+            assert!(!runtime.is_null());
+            let (mut w, mut h) = (0, 0);
+            assert_eq!(rogue_gym_screen_size(runtime, &mut w, &mut h), 0);
+            let mut buf = vec![0u8; (w * h) as usize];
+            assert_eq!(
+                rogue_gym_draw_screen(runtime, buf.as_mut_ptr(), buf.len()),
+                0
+            );
+            assert!(buf.contains(&b'@'));
+            let small = buf.len() - 1;
+            assert_eq!(
+                rogue_gym_draw_screen(runtime, buf.as_mut_ptr(), small),
+                ROGUE_GYM_ERROR
+            );
+            assert!(!rogue_gym_last_error().is_null());
+            assert_eq!(
+                rogue_gym_react_to_key(runtime, ROGUE_GYM_KEY_CHAR, 's' as u32),
+                0
+            );
+            // a successful call clears the last error
+            assert!(rogue_gym_last_error().is_null());
+            assert_eq!(
+                rogue_gym_react_to_key(runtime, ROGUE_GYM_KEY_CHAR, 'Z' as u32),
+                ROGUE_GYM_IGNORED
+            );
+            // open the inventory and close it by Esc
+            assert_eq!(
+                rogue_gym_react_to_key(runtime, ROGUE_GYM_KEY_CHAR, 'i' as u32),
+                0
+            );
+            assert_eq!(rogue_gym_react_to_key(runtime, ROGUE_GYM_KEY_ESC, 0), 0);
+            assert_eq!(
+                rogue_gym_react_to_key(runtime, ROGUE_GYM_KEY_F, 1000),
+                ROGUE_GYM_ERROR
+            );
+            assert_eq!(rogue_gym_react_to_key(runtime, 100, 0), ROGUE_GYM_ERROR);
+            let mut status = RogueGymStatus::default();
+            assert_eq!(rogue_gym_player_status(runtime, &mut status), 0);
+            assert_eq!(status.dungeon_level, 1);
+            assert_eq!(status.hp_current, status.hp_max);
+            rogue_gym_runtime_free(runtime);
+            let broken = CString::new("{").unwrap();
+            assert!(rogue_gym_config_from_json(broken.as_ptr()).is_null());
+            assert!(rogue_gym_build(ptr::null()).is_null());
+            let err = CStr::from_ptr(rogue_gym_last_error());
+            assert!(err.to_str().unwrap().contains("NULL"));
+        }
+    }
+}
@@ -1,15 +1,23 @@
 //! module for handling actions and do some operations related to multiple modules
 use crate::character::{
-    fight, player::PlayerEvent, Action, DamageReaction, Enemy, EnemyHandler, Player,
+    fight::{self, Hit},
+    player::{ItemAction, PlayerEvent, RunConfig},
+    Action, DamageReaction, Enemy, EnemyHandler, EnemyId, Player,
 };
-use crate::dungeon::{Direction, Dungeon, DungeonPath};
+use crate::dungeon::{Direction, Dungeon, DungeonPath, TravelTarget};
 use crate::error::*;
 use crate::item::{itembox::Entry as ItemEntry, ItemHandler, ItemToken};
-use crate::ui::UiState;
-use crate::{GameInfo, GameMsg, Reaction};
+use crate::rng::Parcent;
+use crate::ui::{MordalKind, UiState};
+use crate::{GameInfo, GameMsg, Reaction, SmallStr};
+use enum_iterator::IntoEnumIterator;
+use std::collections::BTreeSet;
 use std::iter;
 use std::rc::Rc;
 
+/// enemies within this distance wake up when the player kicks a door
+const KICK_NOISE_RANGE: i32 = 8;
+
 pub(crate) fn process_action(
     action: Action,
     info: &mut GameInfo,
@@ -38,29 +46,98 @@ pub(crate) fn process_action(
             out.append(&mut move_player(d, dungeon, player, enemies)?.0);
             ui = after_turn(player, enemies, dungeon, &mut out)?;
         }
-        Action::MoveUntil(d) => loop {
-            let res = move_player(d, dungeon, player, enemies)?;
-            let tile = dungeon
-                .tile(&player.pos)
-                .map(|t| t.to_char())
-                .unwrap_or(' ');
-            if res.1 || (tile != '.' && tile != '#') {
-                out.extend(res.0);
-                break;
-            } else if out.is_empty() {
-                out.extend(res.0);
+        Action::MoveUntil(mut d) => {
+            let config = player.run_config();
+            let mut visible = visible_enemies(dungeon, player, enemies);
+            for _ in 0..config.max_steps {
+                let res = move_player(d, dungeon, player, enemies)?;
+                let tile = tile_char(dungeon, &player.pos);
+                let found_enemy = config.stop_at_monster
+                    && !visible_enemies(dungeon, player, enemies).is_subset(&visible);
+                let next = if res.1 || (tile != '.' && tile != '#') || found_enemy {
+                    None
+                } else {
+                    run_direction(&config, d, dungeon, player)
+                };
+                match next {
+                    Some(next) => d = next,
+                    None => {
+                        out.extend(res.0);
+                        break;
+                    }
+                }
+                if out.is_empty() {
+                    out.extend(res.0);
+                }
+                ui = after_turn(player, enemies, dungeon, &mut out)?;
+                if ui.is_some() {
+                    break;
+                }
+                visible = visible_enemies(dungeon, player, enemies);
             }
-            ui = after_turn(player, enemies, dungeon, &mut out)?;
-        },
+        }
         Action::Search => {
             out.append(&mut search(dungeon, player)?);
             ui = after_turn(player, enemies, dungeon, &mut out)?;
         }
+        Action::Open(d) | Action::Kick(d) => {
+            let kick = matches!(action, Action::Kick(_));
+            let msg = unlock_door(d, kick, dungeon, player, enemies);
+            out.push(Reaction::Notify(msg.clone()));
+            // it takes no time to find there's no locked door
+            if msg == GameMsg::NoLockedDoor {
+                return Ok((None, out));
+            }
+            ui = after_turn(player, enemies, dungeon, &mut out)?;
+        }
+        Action::Travel(target) => {
+            let route = match dungeon.find_route(&player.pos, target) {
+                Some(route) => route,
+                None => {
+                    out.push(Reaction::Notify(GameMsg::NoRoute));
+                    return Ok((None, out));
+                }
+            };
+            ui = travel(route, dungeon, player, enemies, &mut out)?.0;
+        }
+        Action::Explore => {
+            ui = explore(dungeon, player, enemies, &mut out)?;
+        }
+        Action::Item(a) => {
+            if player.itembox.items().any(|item| a.accepts(item)) {
+                let mordal = UiState::Mordal(MordalKind::SelectItem(a));
+                out.push(Reaction::UiTransition(mordal.clone()));
+                out.push(Reaction::Notify(GameMsg::WhichItem(a)));
+                ui = Some(mordal);
+            } else {
+                out.push(Reaction::Notify(GameMsg::NothingAppropriate));
+            }
+        }
+        Action::UseItem(a, c) => {
+            let msg = use_item(a, c, player);
+            let done = matches!(msg, GameMsg::Equipped { .. });
+            out.push(Reaction::Notify(msg));
+            if done {
+                out.push(Reaction::StatusUpdated);
+                ui = after_turn(player, enemies, dungeon, &mut out)?;
+            }
+        }
         Action::NoOp => return Ok((None, out)),
     }
     Ok((ui, out))
 }
 
+/// judge if `msg` is the usual result of `action`, which doesn't stop repeating it
+pub(crate) fn is_own_message(action: Action, msg: &GameMsg) -> bool {
+    matches!(
+        (action, msg),
+        (Action::Move(_), GameMsg::HitTo(_))
+            | (Action::Move(_), GameMsg::MissTo(_))
+            | (Action::Open(_), GameMsg::FailedToPick)
+            | (Action::Kick(_), GameMsg::KickFailed)
+    )
+}
+
 fn after_turn(
     player: &mut Player,
     enemies: &mut EnemyHandler,
@@ -91,9 +168,14 @@ fn move_active_enemies(
         match fight::enemy_attack(at.enemy(), player, enemies.rng()) {
             Some(hp) => {
                 let name = at.enemy().name();
-                res.push(Reaction::Notify(GameMsg::HitFrom(name.to_owned())));
                 did_hit = true;
-                match player.get_damage(hp) {
+                let reaction = player.get_damage(hp);
+                res.push(Reaction::Notify(GameMsg::HitFrom(Hit {
+                    name: name.to_owned(),
+                    damage: hp,
+                    hp_left: Some(player.hp()),
+                })));
+                match reaction {
                     DamageReaction::Death => {
                         let mordal = UiState::die(format!("Killed by {}", name));
                         res.push(Reaction::UiTransition(mordal.clone()));
@@ -125,7 +207,7 @@ pub(crate) fn new_level(
 ) -> GameResult<()> {
     if !is_init {
         dungeon
-            .new_level(info, item, enemies)
+            .new_level(&player.pos, info, item, enemies)
             .chain_err(|| "action::new_level")?;
     }
     player.pos = dungeon
@@ -144,14 +226,22 @@ fn player_attack(
     player.buttle();
     enemies.activate(place.clone());
     if let Some(hp) = fight::player_attack(player, None, &*enemy, enemies.rng()) {
-        res.push(Reaction::Notify(GameMsg::HitTo(enemy.name().to_owned())));
-        match enemy.get_damage(hp) {
+        let reaction = enemy.get_damage(hp);
+        res.push(Reaction::Notify(GameMsg::HitTo(Hit {
+            name: enemy.name().to_owned(),
+            damage: hp,
+            hp_left: Some(enemy.hp()),
+        })));
+        match reaction {
             DamageReaction::Death => {
                 enemies.remove(place);
                 if player.level_up(enemy.exp(), enemies.rng()) {
                     res.push(Reaction::StatusUpdated);
                 }
-                res.push(Reaction::Notify(GameMsg::Killed(enemy.name().to_owned())));
+                res.push(Reaction::Notify(GameMsg::Killed {
+                    name: enemy.name().to_owned(),
+                    exp: enemy.exp(),
+                }));
                 res.push(Reaction::Redraw);
             }
             DamageReaction::None => {}
@@ -183,6 +273,10 @@ fn move_player(
     player.run(true);
     let mut done = false;
     let mut res = vec![Reaction::Redraw];
+    for msg in dungeon.take_messages() {
+        res.push(Reaction::Notify(msg));
+        done = true;
+    }
     if let Some(msg) = get_item(dungeon, player).chain_err(|| "in actions::move_player")? {
         res.push(Reaction::Notify(msg));
         res.push(Reaction::StatusUpdated);
@@ -191,6 +285,170 @@ fn move_player(
     Ok((res, done))
 }
 
+/// follow `route` one step per turn, until something interesting happens.
+/// returns if the player reached the end of the route or not.
+fn travel(
+    route: Vec<Direction>,
+    dungeon: &mut dyn Dungeon,
+    player: &mut Player,
+    enemies: &mut EnemyHandler,
+    out: &mut Vec<Reaction>,
+) -> GameResult<(Option<UiState>, bool)> {
+    let mut visible = visible_enemies(dungeon, player, enemies);
+    for d in route {
+        match dungeon.can_move_player(&player.pos, d) {
+            Some(ref next) if enemies.get_enemy(next).is_none() => {}
+            _ => return Ok((None, false)),
+        }
+        let (res, done) = move_player(d, dungeon, player, enemies)?;
+        merge_reactions(out, res);
+        let len = out.len();
+        if let Some(ui) = after_turn(player, enemies, dungeon, out)? {
+            return Ok((Some(ui), false));
+        }
+        let notified = is_notified(&out[len..]);
+        let now_visible = visible_enemies(dungeon, player, enemies);
+        if done || notified || !now_visible.is_subset(&visible) {
+            return Ok((None, false));
+        }
+        visible = now_visible;
+    }
+    Ok((None, true))
+}
+
+/// max number of routes and searches in one Explore action
+const EXPLORE_LIMIT: usize = 200;
+
+/// walk to unvisited cells and search dead ends, until something interesting happens
+fn explore(
+    dungeon: &mut dyn Dungeon,
+    player: &mut Player,
+    enemies: &mut EnemyHandler,
+    out: &mut Vec<Reaction>,
+) -> GameResult<Option<UiState>> {
+    for i in 0..EXPLORE_LIMIT {
+        let route = dungeon
+            .find_route(&player.pos, TravelTarget::Unexplored)
+            .or_else(|| dungeon.find_route(&player.pos, TravelTarget::DeadEnd));
+        let route = match route {
+            Some(route) => route,
+            None => {
+                if i == 0 {
+                    out.push(Reaction::Notify(GameMsg::NoRoute));
+                }
+                break;
+            }
+        };
+        if route.is_empty() {
+            // the player is at a dead end
+            let len = out.len();
+            merge_reactions(out, search(dungeon, player)?);
+            if let Some(ui) = after_turn(player, enemies, dungeon, out)? {
+                return Ok(Some(ui));
+            }
+            if is_notified(&out[len..]) {
+                break;
+            }
+            continue;
+        }
+        let (ui, completed) = travel(route, dungeon, player, enemies, out)?;
+        if ui.is_some() || !completed {
+            return Ok(ui);
+        }
+    }
+    Ok(None)
+}
+
+/// append reactions of a step in a macro action, without duplicated redraws
+fn merge_reactions(out: &mut Vec<Reaction>, res: Vec<Reaction>) {
+    for r in res {
+        if r != Reaction::Redraw || !out.contains(&r) {
+            out.push(r);
+        }
+    }
+}
+
+fn is_notified(reactions: &[Reaction]) -> bool {
+    reactions.iter().any(|r| matches!(r, Reaction::Notify(_)))
+}
+
+fn tile_char(dungeon: &mut dyn Dungeon, path: &DungeonPath) -> char {
+    dungeon.tile(path).map(|t| t.to_char()).unwrap_or(' ')
+}
+
+/// the direction to keep running in, or None if the player should stop
+fn run_direction(
+    config: &RunConfig,
+    direction: Direction,
+    dungeon: &mut dyn Dungeon,
+    player: &Player,
+) -> Option<Direction> {
+    let mut passages = Vec::new();
+    for d in Direction::into_enum_iter().take(8) {
+        if d == direction.reverse() {
+            continue;
+        }
+        let next = match dungeon.can_move_player(&player.pos, d) {
+            Some(next) => next,
+            None => continue,
+        };
+        let tile = tile_char(dungeon, &next);
+        if config.stop_at_door && tile == '+' {
+            return None;
+        }
+        if config.stop_at_item && tile != ' ' && dungeon.get_item(&next).is_some() {
+            return None;
+        }
+        if !d.is_diag() && (tile == '#' || tile == '+') {
+            passages.push(d);
+        }
+    }
+    if tile_char(dungeon, &player.pos) != '#' {
+        return Some(direction);
+    }
+    if config.stop_at_fork && passages.len() > 1 {
+        return None;
+    }
+    if config.follow_passage && !passages.contains(&direction) {
+        // turn at the corner, or stop at the dead end
+        return if passages.len() == 1 {
+            Some(passages[0])
+        } else {
+            None
+        };
+    }
+    Some(direction)
+}
+
+/// ids of enemies the player can see, to find one which newly came into view
+fn visible_enemies(
+    dungeon: &dyn Dungeon,
+    player: &Player,
+    enemies: &EnemyHandler,
+) -> BTreeSet<EnemyId> {
+    enemies
+        .paths()
+        .filter(|path| dungeon.draw_enemy(&player.pos, path))
+        .filter_map(|path| enemies.get_enemy(path).map(Enemy::id))
+        .collect()
+}
+
+fn use_item(action: ItemAction, c: char, player: &mut Player) -> GameMsg {
+    let token = match player.itembox.get_by_char(c) {
+        Some(token) => token.clone(),
+        None => return GameMsg::NoSuchItem(c),
+    };
+    if !action.accepts(&token) {
+        return GameMsg::CantUse(action);
+    }
+    if token.attr.is_equiped() {
+        return GameMsg::AlreadyInUse;
+    }
+    let name = SmallStr::from_str(token.name().unwrap_or_default());
+    player.equip(action, token);
+    GameMsg::Equipped { action, name }
+}
+
 fn search(dungeon: &mut dyn Dungeon, player: &mut Player) -> GameResult<Vec<Reaction>> {
     dungeon.search(&player.pos).map(|v| {
         v.into_iter()
@@ -200,6 +458,34 @@ fn search(dungeon: &mut dyn Dungeon, player: &mut Player) -> GameResult<Vec<Reac
     })
 }
 
+fn unlock_door(
+    direction: Direction,
+    kick: bool,
+    dungeon: &mut dyn Dungeon,
+    player: &mut Player,
+    enemies: &mut EnemyHandler,
+) -> GameMsg {
+    let st = player.strength().current.0;
+    let chance = if kick {
+        Parcent::truncate(5 * st - 30)
+    } else {
+        Parcent::truncate(2 * st + 10)
+    };
+    let res = dungeon.unlock_door(&player.pos, direction, chance);
+    if kick && res.is_some() {
+        // kicking a door makes noise
+        let cd = dungeon.path_to_cd(&player.pos);
+        enemies.activate_area(|p| dungeon.path_to_cd(p).move_dist(cd) <= KICK_NOISE_RANGE);
+    }
+    match res {
+        None => GameMsg::NoLockedDoor,
+        Some(true) if kick => GameMsg::KickedOpen,
+        Some(false) if kick => GameMsg::KickFailed,
+        Some(true) => GameMsg::PickedLock,
+        Some(false) => GameMsg::FailedToPick,
+    }
+}
+
 fn get_item(dungeon: &mut dyn Dungeon, player: &mut Player) -> GameResult<Option<GameMsg>> {
     macro_rules! try_or_ok {
         ($res: expr) => {
@@ -226,3 +512,69 @@ fn get_item(dungeon: &mut dyn Dungeon, player: &mut Player) -> GameResult<Option
         num: got_item.how_many.0,
     }))
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::dungeon::custom::{test_config, TEST_MAP};
+    use crate::dungeon::{Coord, DungeonStyle};
+    use crate::input::{InputCode, Key};
+    use crate::RunTime;
+    #[test]
+    fn run_rules() {
+        let map: &[&str] = &[
+            "----------",
+            "|@.......+###",
+            "|........|  #",
+            "----------  #",
+        ];
+        let run = |rogue: bool, keys: &str| {
+            let mut config = test_config(&[map]);
+            if rogue {
+                config.player.run = RunConfig::rogue();
+            }
+            let mut runtime: RunTime = config.build().unwrap();
+            keys.chars()
+                .map(|c| {
+                    runtime.react_to_key(Key::Char(c)).unwrap();
+                    runtime.dungeon.path_to_cd(&runtime.player.pos)
+                })
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(run(false, "LL"), vec![Coord::new(9, 2), Coord::new(12, 2)]);
+        assert_eq!(
+            run(true, "LLL"),
+            vec![Coord::new(8, 2), Coord::new(9, 2), Coord::new(12, 4)]
+        );
+    }
+    #[test]
+    fn run_stops_at_new_monster() {
+        // one emu goes out of sight when the other comes into sight
+        let map: &[&str] = &["----------", "|.@......|", "|E..E....|", "----------"];
+        let mut config = test_config(&[map]);
+        if let DungeonStyle::Custom(ref mut custom) = config.dungeon {
+            custom.dark = true;
+            custom.wake_range = 0;
+        }
+        config.player.run.stop_at_monster = true;
+        let mut runtime: RunTime = config.build().unwrap();
+        runtime.react_to_key(Key::Char('L')).unwrap();
+        let pos = runtime.dungeon.path_to_cd(&runtime.player.pos);
+        assert_eq!(pos, Coord::new(3, 2));
+    }
+    #[test]
+    fn travel() {
+        let mut runtime: RunTime = test_config(&[TEST_MAP]).build().unwrap();
+        let travel = InputCode::Act(Action::Travel(TravelTarget::DownStair));
+        for _ in 0..20 {
+            if runtime.dungeon.is_downstair(&runtime.player.pos) {
+                let pos = runtime.dungeon.path_to_cd(&runtime.player.pos);
+                assert_eq!(pos, Coord::new(10, 3));
+                return;
+            }
+            let res = runtime.react_to_input(travel).unwrap();
+            assert!(!res.contains(&Reaction::Notify(GameMsg::NoRoute)));
+        }
+        panic!("Couldn't reach the downstair");
+    }
+}
@@ -1,11 +1,13 @@
 use super::{DamageReaction, Defense, Dice, Exp, HitPoint, Level, Strength};
 use crate::{Drawable, SmallStr};
 use dungeon::{Dungeon, DungeonPath, MoveResult};
+use hash::{HashState, StateHasher};
 use item::ItemNum;
 use rng::{Parcent, RngHandle};
 use smallvec::SmallVec;
 use std::cell::Cell;
 use std::collections::BTreeMap;
+use std::hash::Hasher;
 use std::ops::Range;
 use std::rc::{Rc, Weak};
 use tile::Tile;
@@ -168,6 +170,15 @@ pub struct Enemy {
     tile: Tile,
 }
 
+impl HashState for Enemy {
+    fn hash_state(&self, hasher: &mut StateHasher) {
+        hasher.write_u32(self.id.0);
+        hasher.write_i64(self.hp.get().0);
+        hasher.write_u16(self.attr.get().0);
+        hasher.write_u8(self.running.get() as u8);
+    }
+}
+
 impl Enemy {
     pub(crate) const STRENGTH: Strength = Strength(10);
     pub fn is_mean(&self) -> bool {
@@ -191,6 +202,9 @@ impl Enemy {
     pub fn defense(&self) -> Defense {
         self.defense
     }
+    pub fn id(&self) -> EnemyId {
+        self.id
+    }
     pub fn exp(&self) -> Exp {
         self.exp
     }
@@ -200,12 +214,16 @@ impl Enemy {
     pub fn name(&self) -> &SmallStr {
         &self.name
     }
+    pub fn hp(&self) -> HitPoint {
+        self.hp.get()
+    }
     pub fn get_damage(&self, damage: HitPoint) -> DamageReaction {
         let cur = self.hp.get();
         if cur <= damage {
+            self.hp.replace(HitPoint(0));
             DamageReaction::Death
         } else {
-            self.hp.replace(damage - cur);
+            self.hp.replace(cur - damage);
             DamageReaction::None
         }
     }
@@ -238,6 +256,20 @@ pub struct EnemyHandler {
     next_id: EnemyId,
 }
 
+impl HashState for EnemyHandler {
+    fn hash_state(&self, hasher: &mut StateHasher) {
+        for enemies in &[&self.placed_enemies, &self.active_enemies] {
+            hasher.write_usize(enemies.len());
+            enemies.iter().for_each(|(path, enemy)| {
+                path.hash_state(hasher);
+                enemy.hash_state(hasher);
+            });
+        }
+        hasher.write_u32(self.next_id.0);
+        self.rng.hash_state(hasher);
+    }
+}
+
 impl EnemyHandler {
     fn new(mut stats: Vec<Status>, rng: RngHandle, config: ConfigInner) -> Self {
         stats.sort_by_key(|stat| stat.rarelity);
@@ -289,7 +321,23 @@ impl EnemyHandler {
         if !self.rng.parcent(appear_parcent) {
             return None;
         }
+        self.gen_enemy_in(range, lev_add)
+    }
+    /// generate an enemy without randomness of appearance
+    pub fn gen_enemy_in(&mut self, range: Range<u32>, lev_add: i64) -> Option<Rc<Enemy>> {
         let idx = self.select(range);
+        self.spawn(idx, lev_add)
+    }
+    /// generate an enemy whose tile is `tile`
+    pub fn gen_enemy_by_tile(&mut self, tile: Tile, lev_add: i64) -> Option<Rc<Enemy>> {
+        let idx = self.enemy_stats.iter().position(|stat| stat.tile == tile)?;
+        self.spawn(idx, lev_add)
+    }
+    /// if an enemy with `tile` is registered or not
+    pub fn has_enemy_tile(&self, tile: Tile) -> bool {
+        self.enemy_stats.iter().any(|stat| stat.tile == tile)
+    }
+    fn spawn(&mut self, idx: usize, lev_add: i64) -> Option<Rc<Enemy>> {
         let stat = self.enemy_stats.get(idx)?;
         let level = stat.level + lev_add.into();
         let hp = Dice::new(8, level).exec::<i64>(&mut self.rng).0.into();
@@ -331,6 +379,10 @@ impl EnemyHandler {
             .or_else(|| self.active_enemies.get(&path))
             .map(Rc::clone)
     }
+    /// positions of all enemies on the current level
+    pub fn paths(&self) -> impl Iterator<Item = &DungeonPath> {
+        self.placed_enemies.keys().chain(self.active_enemies.keys())
+    }
     pub fn activate_area<'a, F>(&mut self, is_in_activation_area: F)
     where
         F: Fn(&DungeonPath) -> bool,
@@ -1,7 +1,19 @@
 use super::{Damage, Defense, Dice, Enemy, HitPoint, Level, Player, Strength};
 use crate::rng::{Parcent, RngHandle};
 use item::ItemToken;
-use std::iter;
+use smallstr::SmallStr;
+use std::{cmp, iter};
+
+/// details of an attack which hit
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Hit {
+    /// name of the enemy
+    pub name: SmallStr,
+    /// damage dealt
+    pub damage: HitPoint,
+    /// HP left of the attacked one, if known
+    pub hp_left: Option<HitPoint>,
+}
 
 pub fn player_attack(
     player: &Player,
@@ -62,7 +74,8 @@ fn roll<'a>(
             continue;
         }
         did_hit = true;
-        sum += dice.random(rng) + dam_plus;
+        // negative bonus never heals
+        sum += cmp::max(dice.random(rng) + dam_plus, HitPoint(0));
     }
     if did_hit {
         Some(sum)
@@ -107,3 +120,58 @@ fn damage_plus(strength: Strength) -> HitPoint {
     }
     DATA[strength.0 as usize - 1].into()
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use dungeon::custom::{test_config, TEST_MAP};
+    use input::Key;
+    use {GameMsg, Reaction, RunTime};
+    #[test]
+    fn negative_bonus() {
+        let mut rng = RngHandle::from_seed(1);
+        let dices = [Dice::new(1, HitPoint(2)), Dice::new(1, HitPoint(2))];
+        for _ in 0..100 {
+            // a negative bonus makes each dice deal no damage, not heal the target
+            let sum = roll(dices.iter(), Parcent(100), HitPoint(-5), &mut rng);
+            assert_eq!(sum, Some(HitPoint(0)));
+        }
+        assert_eq!(roll(dices.iter(), Parcent(0), HitPoint(0), &mut rng), None);
+    }
+    #[test]
+    fn combat_details() {
+        let map = [TEST_MAP[0], "|@E........|", TEST_MAP[2], TEST_MAP[3]];
+        let mut runtime: RunTime = test_config(&[&map]).build().unwrap();
+        let mut enemy_hp = None;
+        let mut killed = false;
+        for _ in 0..100 {
+            for r in runtime.react_to_key(Key::Char('l')).unwrap() {
+                match r {
+                    Reaction::Notify(GameMsg::HitTo(hit)) => {
+                        assert_eq!(hit.name, "emu");
+                        assert!(hit.damage.0 >= 0);
+                        let left = hit.hp_left.unwrap();
+                        if let Some(prev) = enemy_hp {
+                            assert_eq!(left, cmp::max(prev - hit.damage, HitPoint(0)));
+                        }
+                        enemy_hp = Some(left);
+                    }
+                    Reaction::Notify(GameMsg::HitFrom(hit)) => {
+                        assert_eq!(hit.hp_left, Some(runtime.player.hp()));
+                    }
+                    Reaction::Notify(GameMsg::Killed { name, exp }) => {
+                        assert_eq!(name, "emu");
+                        assert!(exp.0 > 0);
+                        assert_eq!(enemy_hp, Some(HitPoint(0)));
+                        killed = true;
+                    }
+                    _ => {}
+                }
+            }
+            if killed {
+                break;
+            }
+        }
+        assert!(killed);
+    }
+}
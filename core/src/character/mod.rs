@@ -1,8 +1,8 @@
 pub mod enemies;
 pub mod fight;
 pub mod player;
-pub use self::player::{Action, Hunger, Leveling, Player};
-pub use enemies::{Enemy, EnemyHandler};
+pub use self::player::{Action, Hunger, ItemAction, Leveling, Player};
+pub use enemies::{Enemy, EnemyHandler, EnemyId};
 use num_traits::PrimInt;
 use rand::distributions::uniform::SampleUniform;
 use rng::RngHandle;
@@ -282,4 +282,19 @@ mod test {
             assert!(2 <= hp.0 && hp.0 <= 8);
         }
     }
+    #[test]
+    fn test_enemy_damage() {
+        let mut handler = enemies::Config::default().build(1);
+        let enemy = (0..100)
+            .filter_map(|_| handler.gen_enemy_in(0..10, 0))
+            .find(|enemy| enemy.hp() > HitPoint(1))
+            .unwrap();
+        let hp = enemy.hp();
+        // damage is subtracted from the current HP
+        let res = enemy.get_damage(HitPoint(1));
+        assert!(matches!(res, DamageReaction::None));
+        assert_eq!(enemy.hp(), hp - HitPoint(1));
+        assert!(matches!(enemy.get_damage(hp), DamageReaction::Death));
+        assert_eq!(enemy.hp(), HitPoint(0));
+    }
 }
@@ -1,12 +1,14 @@
 use super::{clamp, DamageReaction, Defense, Dice, Exp, HitPoint, Level, Maxed, Strength};
-use crate::dungeon::{Direction, DungeonPath};
+use crate::dungeon::{Direction, DungeonPath, TravelTarget};
 use crate::error::GameResult;
+use crate::hash::{HashState, StateHasher};
 use crate::item::{
     armor, food::Food, itembox::ItemBox, weapon, InitItem, Item, ItemHandler, ItemKind, ItemToken,
 };
 use crate::rng::RngHandle;
 use crate::tile::{Drawable, Tile};
 use smallstr::SmallStr;
+use std::hash::{Hash, Hasher};
 use std::{cmp, fmt};
 use tuple_map::TupleMap2;
 
@@ -27,6 +29,9 @@ pub struct Config {
     pub init_items: Vec<InitItem>,
     #[serde(default = "default_heal_threshold")]
     pub heal_threshold: u32,
+    /// rules of running(MoveUntil)
+    #[serde(default)]
+    pub run: RunConfig,
 }
 
 impl Default for Config {
@@ -39,6 +44,7 @@ impl Default for Config {
             max_items: default_max_items(),
             init_items: default_init_items(),
             heal_threshold: default_heal_threshold(),
+            run: RunConfig::default(),
         }
     }
 }
@@ -72,6 +78,61 @@ fn default_init_items() -> Vec<InitItem> {
     res
 }
 
+/// When the player stops running.
+/// By default, the player runs straight until the cell under the player isn't a floor or a passage.
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, Eq, PartialEq)]
+pub struct RunConfig {
+    /// turn at corners of passages
+    #[serde(default)]
+    pub follow_passage: bool,
+    /// stop at forks of passages
+    #[serde(default)]
+    pub stop_at_fork: bool,
+    /// stop beside doors
+    #[serde(default)]
+    pub stop_at_door: bool,
+    /// stop beside items
+    #[serde(default)]
+    pub stop_at_item: bool,
+    /// stop when a monster comes into view
+    #[serde(default)]
+    pub stop_at_monster: bool,
+    /// max number of steps in one run
+    #[serde(default = "default_max_run_steps")]
+    pub max_steps: u32,
+}
+
+impl Default for RunConfig {
+    fn default() -> Self {
+        RunConfig {
+            follow_passage: false,
+            stop_at_fork: false,
+            stop_at_door: false,
+            stop_at_item: false,
+            stop_at_monster: false,
+            max_steps: default_max_run_steps(),
+        }
+    }
+}
+
+impl RunConfig {
+    /// rules same as rogue
+    pub fn rogue() -> Self {
+        RunConfig {
+            follow_passage: true,
+            stop_at_fork: true,
+            stop_at_door: true,
+            stop_at_item: true,
+            stop_at_monster: true,
+            max_steps: default_max_run_steps(),
+        }
+    }
+}
+
+const fn default_max_run_steps() -> u32 {
+    200
+}
+
 impl Config {
     pub fn build(self) -> Player {
         let status = StatusInner::from_config(&self);
@@ -101,7 +162,20 @@ pub struct Player {
     config: Config,
 }
 
+impl HashState for Player {
+    fn hash_state(&self, hasher: &mut StateHasher) {
+        self.pos.hash_state(hasher);
+        self.itembox.hash_state(hasher);
+        self.armor.as_ref().map(ItemToken::id).hash(hasher);
+        self.weapon.as_ref().map(ItemToken::id).hash(hasher);
+        self.status.hash_state(hasher);
+    }
+}
+
 impl Player {
+    pub fn run_config(&self) -> RunConfig {
+        self.config.run
+    }
     pub fn fill_status(&self, status: &mut Status) {
         status.hp = self.status.hp;
         status.strength = self.status.strength;
@@ -131,6 +205,25 @@ impl Player {
     pub fn weapon(&self) -> Option<&ItemToken> {
         self.weapon.as_ref()
     }
+    pub fn hp(&self) -> HitPoint {
+        self.status.hp.current
+    }
+    /// turns passed since the game started
+    pub fn turns(&self) -> u32 {
+        self.status.turns
+    }
+    /// equip `token` as weapon or armor, and take off the old one
+    pub(crate) fn equip(&mut self, action: ItemAction, mut token: ItemToken) {
+        let slot = match action {
+            ItemAction::Wear => &mut self.armor,
+            ItemAction::Wield => &mut self.weapon,
+        };
+        if let Some(mut old) = slot.take() {
+            old.get_mut().attr.unequip();
+        }
+        token.get_mut().attr.equip();
+        *slot = Some(token);
+    }
     pub fn init_items(&mut self, items: &mut ItemHandler) -> GameResult<()> {
         items.init_player_items(&mut self.itembox, &self.config.init_items)?;
         if let Some(name) = self.get_initial_weapon() {
@@ -160,6 +253,7 @@ impl Player {
     }
     pub(crate) fn turn_passed(&mut self, rng: &mut RngHandle) -> Vec<PlayerEvent> {
         let mut res = vec![];
+        self.status.turns += 1;
         self.status.food_left -= 1;
         if self.status.food_left == 0 {
             return vec![PlayerEvent::Dead];
@@ -269,6 +363,23 @@ struct StatusInner {
     food_left: u32,
     running: bool,
     quiet: u32,
+    /// turns passed since the game started
+    turns: u32,
+}
+
+impl HashState for StatusInner {
+    fn hash_state(&self, hasher: &mut StateHasher) {
+        hasher.write_i64(self.hp.current.0);
+        hasher.write_i64(self.hp.max.0);
+        hasher.write_i64(self.strength.current.0);
+        hasher.write_i64(self.strength.max.0);
+        hasher.write_u32(self.exp.0);
+        hasher.write_i64(self.level.0);
+        hasher.write_u32(self.food_left);
+        hasher.write_u8(self.running as u8);
+        hasher.write_u32(self.quiet);
+        hasher.write_u32(self.turns);
+    }
 }
 
 impl StatusInner {
@@ -281,6 +392,7 @@ impl StatusInner {
             food_left: config.hunger_time,
             running: false,
             quiet: 0,
+            turns: 0,
         }
     }
 }
@@ -293,9 +405,47 @@ pub enum Action {
     UpStair,
     DownStair,
     Search,
+    /// try to pick the lock of the door
+    Open(Direction),
+    /// try to kick open the door, which is noisy
+    Kick(Direction),
+    /// go to the target, one step per turn
+    Travel(TravelTarget),
+    /// walk around unvisited cells and search dead ends
+    Explore,
+    /// ask which item to use
+    Item(ItemAction),
+    /// use the item in the pack specified by the letter
+    UseItem(ItemAction, char),
     NoOp,
 }
 
+/// actions which need an item in the pack
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, Eq, PartialEq, Hash)]
+pub enum ItemAction {
+    Wear,
+    Wield,
+}
+
+impl ItemAction {
+    /// judge if `item` can be used for this action
+    pub fn accepts(self, item: &Item) -> bool {
+        match self {
+            ItemAction::Wear => matches!(item.kind, ItemKind::Armor(_)),
+            ItemAction::Wield => matches!(item.kind, ItemKind::Weapon(_)),
+        }
+    }
+}
+
+impl fmt::Display for ItemAction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ItemAction::Wear => write!(f, "wear"),
+            ItemAction::Wield => write!(f, "wield"),
+        }
+    }
+}
+
 #[derive(Clone, Debug, Serialize, Deserialize, Eq, PartialEq)]
 pub struct Leveling {
     /// necesarry exp for level up
@@ -0,0 +1,898 @@
+//! cave-like dungeon generated by cellular automata
+use super::rogue::{Address, Surface};
+use super::route;
+use super::{
+    Cell, CellAttr, Coord, Direction, Dungeon as DungeonTrait, DungeonPath, Field, MoveResult,
+    Positioned, TravelTarget, X, Y,
+};
+use character::{player::Status as PlayerStatus, EnemyHandler};
+use enum_iterator::IntoEnumIterator;
+use error::*;
+use fenwick::FenwickSet;
+use hash::{self, HashState, PastFloorsHash, StateHasher};
+use item::{ItemHandler, ItemToken};
+use ndarray::Array2;
+use rect_iter::{Get2D, GetMut2D, RectRange};
+use rng::{Parcent, RngHandle};
+use std::collections::{HashMap, VecDeque};
+use std::hash::Hasher;
+use tile::{Drawable, Tile};
+use tuple_map::TupleMap2;
+use {GameInfo, GameMsg, GlobalConfig};
+
+#[derive(Clone, Debug, Serialize, Deserialize, Eq, PartialEq)]
+pub struct Config {
+    /// initial probability(%) that a cell is filled with rock
+    #[serde(default = "default_wall_rate")]
+    pub wall_rate: u32,
+    /// number of smoothing steps of the cellular automata
+    #[serde(default = "default_smoothing")]
+    pub smoothing: u32,
+    /// maximum number of gold placed in a floor
+    #[serde(default = "default_max_golds")]
+    pub max_golds: u32,
+    /// maximum number of enemies placed in a floor
+    #[serde(default = "default_max_enemies")]
+    pub max_enemies: u32,
+    /// sleeping enemies within this distance from the player wake up
+    #[serde(default = "default_wake_range")]
+    pub wake_range: u32,
+    /// the level where the Amulet of Yendor is
+    #[serde(default = "default_amulet_level")]
+    pub amulet_level: u32,
+}
+
+const fn default_wall_rate() -> u32 {
+    45
+}
+
+const fn default_smoothing() -> u32 {
+    4
+}
+
+const fn default_max_golds() -> u32 {
+    6
+}
+
+const fn default_max_enemies() -> u32 {
+    6
+}
+
+const fn default_wake_range() -> u32 {
+    3
+}
+
+const fn default_amulet_level() -> u32 {
+    25
+}
+
+impl Default for Config {
+    fn default() -> Config {
+        Config {
+            wall_rate: default_wall_rate(),
+            smoothing: default_smoothing(),
+            max_golds: default_max_golds(),
+            max_enemies: default_max_enemies(),
+            wake_range: default_wake_range(),
+            amulet_level: default_amulet_level(),
+        }
+    }
+}
+
+/// representation of cave dungeon
+#[derive(Clone)]
+pub struct Dungeon {
+    /// current level
+    pub level: u32,
+    /// amulet level or more deeper level the player visited
+    pub max_level: u32,
+    /// current floor
+    pub current_floor: Floor,
+    /// dungeon specific configuration(constant)
+    pub config: Config,
+    /// global configuration(constant)
+    pub config_global: GlobalConfig,
+    /// past floors
+    pub past_floors: Vec<Floor>,
+    /// hash of past floors
+    past_hash: PastFloorsHash,
+    /// random number generator
+    pub rng: RngHandle,
+}
+
+impl HashState for Dungeon {
+    fn hash_state(&self, hasher: &mut StateHasher) {
+        hasher.write_u32(self.level);
+        self.current_floor.hash_state(hasher);
+        self.past_hash.hash_state(hasher);
+        self.rng.hash_state(hasher);
+    }
+}
+
+impl DungeonTrait for Dungeon {
+    fn is_downstair(&self, path: &DungeonPath) -> bool {
+        let address = Address::from_path(path);
+        if address.level != self.level {
+            return false;
+        }
+        if let Ok(cell) = self.current_floor.field.try_get_p(address.cd) {
+            cell.surface == Surface::Stair
+        } else {
+            false
+        }
+    }
+    fn level(&self) -> u32 {
+        self.level
+    }
+    fn new_level(
+        &mut self,
+        _stair: &DungeonPath,
+        game_info: &GameInfo,
+        item: &mut ItemHandler,
+        enemies: &mut EnemyHandler,
+    ) -> GameResult<()> {
+        self.new_level_(game_info, item, enemies, false)
+    }
+    fn can_move_player(&self, path: &DungeonPath, direction: Direction) -> Option<DungeonPath> {
+        let address = Address::from_path(path);
+        if address.level != self.level || !self.current_floor.can_move(address.cd, direction) {
+            return None;
+        }
+        let cd = address.cd + direction.to_cd();
+        Some(Address::new(address.level, cd).into())
+    }
+    fn move_player(
+        &mut self,
+        path: &DungeonPath,
+        direction: Direction,
+        enemies: &mut EnemyHandler,
+    ) -> GameResult<DungeonPath> {
+        let address = Address::from_path(path);
+        const ERR_STR: &str = "[cave::Dungeon::move_player]";
+        if address.level != self.level {
+            return Err(ErrorId::MaybeBug.into_with(|| ERR_STR));
+        }
+        self.current_floor.player_out(address.cd);
+        let cd = address.cd + direction.to_cd();
+        self.current_floor.player_in(cd).chain_err(|| ERR_STR)?;
+        let address = Address::new(self.level, cd);
+        wake_enemies(enemies, address, self.config.wake_range);
+        Ok(address.into())
+    }
+    fn search(&mut self, path: &DungeonPath) -> GameResult<Vec<GameMsg>> {
+        let address = Address::from_path(path);
+        if address.level != self.level {
+            return Err(ErrorId::MaybeBug.into_with(|| "[cave::Dungeon::search]"));
+        }
+        // caves have no hidden passages
+        Ok(vec![])
+    }
+    fn unlock_door(
+        &mut self,
+        _path: &DungeonPath,
+        _d: Direction,
+        _chance: Parcent,
+    ) -> Option<bool> {
+        // caves have no doors
+        None
+    }
+    fn find_route(&mut self, path: &DungeonPath, target: TravelTarget) -> Option<Vec<Direction>> {
+        let address = Address::from_path(path);
+        if address.level != self.level {
+            return None;
+        }
+        self.current_floor.find_route(address.cd, target)
+    }
+    fn select_cell(&mut self, is_character: bool) -> Option<DungeonPath> {
+        self.current_floor
+            .select_cell(&mut self.rng, is_character)
+            .map(|cd| Address::new(self.level, cd).into())
+    }
+    fn enter_room(&mut self, path: &DungeonPath, enemies: &mut EnemyHandler) -> GameResult<()> {
+        let address = Address::from_path(path);
+        self.current_floor
+            .player_in(address.cd)
+            .chain_err(|| "[cave::Dungeon::enter_room]")?;
+        wake_enemies(enemies, address, self.config.wake_range);
+        Ok(())
+    }
+    fn take_messages(&mut self) -> Vec<GameMsg> {
+        vec![]
+    }
+    fn draw(&self, drawer: &mut dyn FnMut(Positioned<Tile>) -> GameResult<()>) -> GameResult<()> {
+        self.current_floor
+            .draw(drawer)
+            .chain_err(|| "in cave::Dungeon::draw")
+    }
+    fn draw_ranges(&self) -> Vec<DungeonPath> {
+        self.current_floor.draw_ranges(self.level)
+    }
+    fn path_to_cd(&self, path: &DungeonPath) -> Coord {
+        Coord::new(path.0[1], path.0[2])
+    }
+    fn get_item(&self, path: &DungeonPath) -> Option<&ItemToken> {
+        let addr = Address::from_path(path);
+        if addr.level != self.level {
+            return None;
+        }
+        self.current_floor.items.get(&addr.cd)
+    }
+    fn remove_item(&mut self, path: &DungeonPath) -> Option<ItemToken> {
+        let addr = Address::from_path(path);
+        if addr.level != self.level {
+            return None;
+        }
+        if !self.current_floor.unfill_cell(addr.cd, false) {
+            return None;
+        }
+        self.current_floor.items.remove(&addr.cd)
+    }
+    fn tile(&mut self, path: &DungeonPath) -> Option<Tile> {
+        let cd = self.path_to_cd(path);
+        self.current_floor
+            .field
+            .try_get_p(cd)
+            .ok()
+            .map(|s| s.tile())
+    }
+    fn get_history(&self, status: &PlayerStatus) -> Option<Array2<bool>> {
+        let level = status.dungeon_level;
+        if level == self.level {
+            Some(self.current_floor.history_map())
+        } else {
+            let idx = level.checked_sub(self.config_global.start_level)?;
+            self.past_floors.get(idx as usize).map(Floor::history_map)
+        }
+    }
+    fn move_enemy(
+        &mut self,
+        current: &DungeonPath,
+        dist: &DungeonPath,
+        skip: &dyn Fn(&DungeonPath) -> bool,
+    ) -> MoveResult {
+        let (cur, dist) = (current, dist).map(Address::from_path);
+        if cur.level != dist.level {
+            return MoveResult::CantMove;
+        }
+        self.current_floor.move_enemy(cur, dist.cd, skip)
+    }
+    fn move_enemy_randomly(
+        &mut self,
+        enemy_pos: &DungeonPath,
+        player_pos: &DungeonPath,
+        skip: &dyn Fn(&DungeonPath) -> bool,
+    ) -> MoveResult {
+        let cur = Address::from_path(enemy_pos);
+        self.current_floor
+            .move_enemy_randomly(cur, player_pos, skip, &mut self.rng)
+    }
+    fn draw_enemy(&self, player: &DungeonPath, enemy: &DungeonPath) -> bool {
+        let (p, e) = (player, enemy).map(Address::from_path);
+        // caves are dark, so the player can see only adjacent enemies
+        p.level == e.level && p.cd.is_adjacent(e.cd)
+    }
+}
+
+impl Dungeon {
+    /// make new dungeon
+    pub fn new(
+        config: Config,
+        config_global: &GlobalConfig,
+        game_info: &GameInfo,
+        item_handle: &mut ItemHandler,
+        enemies: &mut EnemyHandler,
+        seed: u128,
+    ) -> GameResult<Self> {
+        let mut dungeon = Dungeon {
+            level: config_global.start_level - 1,
+            max_level: config.amulet_level,
+            current_floor: Floor::default(),
+            config,
+            config_global: config_global.clone(),
+            past_floors: vec![],
+            past_hash: PastFloorsHash::default(),
+            rng: RngHandle::from_seed(seed),
+        };
+        dungeon
+            .new_level_(game_info, item_handle, enemies, true)
+            .chain_err(|| "cave::Dungeon::new")?;
+        Ok(dungeon)
+    }
+
+    fn new_level_(
+        &mut self,
+        game_info: &GameInfo,
+        item_handle: &mut ItemHandler,
+        enemies: &mut EnemyHandler,
+        is_initial: bool,
+    ) -> GameResult<()> {
+        const ERR_STR: &str = "in cave::Dungeon::new_level";
+        self.level += 1;
+        let level = self.level;
+        if level > self.max_level {
+            self.max_level = level;
+        }
+        if let Some(seed) = self.config_global.level_seed(0, level) {
+            self.rng.reseed(seed);
+            item_handle.rng().reseed(seed);
+            enemies.rng().reseed(seed);
+        }
+        let (width, height) = (self.config_global.width, self.config_global.height);
+        let mut floor =
+            Floor::gen_floor(&self.config, width, height, &mut self.rng).chain_err(|| ERR_STR)?;
+        debug!("[cave::Dungeon::new_level] field: {}", floor.field);
+        let set_gold = !game_info.is_cleared || level >= self.max_level;
+        if set_gold {
+            floor.setup_items(level, self.config.max_golds, item_handle, &mut self.rng);
+        }
+        floor.setup_stair(&mut self.rng).chain_err(|| ERR_STR)?;
+        if !is_initial {
+            enemies.remove_enemies();
+        }
+        self.place_enemies(&mut floor, enemies);
+        if !self.config_global.hide_dungeon {
+            floor.field.size().into_iter().for_each(|cd| {
+                floor.field.get_mut_p(cd).visible(true);
+            });
+        }
+        ::std::mem::swap(&mut self.current_floor, &mut floor);
+        if !is_initial {
+            self.past_hash.push(&floor);
+            self.past_floors.push(floor);
+        }
+        Ok(())
+    }
+
+    fn place_enemies(&mut self, floor: &mut Floor, enemies: &mut EnemyHandler) {
+        if enemies.is_no_enemy() {
+            return;
+        }
+        let level = self.level;
+        let lev_add = level.saturating_sub(self.config.amulet_level);
+        let range = level.saturating_sub(4)..level + 6;
+        for _ in 0..self.config.max_enemies {
+            let cd = match floor.select_cell(&mut self.rng, true) {
+                Some(cd) => cd,
+                None => return,
+            };
+            if let Some(enemy) = enemies.gen_enemy(range.clone(), i64::from(lev_add), false) {
+                enemies.place(Address::new(level, cd).into(), enemy);
+                floor.fill_cell(cd, true);
+            }
+        }
+    }
+}
+
+/// wake up sleeping enemies around the player
+pub(crate) fn wake_enemies(enemies: &mut EnemyHandler, player: Address, range: u32) {
+    enemies.activate_area(|p| {
+        let addr = Address::from_path(p);
+        addr.level == player.level && addr.cd.move_dist(player.cd) <= range as i32
+    });
+}
+
+/// representation of cave floor
+#[derive(Clone, Debug, Default)]
+pub struct Floor {
+    /// field (level map)
+    pub field: Field<Surface>,
+    /// items
+    pub items: HashMap<Coord, ItemToken>,
+    /// cells which has no object
+    empty_cells: FenwickSet,
+    /// cells which has no enemy
+    nocharacter_cells: FenwickSet,
+}
+
+impl HashState for Floor {
+    fn hash_state(&self, hasher: &mut StateHasher) {
+        self.field.hash_state(hasher);
+        hash::hash_map(self.items.iter(), hasher);
+    }
+}
+
+impl Floor {
+    /// make a floor from a field, where objects can be placed on floors and passages
+    pub(crate) fn new(field: Field<Surface>) -> Self {
+        let (w, h) = (field.width().0, field.height().0);
+        let mut empty_cells = FenwickSet::with_capacity((w * h) as usize);
+        field.size().into_iter().for_each(|cd| {
+            match field.get_p(cd).surface {
+                Surface::Floor | Surface::Passage => {
+                    empty_cells.insert((cd.1 * w + cd.0) as usize);
+                }
+                _ => {}
+            };
+        });
+        Floor {
+            field,
+            items: HashMap::new(),
+            nocharacter_cells: empty_cells.clone(),
+            empty_cells,
+        }
+    }
+
+    /// generate a new floor without items
+    pub fn gen_floor(
+        config: &Config,
+        width: X,
+        height: Y,
+        rng: &mut RngHandle,
+    ) -> GameResult<Self> {
+        // if the largest cave is smaller than inner_area / MIN_SPACE_RATE_INV, retry
+        const MIN_SPACE_RATE_INV: usize = 8;
+        const MAX_TRIAL: usize = 100;
+        let inner = RectRange::from_ranges(1..width.0 - 1, 2..height.0 - 2)
+            .ok_or_else(|| ErrorId::InvalidSetting.into_with(|| "[cave] too small dungeon"))?;
+        for _ in 0..MAX_TRIAL {
+            let mut rocks = RockMap::random(width, height, inner.clone(), config.wall_rate, rng);
+            (0..config.smoothing).for_each(|_| rocks.smooth());
+            if rocks.connect_regions() * MIN_SPACE_RATE_INV >= inner.len() {
+                return Ok(rocks.into_floor());
+            }
+        }
+        Err(ErrorId::InvalidSetting.into_with(|| "[cave] failed to dig cave: too high wall_rate"))
+    }
+
+    /// place gold
+    pub fn setup_items(
+        &mut self,
+        level: u32,
+        max_golds: u32,
+        item_handle: &mut ItemHandler,
+        rng: &mut RngHandle,
+    ) {
+        for _ in 0..max_golds {
+            let cd = match self.select_cell(rng, false) {
+                Some(cd) => cd,
+                None => return,
+            };
+            if let Some(gold) = item_handle.setup_gold(level) {
+                self.fill_cell(cd, false);
+                self.items.insert(cd, gold);
+            }
+        }
+    }
+
+    /// set stair
+    pub fn setup_stair(&mut self, rng: &mut RngHandle) -> GameResult<()> {
+        let cd = self
+            .select_cell(rng, false)
+            .ok_or_else(|| ErrorId::MaybeBug.into_with(|| "[setup stair] no empty cell!"))?;
+        self.field
+            .try_get_mut_p(cd)
+            .into_chained(|| "[setup stair] select_cell returned invalid coord")?
+            .surface = Surface::Stair;
+        self.fill_cell(cd, false);
+        Ok(())
+    }
+
+    fn cell_id(&self, cd: Coord) -> Option<usize> {
+        let (w, h) = (self.field.width().0, self.field.height().0);
+        if cd.x.0 < 0 || cd.y.0 < 0 || cd.x.0 >= w || cd.y.0 >= h {
+            return None;
+        }
+        Some((cd.y.0 * w + cd.x.0) as usize)
+    }
+
+    fn id_to_cd(&self, id: usize) -> Coord {
+        let w = self.field.width().0 as usize;
+        Coord::new((id % w) as i32, (id / w) as i32)
+    }
+
+    /// register an object to cell
+    pub(crate) fn fill_cell(&mut self, cd: Coord, is_character: bool) -> bool {
+        let id = match self.cell_id(cd) {
+            Some(id) => id,
+            None => return false,
+        };
+        if is_character {
+            self.nocharacter_cells.remove(id);
+        }
+        self.empty_cells.remove(id)
+    }
+
+    /// unregister an object to cell
+    pub(crate) fn unfill_cell(&mut self, cd: Coord, is_character: bool) -> bool {
+        let id = match self.cell_id(cd) {
+            Some(id) => id,
+            None => return false,
+        };
+        if is_character {
+            self.nocharacter_cells.insert(id);
+        }
+        self.empty_cells.insert(id)
+    }
+
+    /// select an empty cell randomly
+    pub(crate) fn select_cell(&self, rng: &mut RngHandle, is_character: bool) -> Option<Coord> {
+        let set = if is_character {
+            &self.nocharacter_cells
+        } else {
+            &self.empty_cells
+        };
+        set.select(rng).map(|id| self.id_to_cd(id))
+    }
+
+    fn can_walk(&self, cd: Coord) -> bool {
+        self.cell_id(cd).is_some() && self.field.get_p(cd).surface.can_walk()
+    }
+
+    /// judge if a character can move from `cd` in `direction`
+    pub(crate) fn can_move(&self, cd: Coord, direction: Direction) -> bool {
+        let mut res = self.can_walk(cd + direction.to_cd());
+        if direction.is_diag() {
+            res &= self.can_walk(cd + direction.x());
+            res &= self.can_walk(cd + direction.y());
+        }
+        res
+    }
+
+    /// player walks in the cell
+    pub(crate) fn player_in(&mut self, cd: Coord) -> GameResult<()> {
+        if self.cell_id(cd).is_none() {
+            return Err(ErrorId::MaybeBug.into_with(|| "cave::Floor::player_in Cannot move"));
+        }
+        self.field.get_mut_p(cd).visit();
+        self.fill_cell(cd, true);
+        Direction::into_enum_iter().for_each(|d| {
+            let cd = cd + d.to_cd();
+            if self.cell_id(cd).is_some() {
+                self.field.get_mut_p(cd).approached();
+            }
+        });
+        Ok(())
+    }
+
+    /// player leaves the cell
+    pub(crate) fn player_out(&mut self, cd: Coord) {
+        self.unfill_cell(cd, true);
+        Direction::into_enum_iter().for_each(|d| {
+            let cd = cd + d.to_cd();
+            if self.cell_id(cd).is_some() {
+                self.field.get_mut_p(cd).left();
+            }
+        });
+    }
+
+    pub(crate) fn draw(
+        &self,
+        drawer: &mut dyn FnMut(Positioned<Tile>) -> GameResult<()>,
+    ) -> GameResult<()> {
+        let range = self
+            .field
+            .size_ytrimed()
+            .ok_or_else(|| ErrorId::MaybeBug.into_with(|| "cave::Floor::draw"))?;
+        range.into_iter().try_for_each(|cd| {
+            let cd = Coord::from(cd);
+            let cell = self.field.try_get_p(cd)?;
+            drawer(Positioned(cd, cell.tile()))
+        })
+    }
+
+    pub(crate) fn draw_ranges(&self, level: u32) -> Vec<DungeonPath> {
+        let xmax = self.field.width().0;
+        let ymax = self.field.height().0 - 1;
+        RectRange::from_ranges(0..xmax, 1..ymax)
+            .unwrap()
+            .into_iter()
+            .filter(|&cd| self.field.get_p(cd).is_obj_visible())
+            .map(|cd| [level as i32, cd.0, cd.1].into())
+            .collect()
+    }
+
+    pub(crate) fn move_enemy(
+        &self,
+        cur: Address,
+        dist: Coord,
+        skip: &dyn Fn(&DungeonPath) -> bool,
+    ) -> MoveResult {
+        let dist_map = self.make_dist_map(dist, false);
+        let mut cand = Vec::new();
+        for d in Direction::into_enum_iter().take(8) {
+            let next = cur.cd + d.to_cd();
+            if skip(&DungeonPath::from(Address::new(cur.level, next))) {
+                continue;
+            }
+            let ndist = match dist_map.try_get_p(next) {
+                Ok(&n) => n,
+                Err(_) => continue,
+            };
+            if ndist == 0 && self.can_move(cur.cd, d) {
+                return MoveResult::Reach;
+            }
+            if ndist != u32::MAX && ndist > 0 && self.can_move(cur.cd, d) {
+                cand.push((ndist, next))
+            }
+        }
+        match cand.into_iter().min_by_key(|t| t.0) {
+            Some((_, next)) => MoveResult::CanMove(Address::new(cur.level, next).into()),
+            None => MoveResult::CantMove,
+        }
+    }
+
+    pub(crate) fn move_enemy_randomly(
+        &self,
+        cur: Address,
+        player_pos: &DungeonPath,
+        skip: &dyn Fn(&DungeonPath) -> bool,
+        rng: &mut RngHandle,
+    ) -> MoveResult {
+        let idx = rng.range(0..8);
+        let d = Direction::into_enum_iter().nth(idx).unwrap();
+        let next = cur.cd + d.to_cd();
+        if skip(&DungeonPath::from(Address::new(cur.level, next))) || !self.can_move(cur.cd, d) {
+            return MoveResult::CantMove;
+        }
+        let res = Address::new(cur.level, next).into();
+        if res == *player_pos {
+            MoveResult::Reach
+        } else {
+            MoveResult::CanMove(res)
+        }
+    }
+
+    pub(crate) fn history_map(&self) -> Array2<bool> {
+        let size = self.field.size();
+        let mut array = Array2::from_elem([size.ylen() as usize, size.xlen() as usize], false);
+        size.into_iter().for_each(|cd| {
+            *array.get_mut_p(cd) = self.field.get_p(cd).is_visited();
+        });
+        array
+    }
+
+    /// make the distance map from `from`.
+    /// if `known_only` is true, only the cells the player knows are used.
+    fn make_dist_map(&self, from: Coord, known_only: bool) -> Array2<u32> {
+        route::make_dist_map(&self.field, from, known_only, |cd, d| self.can_move(cd, d))
+    }
+
+    /// the route from `from` to `target` through the cells the player knows
+    pub(crate) fn find_route(&self, from: Coord, target: TravelTarget) -> Option<Vec<Direction>> {
+        let goal = match target {
+            TravelTarget::Cell(cd) => cd,
+            TravelTarget::DownStair => {
+                let dist_map = self.make_dist_map(from, true);
+                route::nearest_known(&self.field, &dist_map, |_, cell| {
+                    cell.surface == Surface::Stair
+                })?
+            }
+            TravelTarget::Unexplored => {
+                let dist_map = self.make_dist_map(from, true);
+                route::nearest_known(&self.field, &dist_map, |cd, _| {
+                    route::is_frontier(&self.field, cd)
+                })?
+            }
+            // caves have no hidden passages to search for
+            TravelTarget::DeadEnd => return None,
+        };
+        if self.cell_id(goal).is_none() || !self.field.get_p(goal).is_obj_visible() {
+            return None;
+        }
+        let dist_map = self.make_dist_map(goal, true);
+        route::trace_route(from, &dist_map, |cd, d| self.can_move(cd, d))
+    }
+}
+
+/// intermediate representation of a cave used while generating
+struct RockMap {
+    is_rock: Array2<bool>,
+    inner: RectRange<i32>,
+    width: X,
+    height: Y,
+}
+
+impl RockMap {
+    fn random(width: X, height: Y, inner: RectRange<i32>, rate: u32, rng: &mut RngHandle) -> Self {
+        let mut is_rock = Array2::from_elem([height.0 as usize, width.0 as usize], true);
+        inner.iter().for_each(|cd| {
+            *is_rock.get_mut_p(cd) = rng.range(0..100) < rate;
+        });
+        RockMap {
+            is_rock,
+            inner,
+            width,
+            height,
+        }
+    }
+    fn is_rock(&self, cd: Coord) -> bool {
+        *self.is_rock.try_get_p(cd).unwrap_or(&true)
+    }
+    /// a cell becomes rock if 5 or more cells of 3x3 area around it are rock
+    fn smooth(&mut self) {
+        let prev = RockMap {
+            is_rock: self.is_rock.clone(),
+            inner: self.inner.clone(),
+            ..*self
+        };
+        self.inner.iter().for_each(|cd| {
+            let cd = Coord::from(cd);
+            let rocks = Direction::into_enum_iter()
+                .filter(|d| prev.is_rock(cd + d.to_cd()))
+                .count();
+            *self.is_rock.get_mut_p(cd) = rocks >= 5;
+        });
+    }
+    /// split empty cells into 4-connected regions
+    fn regions(&self) -> Vec<Vec<Coord>> {
+        let mut checked = self.is_rock.clone();
+        let mut res = vec![];
+        for start in self.inner.iter() {
+            let start = Coord::from(start);
+            if *checked.get_p(start) {
+                continue;
+            }
+            *checked.get_mut_p(start) = true;
+            let mut region = vec![start];
+            let mut queue = VecDeque::new();
+            queue.push_back(start);
+            while let Some(cd) = queue.pop_front() {
+                for d in Direction::into_enum_iter().take(4) {
+                    let next = cd + d.to_cd();
+                    if let Ok(c) = checked.try_get_mut_p(next) {
+                        if !*c {
+                            *c = true;
+                            region.push(next);
+                            queue.push_back(next);
+                        }
+                    }
+                }
+            }
+            res.push(region);
+        }
+        res
+    }
+    /// connect all regions to the largest one by L-shaped tunnels,
+    /// and returns the number of empty cells
+    fn connect_regions(&mut self) -> usize {
+        let mut regions = self.regions();
+        let largest = match regions.iter().enumerate().max_by_key(|(_, r)| r.len()) {
+            Some((i, _)) => i,
+            None => return 0,
+        };
+        let mut connected = regions.swap_remove(largest);
+        for region in regions {
+            let start = region[0];
+            let goal = *connected
+                .iter()
+                .min_by_key(|cd| cd.move_dist(start))
+                .expect("[cave::RockMap::connect_regions] empty region");
+            let mut cd = start;
+            while cd != goal {
+                if cd.x != goal.x {
+                    cd.x += X((goal.x - cd.x).0.signum());
+                } else {
+                    cd.y += Y((goal.y - cd.y).0.signum());
+                }
+                *self.is_rock.get_mut_p(cd) = false;
+                connected.push(cd);
+            }
+            connected.extend(region);
+        }
+        self.inner
+            .iter()
+            .filter(|&cd| !self.is_rock(cd.into()))
+            .count()
+    }
+    fn into_floor(self) -> Floor {
+        let mut field = Field::new(
+            self.width,
+            self.height,
+            Cell::with_default_attr(Surface::None),
+        );
+        let is_floor = |cd: Coord| !self.is_rock(cd);
+        field.size().into_iter().for_each(|t| {
+            let cd = Coord::from(t);
+            let cell = field.get_mut_p(cd);
+            if is_floor(cd) {
+                cell.surface = Surface::Floor;
+                cell.attr |= CellAttr::IS_DARK;
+            } else if is_floor(cd + Direction::Up.to_cd()) || is_floor(cd + Direction::Down.to_cd())
+            {
+                cell.surface = Surface::WallX;
+            } else if Direction::into_enum_iter().any(|d| is_floor(cd + d.to_cd())) {
+                cell.surface = Surface::WallY;
+            }
+        });
+        Floor::new(field)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use character::Action;
+    use input::InputCode;
+    use {GameConfig, GameMsg, Reaction, RunTime};
+    const CONFIG: &str = r#"
+{
+    "width": 48,
+    "height": 20,
+    "seed": 3,
+    "dungeon": {
+        "style": "cave"
+    }
+}
+"#;
+    #[test]
+    #[ignore]
+    fn print_floor() {
+        let mut rng = RngHandle::new();
+        let floor = Floor::gen_floor(&Config::default(), X(80), Y(24), &mut rng).unwrap();
+        println!("{}", floor.field);
+    }
+    #[test]
+    fn connectivity() {
+        let mut rng = RngHandle::from_seed(1);
+        for _ in 0..100 {
+            let floor = Floor::gen_floor(&Config::default(), X(80), Y(24), &mut rng).unwrap();
+            let start = floor.select_cell(&mut rng, false).unwrap();
+            let dist = floor.make_dist_map(start, false);
+            floor.field.size().into_iter().for_each(|cd| {
+                if floor.field.get_p(cd).surface == Surface::Floor {
+                    assert!(*dist.get_p(cd) < u32::MAX, "{}", floor.field);
+                }
+            });
+        }
+    }
+    #[test]
+    fn too_many_rocks() {
+        let config = Config {
+            wall_rate: 100,
+            ..Config::default()
+        };
+        let mut rng = RngHandle::from_seed(1);
+        assert!(Floor::gen_floor(&config, X(80), Y(24), &mut rng).is_err());
+    }
+    #[test]
+    fn play_cave() {
+        let mut runtime: RunTime = GameConfig::from_json(CONFIG).unwrap().build().unwrap();
+        let status = runtime.player_status();
+        assert_eq!(status.dungeon_level, 1);
+        assert!(runtime.history(&status).is_some());
+        let mut player_found = false;
+        runtime
+            .draw_screen(|Positioned(_, tile)| {
+                player_found |= tile == Tile::from(b'@');
+                Ok(())
+            })
+            .unwrap();
+        assert!(player_found);
+        let mut rng = RngHandle::from_seed(2);
+        let keys = b"hjklyubn>s";
+        for _ in 0..1000 {
+            let key = keys[rng.range(0..keys.len())];
+            let _ = runtime.react_to_key(::input::Key::Char(key as char));
+        }
+    }
+    #[test]
+    fn travel() {
+        let mut config = GameConfig::from_json(CONFIG).unwrap();
+        config.enemies.enemies.clear();
+        let mut runtime = config.build().unwrap();
+        let mut act = |action| {
+            let res = runtime.react_to_input(InputCode::Act(action)).unwrap();
+            let pos = runtime.player.pos.clone();
+            let found = runtime
+                .dungeon
+                .find_route(&pos, TravelTarget::DownStair)
+                .is_some();
+            (res, found, runtime.dungeon.is_downstair(&pos))
+        };
+        let (res, _, _) = act(Action::Travel(TravelTarget::Cell(Coord::new(0, 0))));
+        assert!(res.contains(&Reaction::Notify(GameMsg::NoRoute)));
+        for _ in 0..100 {
+            let (res, found, _) = act(Action::Explore);
+            assert!(!res.contains(&Reaction::Notify(GameMsg::NoRoute)));
+            if found {
+                break;
+            }
+        }
+        for _ in 0..20 {
+            if let (_, _, true) = act(Action::Travel(TravelTarget::DownStair)) {
+                return;
+            }
+        }
+        panic!("Couldn't reach the downstair");
+    }
+}
@@ -0,0 +1,570 @@
+//! hand-authored dungeon loaded from ASCII maps
+use super::cave::{self, Floor};
+use super::rogue::{Address, Surface};
+use super::{
+    Cell, CellAttr, Coord, Direction, Dungeon as DungeonTrait, DungeonPath, Field, MoveResult,
+    Positioned, TravelTarget, X, Y,
+};
+use character::{player::Status as PlayerStatus, EnemyHandler};
+use error::*;
+use hash::{HashState, PastFloorsHash, StateHasher};
+use item::{ItemHandler, ItemToken};
+use ndarray::Array2;
+use rect_iter::{Get2D, GetMut2D};
+use rng::{Parcent, RngHandle};
+use std::cmp;
+use std::hash::Hasher;
+use std::path::Path;
+use tile::{Drawable, Tile};
+use tuple_map::TupleMap2;
+use {read_file, GameInfo, GameMsg, GlobalConfig};
+
+/// Configuration of custom dungeon.
+///
+/// Each map is drawn with the same characters as `Surface`
+/// ('-', '|', '+', '#', '.', '%', '^' and ' '), and also can contain
+/// '@'(player's start position), '*'(gold) and 'A'-'Z'(enemies).
+#[derive(Clone, Debug, Serialize, Deserialize, Eq, PartialEq)]
+pub struct Config {
+    /// maps of each level. The last map is used for all deeper levels.
+    pub maps: Vec<MapSource>,
+    /// if floors are dark or not
+    #[serde(default)]
+    pub dark: bool,
+    /// sleeping enemies within this distance from the player wake up
+    #[serde(default = "default_wake_range")]
+    pub wake_range: u32,
+}
+
+const fn default_wake_range() -> u32 {
+    3
+}
+
+/// ASCII map written directly in the config, or in a text file
+#[derive(Clone, Debug, Serialize, Deserialize, Eq, PartialEq)]
+#[serde(untagged)]
+pub enum MapSource {
+    /// lines of the map
+    Inline(Vec<String>),
+    /// path to the map file, relative to the config file when loaded by `GameConfig::from_file`
+    File { file: String },
+}
+
+impl Config {
+    /// make relative paths of map files relative to `dir`
+    pub fn resolve_paths(&mut self, dir: &Path) {
+        for src in &mut self.maps {
+            if let MapSource::File { file } = src {
+                if Path::new(file.as_str()).is_relative() {
+                    *file = dir.join(&file).to_string_lossy().into_owned();
+                }
+            }
+        }
+    }
+}
+
+impl MapSource {
+    fn lines(&self) -> GameResult<Vec<String>> {
+        match self {
+            MapSource::Inline(lines) => Ok(lines.clone()),
+            MapSource::File { file } => read_file(file)
+                .map(|s| s.lines().map(str::to_owned).collect())
+                .into_chained(|| format!("failed to read map file {}", file)),
+        }
+    }
+}
+
+/// parsed ASCII map
+#[derive(Clone, Debug)]
+pub struct Map {
+    /// field (level map)
+    pub field: Field<Surface>,
+    /// the player's start position
+    pub start: Option<Coord>,
+    /// positions of gold
+    pub golds: Vec<Coord>,
+    /// positions and tiles of enemies
+    pub enemies: Vec<(Coord, Tile)>,
+}
+
+impl Map {
+    /// parse ASCII map.
+    /// The first line of the map is placed at the 2nd line of the screen,
+    /// since the 1st line is used for messages.
+    pub fn parse(lines: &[String], width: X, height: Y, dark: bool) -> GameResult<Self> {
+        if lines.len() as i32 > height.0 - 2 {
+            return Err(ErrorId::InvalidSetting.into_with(|| {
+                format!("map has {} lines, but height is {}", lines.len(), height.0)
+            }));
+        }
+        let mut map = Map {
+            field: Field::new(width, height, Cell::with_default_attr(Surface::None)),
+            start: None,
+            golds: vec![],
+            enemies: vec![],
+        };
+        for (y, line) in lines.iter().enumerate() {
+            if line.len() as i32 > width.0 {
+                return Err(ErrorId::InvalidSetting
+                    .into_with(|| format!("line {} of map is longer than width", y + 1)));
+            }
+            for (x, c) in line.bytes().enumerate() {
+                let cd = Coord::new(x as i32, y as i32 + 1);
+                let surface = match c {
+                    b'-' => Surface::WallX,
+                    b'|' => Surface::WallY,
+                    b'+' => Surface::Door,
+                    b'#' => Surface::Passage,
+                    b'.' => Surface::Floor,
+                    b'%' => Surface::Stair,
+                    b'^' => Surface::Trap,
+                    b' ' => Surface::None,
+                    b'@' if map.start.is_none() => {
+                        map.start = Some(cd);
+                        Surface::Floor
+                    }
+                    b'*' => {
+                        map.golds.push(cd);
+                        Surface::Floor
+                    }
+                    b'A'..=b'Z' => {
+                        map.enemies.push((cd, Tile::from(c)));
+                        Surface::Floor
+                    }
+                    _ => {
+                        return Err(ErrorId::InvalidSetting.into_with(|| {
+                            format!("invalid character {:?} in map at {:?}", c as char, cd)
+                        }))
+                    }
+                };
+                let cell = map.field.get_mut_p(cd);
+                cell.surface = surface;
+                if dark && surface == Surface::Floor {
+                    cell.attr |= CellAttr::IS_DARK;
+                }
+            }
+        }
+        Ok(map)
+    }
+}
+
+/// representation of custom dungeon
+#[derive(Clone)]
+pub struct Dungeon {
+    /// current level
+    pub level: u32,
+    /// current floor
+    pub current_floor: Floor,
+    /// dungeon specific configuration(constant)
+    pub config: Config,
+    /// global configuration(constant)
+    pub config_global: GlobalConfig,
+    /// past floors
+    pub past_floors: Vec<Floor>,
+    /// hash of past floors
+    past_hash: PastFloorsHash,
+    /// random number generator
+    pub rng: RngHandle,
+    /// parsed maps
+    maps: Vec<Map>,
+    /// start position in current floor
+    player_start: Option<Coord>,
+}
+
+impl HashState for Dungeon {
+    fn hash_state(&self, hasher: &mut StateHasher) {
+        hasher.write_u32(self.level);
+        self.current_floor.hash_state(hasher);
+        self.past_hash.hash_state(hasher);
+        self.rng.hash_state(hasher);
+    }
+}
+
+impl DungeonTrait for Dungeon {
+    fn is_downstair(&self, path: &DungeonPath) -> bool {
+        let address = Address::from_path(path);
+        if address.level != self.level {
+            return false;
+        }
+        if let Ok(cell) = self.current_floor.field.try_get_p(address.cd) {
+            cell.surface == Surface::Stair
+        } else {
+            false
+        }
+    }
+    fn level(&self) -> u32 {
+        self.level
+    }
+    fn new_level(
+        &mut self,
+        _stair: &DungeonPath,
+        game_info: &GameInfo,
+        item: &mut ItemHandler,
+        enemies: &mut EnemyHandler,
+    ) -> GameResult<()> {
+        self.new_level_(game_info, item, enemies, false)
+    }
+    fn can_move_player(&self, path: &DungeonPath, direction: Direction) -> Option<DungeonPath> {
+        let address = Address::from_path(path);
+        if address.level != self.level || !self.current_floor.can_move(address.cd, direction) {
+            return None;
+        }
+        let cd = address.cd + direction.to_cd();
+        Some(Address::new(address.level, cd).into())
+    }
+    fn move_player(
+        &mut self,
+        path: &DungeonPath,
+        direction: Direction,
+        enemies: &mut EnemyHandler,
+    ) -> GameResult<DungeonPath> {
+        let address = Address::from_path(path);
+        const ERR_STR: &str = "[custom::Dungeon::move_player]";
+        if address.level != self.level {
+            return Err(ErrorId::MaybeBug.into_with(|| ERR_STR));
+        }
+        self.current_floor.player_out(address.cd);
+        let cd = address.cd + direction.to_cd();
+        self.current_floor.player_in(cd).chain_err(|| ERR_STR)?;
+        let address = Address::new(self.level, cd);
+        cave::wake_enemies(enemies, address, self.config.wake_range);
+        Ok(address.into())
+    }
+    fn search(&mut self, path: &DungeonPath) -> GameResult<Vec<GameMsg>> {
+        let address = Address::from_path(path);
+        if address.level != self.level {
+            return Err(ErrorId::MaybeBug.into_with(|| "[custom::Dungeon::search]"));
+        }
+        // hand-authored maps have no hidden passages
+        Ok(vec![])
+    }
+    fn unlock_door(
+        &mut self,
+        _path: &DungeonPath,
+        _d: Direction,
+        _chance: Parcent,
+    ) -> Option<bool> {
+        // doors in hand-authored maps are never locked
+        None
+    }
+    fn find_route(&mut self, path: &DungeonPath, target: TravelTarget) -> Option<Vec<Direction>> {
+        let address = Address::from_path(path);
+        if address.level != self.level {
+            return None;
+        }
+        self.current_floor.find_route(address.cd, target)
+    }
+    fn select_cell(&mut self, is_character: bool) -> Option<DungeonPath> {
+        let start = if is_character {
+            self.player_start.take()
+        } else {
+            None
+        };
+        start
+            .or_else(|| self.current_floor.select_cell(&mut self.rng, is_character))
+            .map(|cd| Address::new(self.level, cd).into())
+    }
+    fn enter_room(&mut self, path: &DungeonPath, enemies: &mut EnemyHandler) -> GameResult<()> {
+        let address = Address::from_path(path);
+        self.current_floor
+            .player_in(address.cd)
+            .chain_err(|| "[custom::Dungeon::enter_room]")?;
+        cave::wake_enemies(enemies, address, self.config.wake_range);
+        Ok(())
+    }
+    fn take_messages(&mut self) -> Vec<GameMsg> {
+        vec![]
+    }
+    fn draw(&self, drawer: &mut dyn FnMut(Positioned<Tile>) -> GameResult<()>) -> GameResult<()> {
+        self.current_floor
+            .draw(drawer)
+            .chain_err(|| "in custom::Dungeon::draw")
+    }
+    fn draw_ranges(&self) -> Vec<DungeonPath> {
+        self.current_floor.draw_ranges(self.level)
+    }
+    fn path_to_cd(&self, path: &DungeonPath) -> Coord {
+        Coord::new(path.0[1], path.0[2])
+    }
+    fn get_item(&self, path: &DungeonPath) -> Option<&ItemToken> {
+        let addr = Address::from_path(path);
+        if addr.level != self.level {
+            return None;
+        }
+        self.current_floor.items.get(&addr.cd)
+    }
+    fn remove_item(&mut self, path: &DungeonPath) -> Option<ItemToken> {
+        let addr = Address::from_path(path);
+        if addr.level != self.level {
+            return None;
+        }
+        if !self.current_floor.unfill_cell(addr.cd, false) {
+            return None;
+        }
+        self.current_floor.items.remove(&addr.cd)
+    }
+    fn tile(&mut self, path: &DungeonPath) -> Option<Tile> {
+        let cd = self.path_to_cd(path);
+        self.current_floor
+            .field
+            .try_get_p(cd)
+            .ok()
+            .map(|s| s.tile())
+    }
+    fn get_history(&self, status: &PlayerStatus) -> Option<Array2<bool>> {
+        let level = status.dungeon_level;
+        if level == self.level {
+            Some(self.current_floor.history_map())
+        } else {
+            let idx = level.checked_sub(self.config_global.start_level)?;
+            self.past_floors.get(idx as usize).map(Floor::history_map)
+        }
+    }
+    fn move_enemy(
+        &mut self,
+        current: &DungeonPath,
+        dist: &DungeonPath,
+        skip: &dyn Fn(&DungeonPath) -> bool,
+    ) -> MoveResult {
+        let (cur, dist) = (current, dist).map(Address::from_path);
+        if cur.level != dist.level {
+            return MoveResult::CantMove;
+        }
+        self.current_floor.move_enemy(cur, dist.cd, skip)
+    }
+    fn move_enemy_randomly(
+        &mut self,
+        enemy_pos: &DungeonPath,
+        player_pos: &DungeonPath,
+        skip: &dyn Fn(&DungeonPath) -> bool,
+    ) -> MoveResult {
+        let cur = Address::from_path(enemy_pos);
+        self.current_floor
+            .move_enemy_randomly(cur, player_pos, skip, &mut self.rng)
+    }
+    fn draw_enemy(&self, player: &DungeonPath, enemy: &DungeonPath) -> bool {
+        let (p, e) = (player, enemy).map(Address::from_path);
+        if p.level != e.level {
+            return false;
+        }
+        if p.cd.is_adjacent(e.cd) {
+            return true;
+        }
+        // in lit floors, the player can see all enemies in visible cells
+        match self.current_floor.field.try_get_p(e.cd) {
+            Ok(cell) => cell.is_visible() && !cell.attr.contains(CellAttr::IS_DARK),
+            Err(_) => false,
+        }
+    }
+}
+
+impl Dungeon {
+    /// make new dungeon
+    pub fn new(
+        config: Config,
+        config_global: &GlobalConfig,
+        game_info: &GameInfo,
+        item_handle: &mut ItemHandler,
+        enemies: &mut EnemyHandler,
+        seed: u128,
+    ) -> GameResult<Self> {
+        const ERR_STR: &str = "custom::Dungeon::new";
+        let (width, height) = (config_global.width, config_global.height);
+        let maps = config
+            .maps
+            .iter()
+            .map(|src| {
+                let map = Map::parse(&src.lines()?, width, height, config.dark)?;
+                if let Some(&(_, tile)) = map
+                    .enemies
+                    .iter()
+                    .find(|(_, tile)| !enemies.is_no_enemy() && !enemies.has_enemy_tile(*tile))
+                {
+                    return Err(ErrorId::InvalidSetting
+                        .into_with(|| format!("unknown enemy {} in map", tile)));
+                }
+                Ok(map)
+            })
+            .enumerate()
+            .map(|(i, res)| res.chain_err(|| format!("{}: in map for level {}", ERR_STR, i + 1)))
+            .collect::<GameResult<Vec<_>>>()?;
+        if maps.is_empty() {
+            return Err(ErrorId::InvalidSetting.into_with(|| "custom dungeon needs 1 or more maps"));
+        }
+        let mut dungeon = Dungeon {
+            level: config_global.start_level - 1,
+            current_floor: Floor::default(),
+            config,
+            config_global: config_global.clone(),
+            past_floors: vec![],
+            past_hash: PastFloorsHash::default(),
+            rng: RngHandle::from_seed(seed),
+            maps,
+            player_start: None,
+        };
+        dungeon
+            .new_level_(game_info, item_handle, enemies, true)
+            .chain_err(|| ERR_STR)?;
+        Ok(dungeon)
+    }
+
+    fn new_level_(
+        &mut self,
+        _game_info: &GameInfo,
+        item_handle: &mut ItemHandler,
+        enemies: &mut EnemyHandler,
+        is_initial: bool,
+    ) -> GameResult<()> {
+        self.level += 1;
+        let level = self.level;
+        if let Some(seed) = self.config_global.level_seed(0, level) {
+            self.rng.reseed(seed);
+            item_handle.rng().reseed(seed);
+            enemies.rng().reseed(seed);
+        }
+        let map = &self.maps[cmp::min(level as usize, self.maps.len()) - 1];
+        let mut floor = Floor::new(map.field.clone());
+        for &cd in &map.golds {
+            floor.fill_cell(cd, false);
+            floor.items.insert(cd, item_handle.gen_gold(level));
+        }
+        if !is_initial {
+            enemies.remove_enemies();
+        }
+        for &(cd, tile) in &map.enemies {
+            if let Some(enemy) = enemies.gen_enemy_by_tile(tile, 0) {
+                enemies.place(Address::new(level, cd).into(), enemy);
+                floor.fill_cell(cd, true);
+            }
+        }
+        if !self.config_global.hide_dungeon {
+            floor.field.size().into_iter().for_each(|cd| {
+                floor.field.get_mut_p(cd).visible(true);
+            });
+        }
+        self.player_start = map.start;
+        ::std::mem::swap(&mut self.current_floor, &mut floor);
+        if !is_initial {
+            self.past_hash.push(&floor);
+            self.past_floors.push(floor);
+        }
+        Ok(())
+    }
+}
+
+/// a small map with gold and a downstair, used by tests of other modules
+#[cfg(test)]
+pub(crate) const TEST_MAP: &[&str] = &[
+    "------------",
+    "|@..*......|",
+    "|.........%|",
+    "------------",
+];
+
+/// config of a game in the custom dungeon made of `maps`, for tests
+#[cfg(test)]
+pub(crate) fn test_config(maps: &[&[&str]]) -> ::GameConfig {
+    let maps = maps
+        .iter()
+        .map(|map| MapSource::Inline(map.iter().map(|&l| l.to_owned()).collect()))
+        .collect();
+    ::GameConfig {
+        width: 32,
+        height: 16,
+        hide_dungeon: false,
+        dungeon: super::DungeonStyle::Custom(Config {
+            maps,
+            dark: false,
+            wake_range: default_wake_range(),
+        }),
+        ..Default::default()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use input::Key;
+    use {GameConfig, RunTime};
+    const LEVEL2: &[&str] = &["------", "|.@.%|", "|....|", "------"];
+    fn config() -> GameConfig {
+        test_config(&[TEST_MAP, LEVEL2])
+    }
+    fn screen(runtime: &RunTime) -> Vec<String> {
+        let (w, h) = runtime.screen_size();
+        let mut lines = vec![vec![b' '; w.0 as usize]; h.0 as usize];
+        runtime
+            .draw_screen(|Positioned(cd, tile)| {
+                lines[cd.y.0 as usize][cd.x.0 as usize] = tile.to_byte();
+                Ok(())
+            })
+            .unwrap();
+        lines
+            .into_iter()
+            .map(|l| String::from_utf8(l).unwrap().trim_end().to_owned())
+            .collect()
+    }
+    #[test]
+    fn play_custom() {
+        let mut runtime: RunTime = config().build().unwrap();
+        assert_eq!(
+            &screen(&runtime)[1..5],
+            &[
+                "------------",
+                "|@..*......|",
+                "|.........%|",
+                "------------"
+            ]
+        );
+        for _ in 0..3 {
+            runtime.react_to_key(Key::Char('l')).unwrap();
+        }
+        assert!(runtime.player_status().gold > 0);
+        // go to the stair and descend
+        for c in "llllln>".chars() {
+            runtime.react_to_key(Key::Char(c)).unwrap();
+        }
+        assert_eq!(runtime.player_status().dungeon_level, 2);
+        assert_eq!(&screen(&runtime)[2], "|.@.%|");
+        // the last map is used for deeper levels
+        for c in "ll>".chars() {
+            runtime.react_to_key(Key::Char(c)).unwrap();
+        }
+        assert_eq!(runtime.player_status().dungeon_level, 3);
+    }
+    #[test]
+    fn invalid_map() {
+        let map = |line| [TEST_MAP[0], line, TEST_MAP[2], TEST_MAP[3]];
+        assert!(test_config(&[&map("|@..*...?..|")]).build().is_err());
+        let mut config = test_config(&[&map("|@..*...Z..|")]);
+        // only Aquator is registered
+        config.enemies.enemies.truncate(1);
+        let err = config.build().err().unwrap();
+        assert!(err
+            .iter_chain()
+            .any(|e| e.to_string().contains("unknown enemy")));
+    }
+    #[test]
+    fn map_file() {
+        use std::fs;
+        let dir = std::env::temp_dir().join(format!("rogue-gym-map-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("map.txt"), "-----\n|@.%|\n-----\n").unwrap();
+        let config = r#"{
+            "width": 32,
+            "height": 16,
+            "dungeon": { "style": "custom", "maps": [{ "file": "map.txt" }] }
+        }"#;
+        let config_file = dir.join("config.json");
+        fs::write(&config_file, config).unwrap();
+        // map files are found from the directory of the config, not the current directory
+        assert!(GameConfig::from_json(config).unwrap().build().is_err());
+        let config = GameConfig::from_file(config_file.to_str().unwrap()).unwrap();
+        let runtime = config.build().unwrap();
+        assert_eq!(
+            runtime.dungeon.path_to_cd(&runtime.player.pos),
+            Coord::new(1, 2)
+        );
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -1,8 +1,10 @@
 //! General field representation
 use super::{X, Y};
+use hash::{HashState, StateHasher};
 use num_traits::ToPrimitive;
 use rect_iter::{Get2D, GetMut2D, IndexError, RectRange};
 use std::fmt;
+use std::hash::Hasher;
 use tile::{Drawable, Tile};
 use tuple_map::TupleMap2;
 
@@ -188,6 +190,17 @@ impl<S> GetMut2D for Field<S> {
     }
 }
 
+impl<S: Drawable> HashState for Field<S> {
+    fn hash_state(&self, hasher: &mut StateHasher) {
+        hasher.write_i32(self.width.0);
+        hasher.write_i32(self.height.0);
+        for cell in &self.inner {
+            hasher.write_u8(cell.surface.tile().to_byte());
+            hasher.write_u32(cell.attr.bits());
+        }
+    }
+}
+
 impl<S: Drawable> fmt::Display for Field<S> {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         for y in 0..self.height.0 as usize {
@@ -0,0 +1,162 @@
+//! field of view calculation by recursive shadowcasting
+use super::{Cell, Coord, Field};
+use rect_iter::Get2D;
+use std::collections::HashSet;
+
+/// multipliers to transform (dx, dy) in the 1st octant into each octant
+const OCTANTS: [[i32; 4]; 8] = [
+    [1, 0, 0, -1],
+    [0, 1, -1, 0],
+    [0, 1, 1, 0],
+    [1, 0, 0, 1],
+    [-1, 0, 0, 1],
+    [0, -1, 1, 0],
+    [0, -1, -1, 0],
+    [-1, 0, 0, -1],
+];
+
+/// call `visit` for each cell visible from `origin` within `radius`(chebyshev distance).
+/// `is_opaque` judges if the cell blocks the sight. Opaque cells themselves can be visible.
+/// `visit` can be called multiple times for the same cell.
+pub fn shadowcast<O, V>(origin: Coord, radius: u32, is_opaque: O, mut visit: V)
+where
+    O: Fn(Coord) -> bool,
+    V: FnMut(Coord),
+{
+    visit(origin);
+    let radius = radius as i32;
+    for mul in &OCTANTS {
+        let octant = Octant {
+            origin,
+            radius,
+            mul,
+        };
+        octant.cast_light(1, 1.0, 0.0, &is_opaque, &mut visit);
+    }
+}
+
+/// returns all cells in `field` visible from `origin` within `radius`
+pub fn visible_cells<S, O>(
+    field: &Field<S>,
+    origin: Coord,
+    radius: u32,
+    is_opaque: O,
+) -> HashSet<Coord>
+where
+    O: Fn(&Cell<S>) -> bool,
+{
+    let mut res = HashSet::new();
+    let range = field.size();
+    shadowcast(
+        origin,
+        radius,
+        |cd| !range.contains(cd) || is_opaque(field.get_p(cd)),
+        |cd| {
+            if range.contains(cd) {
+                res.insert(cd);
+            }
+        },
+    );
+    res
+}
+
+struct Octant<'a> {
+    origin: Coord,
+    radius: i32,
+    mul: &'a [i32; 4],
+}
+
+impl<'a> Octant<'a> {
+    fn transform(&self, dx: i32, dy: i32) -> Coord {
+        let m = self.mul;
+        self.origin + Coord::new(dx * m[0] + dy * m[1], dx * m[2] + dy * m[3])
+    }
+    fn cast_light<O, V>(&self, row: i32, mut start: f64, end: f64, is_opaque: &O, visit: &mut V)
+    where
+        O: Fn(Coord) -> bool,
+        V: FnMut(Coord),
+    {
+        if start < end {
+            return;
+        }
+        for j in row..=self.radius {
+            let dy = -j;
+            let mut blocked = false;
+            let mut new_start = start;
+            for dx in -j..=0 {
+                let (x, y) = (f64::from(dx), f64::from(dy));
+                let (l_slope, r_slope) = ((x - 0.5) / (y + 0.5), (x + 0.5) / (y - 0.5));
+                if start < r_slope {
+                    continue;
+                }
+                if end > l_slope {
+                    break;
+                }
+                let cd = self.transform(dx, dy);
+                visit(cd);
+                let opaque = is_opaque(cd);
+                if blocked {
+                    if opaque {
+                        new_start = r_slope;
+                    } else {
+                        blocked = false;
+                        start = new_start;
+                    }
+                } else if opaque && j < self.radius {
+                    blocked = true;
+                    self.cast_light(j + 1, start, l_slope, is_opaque, visit);
+                    new_start = r_slope;
+                }
+            }
+            if blocked {
+                break;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use rect_iter::{GetMut2D, RectRange};
+    fn parse(map: &[&str]) -> (Field<bool>, Coord) {
+        let (w, h) = (map[0].len() as i32, map.len() as i32);
+        let mut field = Field::new(w.into(), h.into(), Cell::with_default_attr(false));
+        let mut origin = Coord::default();
+        RectRange::zero_start(w, h)
+            .unwrap()
+            .iter()
+            .for_each(|(x, y)| {
+                let c = map[y as usize].as_bytes()[x as usize];
+                if c == b'@' {
+                    origin = Coord::new(x, y);
+                }
+                field.try_get_mut_p((x, y)).unwrap().surface = c == b'#';
+            });
+        (field, origin)
+    }
+    #[test]
+    fn wall_blocks_sight() {
+        let (field, origin) = parse(&[
+            "..........",
+            "..........",
+            "..@..#....",
+            "..........",
+            "..........",
+        ]);
+        let fov = visible_cells(&field, origin, 20, |c| c.surface);
+        assert!(fov.contains(&Coord::new(4, 2)));
+        assert!(fov.contains(&Coord::new(5, 2)));
+        assert!(!fov.contains(&Coord::new(6, 2)));
+        assert!(!fov.contains(&Coord::new(9, 2)));
+        assert!(fov.contains(&Coord::new(9, 0)));
+        assert!(fov.contains(&Coord::new(0, 4)));
+    }
+    #[test]
+    fn radius() {
+        let (field, origin) = parse(&[".....", ".....", "..@..", ".....", "....."]);
+        let fov = visible_cells(&field, origin, 1, |c| c.surface);
+        assert_eq!(fov.len(), 9);
+        assert!(fov.iter().all(|&cd| cd.move_dist(origin) <= 1));
+    }
+}
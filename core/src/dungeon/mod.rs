@@ -1,15 +1,23 @@
 //! module for making and managing dangeon
+pub mod cave;
 mod coord;
+pub mod custom;
 mod field;
-mod rogue;
+pub mod fov;
+pub mod rogue;
+mod route;
 pub use self::coord::{Coord, Direction, Positioned, X, Y};
 pub use self::field::{Cell, CellAttr, Field};
 use character::player::Status as PlayerStatus;
 use character::EnemyHandler;
 use error::*;
+use hash::{HashState, StateHasher};
 use item::{ItemHandler, ItemToken};
 use ndarray::Array2;
+use rng::Parcent;
 use smallvec::SmallVec;
+use std::hash::Hasher;
+use std::path::Path;
 use tile::Tile;
 use {GameInfo, GameMsg, GlobalConfig};
 
@@ -19,12 +27,14 @@ use {GameInfo, GameMsg, GlobalConfig};
 pub enum DungeonStyle {
     /// rogue 5.4.4 like dungeon
     Rogue(rogue::Config),
+    /// cave like dungeon generated by cellular automata
+    Cave(cave::Config),
     /// not implemented now
     NetHack,
     /// not implemented now
     Cataclysm,
-    /// not implemented now
-    Custom,
+    /// hand-authored dungeon loaded from ASCII maps
+    Custom(custom::Config),
 }
 
 impl Default for DungeonStyle {
@@ -55,9 +65,75 @@ impl DungeonStyle {
                 .chain_err(|| "DungeonStyle::build")?;
                 Ok(Box::new(dungeon))
             }
+            DungeonStyle::Custom(config) => {
+                let dungeon = custom::Dungeon::new(
+                    config,
+                    config_global,
+                    game_info,
+                    item_handle,
+                    enemies,
+                    seed,
+                )
+                .chain_err(|| "DungeonStyle::build")?;
+                Ok(Box::new(dungeon))
+            }
+            DungeonStyle::Cave(config) => {
+                let dungeon = cave::Dungeon::new(
+                    config,
+                    config_global,
+                    game_info,
+                    item_handle,
+                    enemies,
+                    seed,
+                )
+                .chain_err(|| "DungeonStyle::build")?;
+                Ok(Box::new(dungeon))
+            }
             _ => unimplemented!(),
         }
     }
+    /// make relative file paths in the config relative to `dir`
+    pub fn resolve_paths(&mut self, dir: &Path) {
+        if let DungeonStyle::Custom(config) = self {
+            config.resolve_paths(dir);
+        }
+    }
+    /// build only the floor of the specified level
+    pub fn build_floor(
+        self,
+        config_global: &GlobalConfig,
+        item_handle: &mut ItemHandler,
+        enemies: &mut EnemyHandler,
+        game_info: &GameInfo,
+        level: u32,
+    ) -> GameResult<rogue::Floor> {
+        match self {
+            DungeonStyle::Rogue(config) => rogue::Dungeon::floor_at(
+                config,
+                config_global,
+                game_info,
+                item_handle,
+                enemies,
+                level,
+            )
+            .chain_err(|| "DungeonStyle::build_floor"),
+            _ => Err(ErrorId::Unimplemented
+                .into_with(|| "DungeonStyle::build_floor is only for rogue style dungeon")),
+        }
+    }
+}
+
+/// destination of travel
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, Hash, Eq, PartialEq)]
+pub enum TravelTarget {
+    /// the cell the player knows
+    Cell(Coord),
+    /// the downstair the player has found
+    DownStair,
+    /// the nearest cell next to the unexplored area
+    Unexplored,
+    /// the nearest dead end of passages, which hasn't been searched enough
+    DeadEnd,
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize, Hash, Eq, PartialEq)]
@@ -67,11 +143,13 @@ pub enum MoveResult {
     Reach,
 }
 
-pub trait Dungeon {
+pub trait Dungeon: HashState {
     fn is_downstair(&self, path: &DungeonPath) -> bool;
     fn level(&self) -> u32;
+    /// go down to the next level by the stair at `stair`
     fn new_level(
         &mut self,
+        stair: &DungeonPath,
         game_info: &GameInfo,
         item: &mut ItemHandler,
         enemies: &mut EnemyHandler,
@@ -85,8 +163,21 @@ pub trait Dungeon {
     ) -> GameResult<DungeonPath>;
     fn draw_enemy(&self, player: &DungeonPath, enemy: &DungeonPath) -> bool;
     fn search(&mut self, path: &DungeonPath) -> GameResult<Vec<GameMsg>>;
+    /// try to unlock the door next to `path` with a chance of `chance`.
+    /// returns None if there's no locked door.
+    fn unlock_door(
+        &mut self,
+        path: &DungeonPath,
+        direction: Direction,
+        chance: Parcent,
+    ) -> Option<bool>;
+    /// the route from `path` to `target` through the cells the player knows.
+    /// returns None if the target is unknown or unreachable.
+    fn find_route(&mut self, path: &DungeonPath, target: TravelTarget) -> Option<Vec<Direction>>;
     fn select_cell(&mut self, is_character: bool) -> Option<DungeonPath>;
     fn enter_room(&mut self, path: &DungeonPath, enemies: &mut EnemyHandler) -> GameResult<()>;
+    /// messages produced while the player moves, e.g. entering a special room
+    fn take_messages(&mut self) -> Vec<GameMsg>;
     fn draw(&self, drawer: &mut dyn FnMut(Positioned<Tile>) -> GameResult<()>) -> GameResult<()>;
     fn draw_ranges(&self) -> Vec<DungeonPath>;
     fn path_to_cd(&self, path: &DungeonPath) -> Coord;
@@ -121,10 +212,19 @@ impl DungeonPath {
     }
 }
 
+impl HashState for DungeonPath {
+    fn hash_state(&self, hasher: &mut StateHasher) {
+        hasher.write_usize(self.0.len());
+        self.0.iter().for_each(|&i| hasher.write_i32(i));
+    }
+}
+
 impl From<rogue::Address> for DungeonPath {
     fn from(r: rogue::Address) -> DungeonPath {
-        let buf = [r.level as i32, r.cd.x.0, r.cd.y.0, 0];
-        DungeonPath(PathVec::from_buf_and_len(buf, 3))
+        let buf = [r.level as i32, r.cd.x.0, r.cd.y.0, r.branch as i32];
+        // the main dungeon has no branch id, for backward compatibility
+        let len = if r.branch == 0 { 3 } else { 4 };
+        DungeonPath(PathVec::from_buf_and_len(buf, len))
     }
 }
 
@@ -1,17 +1,25 @@
 //! rogue floor
-use super::{passages, rooms, Address, Config, Room, Surface};
-use dungeon::{Cell, CellAttr, Coord, Direction, Field, Positioned, X, Y};
+use super::{passages, rooms, Address, Config, Room, RoomLayout, Surface};
+use dungeon::{fov, route, Cell, CellAttr, Coord, Direction, Field, Positioned, X, Y};
 use enemies::EnemyHandler;
 use enum_iterator::IntoEnumIterator;
 use error::*;
 use fenwick::FenwickSet;
+use hash::{self, HashState, StateHasher};
 use item::{ItemHandler, ItemToken};
 use ndarray::Array2;
 use rect_iter::{Get2D, GetMut2D};
-use rng::RngHandle;
-use std::collections::{HashMap, HashSet, VecDeque};
+use rng::{Parcent, RngHandle, SliceRandom};
+use std::collections::{HashMap, HashSet};
 use GameMsg;
 
+/// minimum number of golds and monsters in a treasure room
+const MIN_TREASURES: usize = 2;
+/// maximum(exclusive) number of golds and monsters in a treasure room
+const MAX_TREASURES: usize = 10;
+/// dead ends searched this many times are no longer suspicious
+const DEAD_END_SEARCHES: u32 = 5;
+
 /// representation of 'floor'
 #[derive(Clone, Debug, Default)]
 pub struct Floor {
@@ -25,6 +33,23 @@ pub struct Floor {
     pub non_empty_rooms: FenwickSet,
     /// items
     pub items: HashMap<Coord, ItemToken>,
+    /// side stairs and ids of the branches they lead to
+    pub branch_stairs: Vec<(Coord, u32)>,
+    /// how far the player can see in dark rooms and passages
+    pub light_radius: u32,
+    /// cells the player can see now
+    pub fov: HashSet<Coord>,
+    /// how many times the player searched at each cell
+    pub search_count: HashMap<Coord, u32>,
+}
+
+impl HashState for Floor {
+    fn hash_state(&self, hasher: &mut StateHasher) {
+        self.field.hash_state(hasher);
+        hash::hash_map(self.items.iter(), hasher);
+        hash::hash_set(&self.fov, hasher);
+        hash::hash_map(self.search_count.iter(), hasher);
+    }
 }
 
 impl Floor {
@@ -44,6 +69,10 @@ impl Floor {
             field,
             non_empty_rooms,
             items: Default::default(),
+            branch_stairs: vec![],
+            light_radius: 1,
+            fov: HashSet::new(),
+            search_count: HashMap::new(),
         }
     }
 
@@ -56,8 +85,22 @@ impl Floor {
         height: Y,
         rng: &mut RngHandle,
     ) -> GameResult<Self> {
-        let rooms = rooms::gen_rooms(level, config, width, height, rng)
-            .chain_err(|| "Error in gen_floor")?;
+        let rooms = match config.room_layout {
+            RoomLayout::Grid => rooms::gen_rooms(level, config, width, height, rng),
+            RoomLayout::Bsp {
+                min_partition,
+                max_partition,
+            } => rooms::gen_rooms_bsp(
+                level,
+                config,
+                min_partition,
+                max_partition,
+                width,
+                height,
+                rng,
+            ),
+        }
+        .chain_err(|| "Error in gen_floor")?;
         let mut field = Field::new(width, height, Cell::with_default_attr(Surface::None));
         // in this phase, we can draw surfaces 'as is'
         rooms.iter().try_for_each(|room| {
@@ -73,17 +116,23 @@ impl Floor {
         })?;
         // sometimes door is hidden randomly so first we store positions to avoid borrow restriction
         let mut passages = Vec::new();
-        passages::dig_passges(
-            &rooms,
-            config.room_num_x,
-            config.room_num_y,
-            rng,
-            config.max_extra_edges,
-            |p| {
-                passages.push(p);
-                Ok(())
-            },
-        )?;
+        let register = |p| {
+            passages.push(p);
+            Ok(())
+        };
+        match config.room_layout {
+            RoomLayout::Grid => passages::dig_passges(
+                &rooms,
+                config.room_num_x,
+                config.room_num_y,
+                rng,
+                config.max_extra_edges,
+                register,
+            )?,
+            RoomLayout::Bsp { .. } => {
+                passages::dig_passges_bsp(&rooms, rng, config.max_extra_edges, register)?
+            }
+        }
         let mut doors = HashSet::new();
         passages
             .into_iter()
@@ -98,16 +147,37 @@ impl Floor {
                         // if the passage is not hiddden, let's draw
                         if !cell.is_hidden() && !cell.is_locked() {
                             cell.surface = surface;
+                            // visible but locked door
+                            if surface == Surface::Door
+                                && config.door_lock_rate_inv > 0
+                                && rng.does_happen(config.door_lock_rate_inv)
+                            {
+                                cell.attr |= CellAttr::IS_LOCKED;
+                            }
                         }
                     })
                     .into_chained(|| "Floor::new dig_passges returned invalid index")
             })?;
-        Ok(Floor::new(rooms, doors, field))
+        let mut rooms = rooms;
+        // the first level never has a treasure room
+        if level > 1
+            && config.treasure_room_rate_inv > 0
+            && rng.does_happen(config.treasure_room_rate_inv)
+        {
+            let normal_rooms: Vec<_> = (0..rooms.len()).filter(|&i| rooms[i].is_normal()).collect();
+            if let Some(&i) = normal_rooms.choose(rng) {
+                rooms[i].make_treasure();
+            }
+        }
+        let mut floor = Floor::new(rooms, doors, field);
+        floor.light_radius = config.light_radius;
+        Ok(floor)
     }
     /// place enemies
     pub fn place_enemies(
         &mut self,
         level: u32,
+        branch: u32,
         lev_add: u32,
         enemies: &mut EnemyHandler,
         rng: &mut RngHandle,
@@ -124,11 +194,25 @@ impl Floor {
             .filter_map(|room| Some((room.select_cell(rng, true)?, room)))
         {
             if let Some(enemy) = enemies.gen_enemy(min..max, i64::from(lev_add), room.has_gold) {
-                let place = Address::new(level, cd).into();
+                let place = Address::new(level, cd).with_branch(branch).into();
                 enemies.place(place, enemy);
                 room.fill_cell(cd, true);
             }
         }
+        // treasure rooms are guarded by many sleeping monsters
+        for room in self.rooms.iter_mut().filter(|room| room.is_treasure()) {
+            for _ in 0..rng.range(MIN_TREASURES..MAX_TREASURES) {
+                let cd = match room.select_cell(rng, true) {
+                    Some(cd) => cd,
+                    None => break,
+                };
+                if let Some(enemy) = enemies.gen_enemy_in(min..max, i64::from(lev_add)) {
+                    let place = Address::new(level, cd).with_branch(branch).into();
+                    enemies.place(place, enemy);
+                    room.fill_cell(cd, true);
+                }
+            }
+        }
     }
     /// setup items for a floor
     pub fn setup_items(
@@ -151,11 +235,22 @@ impl Floor {
                     self.items.insert(cd, gold);
                 }
             }
+            for room in self.rooms.iter_mut().filter(|room| room.is_treasure()) {
+                for _ in 0..rng.range(MIN_TREASURES..MAX_TREASURES) {
+                    let cd = match room.select_cell(rng, false) {
+                        Some(cd) => cd,
+                        None => break,
+                    };
+                    room.fill_cell(cd, false);
+                    room.has_gold = true;
+                    self.items.insert(cd, item_handle.gen_gold(level));
+                }
+            }
         }
     }
 
     /// set stair
-    pub fn setup_stair(&mut self, rng: &mut RngHandle) -> GameResult<()> {
+    pub fn setup_stair(&mut self, rng: &mut RngHandle) -> GameResult<Coord> {
         let cd = self
             .select_cell(rng, false)
             .ok_or_else(|| ErrorId::MaybeBug.into_with(|| "[setup stair] no empty cell!"))?;
@@ -167,9 +262,24 @@ impl Floor {
             cell.surface = Surface::Stair;
         }
         self.set_obj(cd, false);
+        Ok(cd)
+    }
+
+    /// set a side stair which leads to the branch
+    pub fn setup_branch_stair(&mut self, branch: u32, rng: &mut RngHandle) -> GameResult<()> {
+        let cd = self.setup_stair(rng).chain_err(|| "[setup branch stair]")?;
+        self.branch_stairs.push((cd, branch));
         Ok(())
     }
 
+    /// the id of the branch which the stair at `cd` leads to
+    pub fn branch_stair(&self, cd: Coord) -> Option<u32> {
+        self.branch_stairs
+            .iter()
+            .find(|&&(stair, _)| stair == cd)
+            .map(|&(_, branch)| branch)
+    }
+
     fn can_move_impl(&self, cd: Coord, direction: Direction, is_enemy: bool) -> Option<bool> {
         let cell = |cd: Coord| self.field.try_get_p(cd).ok();
         let nxt = cell(cd + direction.to_cd())?;
@@ -177,6 +287,9 @@ impl Floor {
         if !is_enemy {
             res &= !nxt.is_hidden();
             res &= !nxt.is_locked();
+        } else if nxt.surface == Surface::Door {
+            // enemies can't pass locked doors, but can pass secret passages
+            res &= !nxt.is_locked();
         }
         if direction.is_diag() {
             res &= cell(cd + direction.x())?.surface.can_walk();
@@ -269,9 +382,16 @@ impl Floor {
         cd: Coord,
         init: bool,
         enemies: &mut EnemyHandler,
-    ) -> GameResult<()> {
+    ) -> GameResult<Option<GameMsg>> {
         debug!("[Floor::player_in] cd: {:?}", cd);
+        let mut msg = None;
         if init || self.doors.contains(&cd) {
+            if let Some(room_id) = self.cd_to_room_id(cd) {
+                let room = &self.rooms[room_id];
+                if !init && !room.is_visited && room.is_treasure() {
+                    msg = Some(GameMsg::TreasureRoom);
+                }
+            }
             self.enters_room(cd).chain_err(|| "Floor::player_in")?;
             if let Some(room_id) = self.cd_to_room_id(cd) {
                 let room = &self.rooms[room_id];
@@ -286,15 +406,40 @@ impl Floor {
             .into_chained(|| "Floor::player_in Cannot move")?
             .visit();
         self.set_obj(cd, true);
-        Direction::into_enum_iter().take(9).for_each(|d| {
-            let cd = cd + d.to_cd();
-            if let Ok(cell) = self.field.try_get_mut_p(cd) {
-                if !d.is_diag() || cell.surface != Surface::Passage {
-                    cell.approached();
-                }
-            }
+        self.update_fov(cd);
+        Ok(msg)
+    }
+
+    /// update the cells the player can see from `cd`
+    pub(super) fn update_fov(&mut self, cd: Coord) {
+        // the whole room is lit if the player is in a lit room or its door
+        let lit_range = self
+            .cd_to_room_id(cd)
+            .map(|id| &self.rooms[id])
+            .filter(|room| room.is_normal() && !room.is_dark)
+            .and_then(|room| room.range())
+            .filter(|range| range.contains(cd))
+            .cloned();
+        let light_radius = self.light_radius;
+        let radius = match lit_range {
+            Some(_) => (self.field.width().0).max(self.field.height().0) as u32,
+            None => light_radius,
+        };
+        let fov = fov::visible_cells(&self.field, cd, radius, |cell| {
+            !cell.surface.can_walk() || cell.is_hidden()
         });
-        Ok(())
+        let field = &self.field;
+        self.fov = fov
+            .into_iter()
+            .filter(|&c| match c.move_dist(cd) as u32 {
+                0 => true,
+                // passages can't be seen diagonally, as rogue
+                1 => c.x == cd.x || c.y == cd.y || field.get_p(c).surface != Surface::Passage,
+                dist => dist <= light_radius || lit_range.iter().any(|r| r.contains(c)),
+            })
+            .collect();
+        let Floor { fov, field, .. } = self;
+        fov.iter().for_each(|&c| field.get_mut_p(c).approached());
     }
 
     /// player leaves the cell
@@ -303,12 +448,11 @@ impl Floor {
             self.leaves_room(cd).chain_err(|| "Floor::player_out")?;
         }
         self.remove_obj(cd, true);
-        Direction::into_enum_iter().take(9).for_each(|d| {
-            let cd = cd + d.to_cd();
-            if let Ok(cell) = self.field.try_get_mut_p(cd) {
-                if cell.surface == Surface::Floor {
-                    cell.left();
-                }
+        let Floor { fov, field, .. } = self;
+        fov.drain().for_each(|c| {
+            let cell = field.get_mut_p(c);
+            if cell.surface == Surface::Floor {
+                cell.left();
             }
         });
         Ok(())
@@ -355,6 +499,7 @@ impl Floor {
         rng: &'a mut RngHandle,
         config: &'a Config,
     ) -> impl 'a + Iterator<Item = GameMsg> {
+        *self.search_count.entry(cd).or_insert(0) += 1;
         let probinc = 0; // TODO: it should be changed by player status
         Direction::into_enum_iter().take(8).filter_map(move |d| {
             let cd = cd + d.to_cd();
@@ -363,7 +508,8 @@ impl Floor {
                 cell.unlock();
                 cell.surface = Surface::Passage;
             }
-            if cell.is_locked() && rng.does_happen(probinc + config.door_unlock_rate_inv) {
+            let is_secret = cell.is_locked() && cell.surface != Surface::Door;
+            if is_secret && rng.does_happen(probinc + config.door_unlock_rate_inv) {
                 cell.unlock();
                 cell.surface = Surface::Door;
                 return Some(GameMsg::SecretDoor);
@@ -372,6 +518,24 @@ impl Floor {
         })
     }
 
+    /// try to unlock the door at `cd`
+    pub(super) fn unlock_door(
+        &mut self,
+        cd: Coord,
+        chance: Parcent,
+        rng: &mut RngHandle,
+    ) -> Option<bool> {
+        let cell = self.field.try_get_mut_p(cd).ok()?;
+        if cell.surface != Surface::Door || !cell.is_locked() || cell.is_hidden() {
+            return None;
+        }
+        let success = rng.parcent(chance);
+        if success {
+            cell.unlock();
+        }
+        Some(success)
+    }
+
     pub(super) fn history_map(&self) -> Array2<bool> {
         let size = self.field.size();
         let mut array = Array2::from_elem([size.ylen() as usize, size.xlen() as usize], false);
@@ -381,41 +545,32 @@ impl Floor {
         array
     }
 
-    pub(super) fn in_same_room(&self, a: Coord, b: Coord) -> bool {
-        let id = match self.cd_to_room_id(a) {
-            Some(i) => i,
-            None => return false,
-        };
-        if self.cd_to_room_id(b) != Some(id) {
-            return false;
-        }
-        self.rooms[id]
-            .range()
-            .map(|r| r.contains(a) == r.contains(b))
-            .unwrap_or(true)
+    /// the player at `player` can see `cd` or not
+    pub(super) fn is_in_sight(&self, player: Coord, cd: Coord) -> bool {
+        player.is_adjacent(cd) || self.fov.contains(&cd)
     }
 
+    /// make the distance map from `from`.
+    /// if `is_enemy` is false, only the cells the player knows are used.
     pub(super) fn make_dist_map(&self, from: Coord, is_enemy: bool) -> Array2<u32> {
-        let (w, h) = (self.field.width(), self.field.height());
-        let inf = u32::max_value();
-        let mut dist = Array2::from_elem([h.0 as usize, w.0 as usize], inf);
-        let mut queue = VecDeque::new();
-        *dist.get_mut_p(from) = 0;
-        queue.push_back(from);
-        while let Some(current) = queue.pop_front() {
-            for d in Direction::into_enum_iter().take(8) {
-                let next = current + d.to_cd();
-                let cdist = *dist.get_p(current);
-                if let Ok(ndist) = dist.try_get_mut_p(next) {
-                    if *ndist != inf || self.can_move_impl(current, d, is_enemy) != Some(true) {
-                        continue;
-                    }
-                    queue.push_back(next);
-                    *ndist = cdist + 1;
-                }
-            }
+        route::make_dist_map(&self.field, from, !is_enemy, |cd, d| {
+            self.can_move_impl(cd, d, is_enemy) == Some(true)
+        })
+    }
+
+    /// judge if `cd` is a dead end of passages which may lead to hidden passages
+    pub(super) fn is_dead_end(&self, cd: Coord) -> bool {
+        if self.field.get_p(cd).surface != Surface::Passage
+            || self.search_count.get(&cd).cloned().unwrap_or(0) >= DEAD_END_SEARCHES
+        {
+            return false;
         }
-        dist
+        let neighbors = Direction::into_enum_iter()
+            .take(4)
+            .filter(|&d| self.can_move_player(cd, d).is_some())
+            .filter(|&d| self.field.get_p(cd + d.to_cd()).is_obj_visible())
+            .count();
+        neighbors <= 1
     }
 }
 
@@ -456,7 +611,11 @@ fn gen_attr(
 #[cfg(test)]
 mod test {
     use super::*;
+    use character::Action;
+    use dungeon::custom::{test_config, TEST_MAP};
+    use input::InputCode;
     use rect_iter::RectRange;
+    use {Reaction, RunTime};
     #[test]
     #[ignore]
     fn print_floor() {
@@ -490,6 +649,72 @@ mod test {
         }
     }
     #[test]
+    fn locked_door() {
+        let config = Config {
+            door_lock_rate_inv: 1,
+            ..Config::default()
+        };
+        let mut rng = RngHandle::from_seed(1);
+        let mut floor = Floor::gen_floor(1, &config, X(80), Y(24), &mut rng).unwrap();
+        let door = *floor
+            .doors
+            .iter()
+            .find(|&&cd| floor.field.get_p(cd).surface == Surface::Door)
+            .unwrap();
+        assert!(floor.field.get_p(door).is_locked());
+        // enemies can't go through locked doors
+        let (from, d) = Direction::into_enum_iter()
+            .filter(|d| !d.is_diag())
+            .map(|d| (door - d.to_cd(), d))
+            .find(|&(cd, _)| floor.field.get_p(cd).surface.can_walk())
+            .unwrap();
+        assert!(!floor.can_move_enemy(from, d));
+        assert_eq!(floor.unlock_door(from, Parcent(100), &mut rng), None);
+        assert_eq!(floor.unlock_door(door, Parcent(0), &mut rng), Some(false));
+        assert_eq!(floor.unlock_door(door, Parcent(100), &mut rng), Some(true));
+        assert!(floor.can_move_enemy(from, d));
+        assert!(floor.can_move_player(from, d).is_some());
+    }
+    #[test]
+    fn no_locked_door() {
+        let mut runtime: RunTime = test_config(&[TEST_MAP]).build().unwrap();
+        let turns = runtime.player.turns();
+        for &act in &[Action::Open(Direction::Up), Action::Kick(Direction::Right)] {
+            let res = runtime.react_to_input(InputCode::Act(act)).unwrap();
+            assert_eq!(res, vec![Reaction::Notify(GameMsg::NoLockedDoor)]);
+        }
+        // it takes no turn
+        assert_eq!(runtime.player.turns(), turns);
+    }
+    #[test]
+    fn treasure_room() {
+        let config = Config {
+            treasure_room_rate_inv: 1,
+            ..Config::default()
+        };
+        let game_config = ::GameConfig::default();
+        let mut item = ItemHandler::new(game_config.item.clone(), 1);
+        let mut enemies = game_config.enemies.clone().build(1);
+        let mut rng = RngHandle::from_seed(1);
+        let level = 5;
+        let mut floor = Floor::gen_floor(level, &config, X(80), Y(24), &mut rng).unwrap();
+        floor.setup_items(level, &mut item, true, &mut rng);
+        floor.place_enemies(level, 0, 0, &mut enemies, &mut rng);
+        let treasures: Vec<_> = floor.rooms.iter().filter(|r| r.is_treasure()).collect();
+        assert_eq!(treasures.len(), 1);
+        let range = treasures[0].range().unwrap();
+        let count = |f: &dyn Fn(Coord) -> bool| range.iter().filter(|&cd| f(cd.into())).count();
+        assert!(count(&|cd| floor.items.contains_key(&cd)) >= MIN_TREASURES);
+        let monsters = count(&|cd| {
+            let path = Address::new(level, cd).into();
+            enemies.get_enemy(&path).is_some()
+        });
+        assert!(monsters >= MIN_TREASURES);
+        // no treasure room in the first level
+        let floor = Floor::gen_floor(1, &config, X(80), Y(24), &mut rng).unwrap();
+        assert!(floor.rooms.iter().all(|r| !r.is_treasure()));
+    }
+    #[test]
     fn select_cell() {
         let config = Config::default();
         let mut rng = RngHandle::new();
@@ -3,17 +3,22 @@ pub mod maze;
 pub mod passages;
 pub mod rooms;
 
-use self::floor::Floor;
+pub use self::floor::Floor;
 pub use self::rooms::{Room, RoomKind};
-use super::{Coord, Direction, Dungeon as DungeonTrait, DungeonPath, MoveResult, Positioned, X, Y};
+use super::{
+    route, Coord, Direction, Dungeon as DungeonTrait, DungeonPath, MoveResult, Positioned,
+    TravelTarget, X, Y,
+};
 use character::{player::Status as PlayerStatus, EnemyHandler};
 use enum_iterator::IntoEnumIterator;
 use error::*;
+use hash::{HashState, PastFloorsHash, StateHasher};
 use item::{ItemHandler, ItemToken};
 use ndarray::Array2;
 use rect_iter::{Get2D, GetMut2D, RectRange};
-use rng::RngHandle;
+use rng::{Parcent, RngHandle};
 use std::collections::VecDeque;
+use std::hash::Hasher;
 use tile::{Drawable, Tile};
 use tuple_map::TupleMap2;
 use {GameInfo, GameMsg, GlobalConfig};
@@ -47,7 +52,7 @@ pub struct Config {
     /// a passage is hidden with a probability of 1 / hidden_rate_inv
     #[serde(default = "default_hidden_passage_rate")]
     pub hidden_passage_rate_inv: u32,
-    /// a door is locked with a probability of 1 / hidden_rate_inv
+    /// a door is hidden as a secret door with a probability of 1 / locked_door_rate_inv
     #[serde(default = "default_locked_door_rate_inv")]
     pub locked_door_rate_inv: u32,
     /// try number of additional passages
@@ -55,8 +60,85 @@ pub struct Config {
     pub max_extra_edges: u32,
     #[serde(default = "default_door_unlock_rate_inv")]
     pub door_unlock_rate_inv: u32,
+    /// a visible door is locked with a probability of 1 / door_lock_rate_inv (0 means never)
+    #[serde(default = "default_door_lock_rate_inv")]
+    pub door_lock_rate_inv: u32,
     #[serde(default = "default_passage_unlock_rate_inv")]
     pub passage_unlock_rate_inv: u32,
+    /// how to divide the screen into rooms
+    #[serde(default)]
+    pub room_layout: RoomLayout,
+    /// a floor has a treasure room with a probability of 1 / treasure_room_rate_inv
+    /// (0 means never, which is the default, and the first level never has it)
+    #[serde(default = "default_treasure_room_rate_inv")]
+    pub treasure_room_rate_inv: u32,
+    /// how far the player can see in dark rooms and passages
+    #[serde(default = "default_light_radius")]
+    pub light_radius: u32,
+    /// optional side branches
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub branches: Vec<Branch>,
+}
+
+/// a short side branch of the dungeon, entered by a side stair
+#[derive(Clone, Debug, Serialize, Deserialize, Eq, PartialEq)]
+pub struct Branch {
+    /// the level where the side stair is placed
+    pub entry_level: u32,
+    /// number of levels in the branch
+    #[serde(default = "default_branch_depth")]
+    pub depth: u32,
+    /// gold and enemies in the branch are generated as if the level were deeper by this
+    #[serde(default = "default_branch_bonus_level")]
+    pub bonus_level: u32,
+    /// generation setting of the branch levels(same as the main dungeon if not specified)
+    /// `branches` in it is ignored
+    #[serde(default)]
+    pub dungeon: Option<Box<Config>>,
+}
+
+const fn default_treasure_room_rate_inv() -> u32 {
+    0
+}
+
+const fn default_light_radius() -> u32 {
+    1
+}
+
+const fn default_branch_depth() -> u32 {
+    2
+}
+
+const fn default_branch_bonus_level() -> u32 {
+    3
+}
+
+/// algorithm to divide the screen into rooms
+#[derive(Clone, Debug, Default, Serialize, Deserialize, Eq, PartialEq)]
+#[serde(tag = "kind", rename_all = "lowercase")]
+pub enum RoomLayout {
+    /// room_num_x × room_num_y grid, same as rogue
+    #[default]
+    Grid,
+    /// binary space partitioning, which makes rooms of varied number and size
+    Bsp {
+        /// minimum size of a partition, which must be larger than min_room_size + (3, 3)
+        #[serde(default = "default_min_partition")]
+        min_partition: Coord,
+        /// a partition larger than this is always divided
+        #[serde(default = "default_max_partition")]
+        max_partition: Coord,
+    },
+}
+
+#[inline]
+fn default_min_partition() -> Coord {
+    Coord::new(10, 7)
+}
+
+#[inline]
+fn default_max_partition() -> Coord {
+    Coord::new(30, 12)
 }
 
 const fn default_room_num_x() -> X {
@@ -108,6 +190,10 @@ const fn default_door_unlock_rate_inv() -> u32 {
     5
 }
 
+const fn default_door_lock_rate_inv() -> u32 {
+    0
+}
+
 const fn default_passage_unlock_rate_inv() -> u32 {
     3
 }
@@ -127,7 +213,12 @@ impl Default for Config {
             locked_door_rate_inv: default_locked_door_rate_inv(),
             max_extra_edges: default_max_extra_edges(),
             door_unlock_rate_inv: default_door_unlock_rate_inv(),
+            door_lock_rate_inv: default_door_lock_rate_inv(),
             passage_unlock_rate_inv: default_passage_unlock_rate_inv(),
+            room_layout: RoomLayout::default(),
+            treasure_room_rate_inv: default_treasure_room_rate_inv(),
+            light_radius: default_light_radius(),
+            branches: vec![],
         }
     }
 }
@@ -167,7 +258,7 @@ impl Default for Surface {
 }
 
 impl Surface {
-    fn can_walk(&self) -> bool {
+    pub fn can_walk(&self) -> bool {
         match *self {
             Surface::WallX | Surface::WallY | Surface::None => false,
             _ => true,
@@ -180,6 +271,8 @@ impl Surface {
 pub struct Dungeon {
     /// current level
     pub level: u32,
+    /// id of the current branch(0 means the main dungeon, n means config.branches[n - 1])
+    pub branch: u32,
     /// amulet level or more deeper level the player visited
     pub max_level: u32,
     /// current floor
@@ -190,9 +283,22 @@ pub struct Dungeon {
     pub config_global: GlobalConfig,
     /// past floors
     pub past_floors: Vec<Floor>,
+    /// hash of past floors
+    past_hash: PastFloorsHash,
     /// random number generator
     pub rng: RngHandle,
     dist_cache: DistCache,
+    messages: Vec<GameMsg>,
+}
+
+impl HashState for Dungeon {
+    fn hash_state(&self, hasher: &mut StateHasher) {
+        hasher.write_u32(self.level);
+        hasher.write_u32(self.branch);
+        self.current_floor.hash_state(hasher);
+        self.past_hash.hash_state(hasher);
+        self.rng.hash_state(hasher);
+    }
 }
 
 impl DungeonTrait for Dungeon {
@@ -212,10 +318,17 @@ impl DungeonTrait for Dungeon {
     }
     fn new_level(
         &mut self,
+        stair: &DungeonPath,
         game_info: &GameInfo,
         item: &mut ItemHandler,
         enemies: &mut EnemyHandler,
     ) -> GameResult<()> {
+        let stair = Address::from_path(stair);
+        self.branch = match self.current_floor.branch_stair(stair.cd) {
+            Some(branch) if stair.level == self.level => branch,
+            _ if self.branch_end() == Some(self.level) => 0,
+            _ => self.branch,
+        };
         self.new_level_(game_info, item, enemies, false)
     }
     fn can_move_player(&self, path: &DungeonPath, direction: Direction) -> Option<DungeonPath> {
@@ -225,7 +338,7 @@ impl DungeonTrait for Dungeon {
         }
         self.current_floor
             .can_move_player(address.cd, direction)
-            .map(|cd| DungeonPath::from(Address::new(address.level, cd)))
+            .map(|cd| DungeonPath::from(Address { cd, ..address }))
     }
     fn move_player(
         &mut self,
@@ -242,13 +355,14 @@ impl DungeonTrait for Dungeon {
             .player_out(address.cd)
             .chain_err(|| ERR_STR)?;
         let cd = address.cd + direction.to_cd();
-        let address = Address {
-            level: self.level,
-            cd,
-        };
-        self.current_floor
+        let address = self.address(cd);
+        let msg = self
+            .current_floor
             .player_in(cd, false, enemies)
             .chain_err(|| ERR_STR)?;
+        self.messages.extend(msg);
+        // the player's knowledge has changed
+        self.dist_cache.invalidate(false);
         Ok(address.into())
     }
     fn search(&mut self, path: &DungeonPath) -> GameResult<Vec<GameMsg>> {
@@ -256,19 +370,89 @@ impl DungeonTrait for Dungeon {
         if address.level != self.level {
             return Err(ErrorId::MaybeBug.into_with(|| "[rogue::Dungeon::search]"));
         }
-        Ok(self
+        let msgs: Vec<_> = self
             .current_floor
             .search(address.cd, &mut self.rng, &self.config)
-            .collect())
+            .collect();
+        // found passages and doors become visible at once
+        self.current_floor.update_fov(address.cd);
+        self.dist_cache.clear();
+        Ok(msgs)
+    }
+    fn unlock_door(
+        &mut self,
+        path: &DungeonPath,
+        direction: Direction,
+        chance: Parcent,
+    ) -> Option<bool> {
+        let address = Address::from_path(path);
+        if address.level != self.level {
+            return None;
+        }
+        let res =
+            self.current_floor
+                .unlock_door(address.cd + direction.to_cd(), chance, &mut self.rng);
+        if res == Some(true) {
+            self.dist_cache.clear();
+        }
+        res
+    }
+    fn find_route(&mut self, path: &DungeonPath, target: TravelTarget) -> Option<Vec<Direction>> {
+        let address = Address::from_path(path);
+        if address.level != self.level {
+            return None;
+        }
+        let Dungeon {
+            current_floor,
+            dist_cache,
+            ..
+        } = self;
+        let goal = match target {
+            TravelTarget::Cell(cd) => cd,
+            TravelTarget::DownStair => {
+                let dist_map = dist_cache.make_dist_map(current_floor, address.cd, false);
+                route::nearest_known(&current_floor.field, dist_map, |_, cell| {
+                    cell.surface == Surface::Stair
+                })?
+            }
+            TravelTarget::Unexplored => {
+                let dist_map = dist_cache.make_dist_map(current_floor, address.cd, false);
+                route::nearest_known(&current_floor.field, dist_map, |cd, _| {
+                    route::is_frontier(&current_floor.field, cd)
+                })?
+            }
+            TravelTarget::DeadEnd => {
+                let dist_map = dist_cache.make_dist_map(current_floor, address.cd, false);
+                route::nearest_known(&current_floor.field, dist_map, |cd, _| {
+                    current_floor.is_dead_end(cd)
+                })?
+            }
+        };
+        if !current_floor.field.size().contains(goal) {
+            return None;
+        }
+        if !current_floor.field.get_p(goal).is_obj_visible() {
+            return None;
+        }
+        let dist_map = dist_cache.make_dist_map(current_floor, goal, false);
+        route::trace_route(address.cd, dist_map, |cd, d| {
+            current_floor.can_move_player(cd, d).is_some()
+        })
     }
     fn select_cell(&mut self, is_character: bool) -> Option<DungeonPath> {
         self.current_floor
             .select_cell(&mut self.rng, is_character)
-            .map(|cd| [self.level as i32, cd.x.0, cd.y.0].into())
+            .map(|cd| self.address(cd).into())
     }
     fn enter_room(&mut self, path: &DungeonPath, enemies: &mut EnemyHandler) -> GameResult<()> {
         let address = Address::from_path(path);
-        self.current_floor.player_in(address.cd, true, enemies)
+        self.dist_cache.invalidate(false);
+        self.current_floor
+            .player_in(address.cd, true, enemies)
+            .map(|_| ())
+    }
+    fn take_messages(&mut self) -> Vec<GameMsg> {
+        ::std::mem::take(&mut self.messages)
     }
     fn draw(&self, drawer: &mut dyn FnMut(Positioned<Tile>) -> GameResult<()>) -> GameResult<()> {
         const ERR_STR: &str = "in rogue::Dungeon::move_player";
@@ -289,8 +473,12 @@ impl DungeonTrait for Dungeon {
         RectRange::from_ranges(0..xmax, 1..ymax)
             .unwrap()
             .into_iter()
-            .filter(|&cd| self.current_floor.field.get_p(cd).is_obj_visible())
-            .map(|cd| [self.level as i32, cd.0, cd.1].into())
+            // objects are drawn only when the player can see them
+            .filter(|&cd| {
+                !self.config_global.hide_dungeon
+                    || self.current_floor.fov.contains(&Coord::from(cd))
+            })
+            .map(|cd| self.address(Coord::from(cd)).into())
             .collect()
     }
     fn path_to_cd(&self, path: &DungeonPath) -> Coord {
@@ -325,10 +513,9 @@ impl DungeonTrait for Dungeon {
         let level = status.dungeon_level;
         if level == self.level {
             Some(self.current_floor.history_map())
-        } else if let Some(floor) = self.past_floors.get(level as usize - 1) {
-            Some(floor.history_map())
         } else {
-            None
+            let idx = level.checked_sub(self.config_global.start_level)?;
+            self.past_floors.get(idx as usize).map(Floor::history_map)
         }
     }
     fn move_enemy(
@@ -350,7 +537,7 @@ impl DungeonTrait for Dungeon {
         let dist_map = dist_cache.make_dist_map(current_floor, dist.cd, true);
         for d in Direction::into_enum_iter() {
             let next = cur.cd + d.to_cd();
-            if skip(&DungeonPath::from(Address::new(cur.level, next))) {
+            if skip(&DungeonPath::from(Address { cd: next, ..cur })) {
                 continue;
             }
             let ndist = *dist_map.get_p(next);
@@ -366,7 +553,7 @@ impl DungeonTrait for Dungeon {
         }
         cand.sort_by_key(|t| t.0);
         let res = cand[0].1;
-        MoveResult::CanMove(Address::new(cur.level, res).into())
+        MoveResult::CanMove(Address { cd: res, ..cur }.into())
     }
     fn move_enemy_randomly(
         &mut self,
@@ -378,12 +565,12 @@ impl DungeonTrait for Dungeon {
         let idx = self.rng.range(0..8);
         let d = Direction::into_enum_iter().nth(idx).unwrap();
         let next = cur.cd + d.to_cd();
-        if skip(&DungeonPath::from(Address::new(cur.level, next)))
+        if skip(&DungeonPath::from(Address { cd: next, ..cur }))
             || !self.current_floor.can_move_enemy(cur.cd, d)
         {
             return MoveResult::CantMove;
         }
-        let res = Address::new(cur.level, next).into();
+        let res = Address { cd: next, ..cur }.into();
         if res == *player_pos {
             MoveResult::Reach
         } else {
@@ -395,7 +582,7 @@ impl DungeonTrait for Dungeon {
         if p.level != e.level {
             return false;
         }
-        p.cd.is_adjacent(e.cd) || self.current_floor.in_same_room(p.cd, e.cd)
+        self.current_floor.is_in_sight(p.cd, e.cd)
     }
 }
 
@@ -409,16 +596,27 @@ impl Dungeon {
         enemies: &mut EnemyHandler,
         seed: u128,
     ) -> GameResult<Self> {
+        if let Some(b) = config
+            .branches
+            .iter()
+            .find(|b| b.entry_level == 0 || b.depth == 0)
+        {
+            return Err(ErrorId::InvalidSetting
+                .into_with(|| format!("rogue::Dungeon::new invalid branch {:?}", b)));
+        }
         let rng = RngHandle::from_seed(seed);
         let mut dungeon = Dungeon {
-            level: 0,
+            level: config_global.start_level - 1,
+            branch: 0,
             max_level: config.amulet_level,
             current_floor: Floor::default(),
             config,
             config_global: config_global.clone(),
             past_floors: vec![],
+            past_hash: PastFloorsHash::default(),
             rng,
             dist_cache: DistCache::new(),
+            messages: vec![],
         };
         dungeon
             .new_level_(game_info, item_handle, enemies, true)
@@ -426,6 +624,38 @@ impl Dungeon {
         Ok(dungeon)
     }
 
+    /// generate only the floor of the specified level.
+    /// It's same as the floor which the player reaches in a game with the same seed
+    /// and `seed_per_level` enabled.
+    pub fn floor_at(
+        config: Config,
+        config_global: &GlobalConfig,
+        game_info: &GameInfo,
+        item_handle: &mut ItemHandler,
+        enemies: &mut EnemyHandler,
+        level: u32,
+    ) -> GameResult<Floor> {
+        if level == 0 {
+            return Err(ErrorId::InvalidSetting.into_with(|| "level must be larger than 0"));
+        }
+        let config_global = GlobalConfig {
+            start_level: level,
+            seed_per_level: true,
+            ..config_global.clone()
+        };
+        let seed = config_global.seed;
+        Dungeon::new(
+            config,
+            &config_global,
+            game_info,
+            item_handle,
+            enemies,
+            seed,
+        )
+        .map(|dungeon| dungeon.current_floor)
+        .chain_err(|| "rogue::Dungeon::floor_at")
+    }
+
     fn new_level_(
         &mut self,
         game_info: &GameInfo,
@@ -441,21 +671,43 @@ impl Dungeon {
         if level > self.max_level {
             self.max_level = level;
         }
+        // with seed_per_level, each level is generated only by its own seed
+        if let Some(seed) = self.config_global.level_seed(self.branch, level) {
+            self.rng.reseed(seed);
+            item_handle.rng().reseed(seed);
+            enemies.rng().reseed(seed);
+        }
         let (width, height) = (self.config_global.width, self.config_global.height);
-        let mut floor = Floor::gen_floor(level, &self.config, width, height, &mut self.rng)
-            .chain_err(|| ERR_STR)?;
+        let branches = &self.config.branches;
+        let branch = (self.branch.checked_sub(1)).and_then(|i| branches.get(i as usize));
+        let bonus_level = branch.map_or(0, |b| b.bonus_level);
+        let config = branch
+            .and_then(|b| b.dungeon.as_ref())
+            .map_or(&self.config, |c| &**c);
+        let mut floor =
+            Floor::gen_floor(level, config, width, height, &mut self.rng).chain_err(|| ERR_STR)?;
         debug!("[Dungeon::new_level] field: {}", floor.field);
         // setup gold
         let set_gold = !game_info.is_cleared || level >= self.max_level;
         debug!("[Dungeon::new_level] set_gold: {}", set_gold);
-        floor.setup_items(level, item_handle, set_gold, &mut self.rng);
+        floor.setup_items(level + bonus_level, item_handle, set_gold, &mut self.rng);
         // place stair
         floor.setup_stair(&mut self.rng).chain_err(|| ERR_STR)?;
+        if self.branch == 0 {
+            for (i, branch) in self.config.branches.iter().enumerate() {
+                if branch.entry_level == level {
+                    floor
+                        .setup_branch_stair(i as u32 + 1, &mut self.rng)
+                        .chain_err(|| ERR_STR)?;
+                }
+            }
+        }
         // place enemies
         if !is_initial {
             enemies.remove_enemies();
         }
-        floor.place_enemies(level, self.lev_add(), enemies, &mut self.rng);
+        let lev_add = self.lev_add() + bonus_level;
+        floor.place_enemies(level, self.branch, lev_add, enemies, &mut self.rng);
         // place traps (STUB)
         if !self.config_global.hide_dungeon {
             let xmax = self.config_global.width.0;
@@ -469,12 +721,28 @@ impl Dungeon {
                 });
         }
         ::std::mem::swap(&mut self.current_floor, &mut floor);
+        self.dist_cache.clear();
         if !is_initial {
+            self.past_hash.push(&floor);
             self.past_floors.push(floor);
         }
         Ok(())
     }
 
+    fn address(&self, cd: Coord) -> Address {
+        Address::new(self.level, cd).with_branch(self.branch)
+    }
+
+    fn current_branch(&self) -> Option<&Branch> {
+        let idx = self.branch.checked_sub(1)?;
+        self.config.branches.get(idx as usize)
+    }
+
+    /// the deepest level of the current branch
+    fn branch_end(&self) -> Option<u32> {
+        self.current_branch().map(|b| b.entry_level + b.depth)
+    }
+
     fn lev_add(&self) -> u32 {
         if self.config.amulet_level < self.level {
             self.level - self.config.amulet_level
@@ -486,7 +754,7 @@ impl Dungeon {
 
 #[derive(Clone)]
 struct DistCache {
-    cache: VecDeque<(Array2<u32>, Coord)>,
+    cache: VecDeque<(Array2<u32>, Coord, bool)>,
 }
 
 impl DistCache {
@@ -497,12 +765,12 @@ impl DistCache {
         }
     }
     fn make_dist_map(&mut self, floor: &Floor, cd: Coord, is_enemy: bool) -> &Array2<u32> {
-        if let Some(pos) = self.cache.iter().position(|t| t.1 == cd) {
+        if let Some(pos) = self.cache.iter().position(|t| t.1 == cd && t.2 == is_enemy) {
             return &self.cache[pos].0;
         }
         let dist_map = floor.make_dist_map(cd, is_enemy);
         let len = self.cache.len();
-        self.cache.push_back((dist_map, cd));
+        self.cache.push_back((dist_map, cd, is_enemy));
         if len > Self::MAX_CACHED_DIST {
             self.cache.pop_front();
             &self.cache[len - 1].0
@@ -510,6 +778,14 @@ impl DistCache {
             &self.cache[len].0
         }
     }
+    /// discard the maps for the player or enemies
+    fn invalidate(&mut self, is_enemy: bool) {
+        self.cache.retain(|t| t.2 != is_enemy);
+    }
+    /// discard all maps, e.g. when the floor has changed
+    fn clear(&mut self) {
+        self.cache.clear();
+    }
 }
 
 /// Address in the dungeon.
@@ -520,24 +796,44 @@ pub struct Address {
     pub level: u32,
     /// coordinate
     pub cd: Coord,
+    /// branch id(0 means the main dungeon)
+    #[serde(default)]
+    pub branch: u32,
 }
 
 impl Address {
     pub fn new(lev: u32, cd: Coord) -> Self {
-        Address { level: lev, cd }
+        Address {
+            level: lev,
+            cd,
+            branch: 0,
+        }
+    }
+    pub fn with_branch(self, branch: u32) -> Self {
+        Address { branch, ..self }
     }
     pub fn from_path(p: &DungeonPath) -> Self {
         Address {
             level: p[0] as u32,
             cd: Coord::new(p[1], p[2]),
+            branch: p.0.get(3).map_or(0, |&b| b as u32),
         }
     }
 }
 
 #[cfg(test)]
 mod test {
-    use super::{Address, Coord, Direction, DungeonPath, MoveResult, TupleMap2};
-    use crate::{GameConfig, RunTime};
+    use super::{
+        Address, Coord, Direction, Dungeon, DungeonPath, DungeonTrait, Floor, GameInfo, Get2D,
+        ItemHandler, MoveResult, Positioned, RectRange, Surface, TravelTarget, TupleMap2,
+    };
+    use crate::actions;
+    use crate::character::Action;
+    use crate::dungeon::DungeonStyle;
+    use crate::input::InputCode;
+    use crate::tile::Drawable;
+    use crate::{GameConfig, GameMsg, Reaction, RunTime};
+    use std::collections::BTreeSet;
     // tiny dungeon setting
     const CONFIG: &str = r#"
 {
@@ -571,4 +867,265 @@ mod test {
         };
         check_move(Coord::new(9, 9), Coord::new(28, 4), Direction::Right);
     }
+    #[test]
+    fn test_floor_at() {
+        let mut config = GameConfig::from_json(CONFIG).unwrap();
+        config.seed_per_level = true;
+        let mut runtime = config.clone().build().unwrap();
+        for _ in 0..3 {
+            // some actions which consume random numbers
+            for _ in 0..5 {
+                runtime
+                    .react_to_input(InputCode::Act(Action::Search))
+                    .unwrap();
+            }
+            let RunTime {
+                game_info,
+                dungeon,
+                item,
+                player,
+                enemies,
+                ..
+            } = &mut runtime;
+            actions::new_level(game_info, &mut **dungeon, item, player, enemies, false).unwrap();
+        }
+        let floor = config.build_floor(5, 4).unwrap();
+        let dungeon = setup_dungeon(&config, 4);
+        assert_eq!(dungeon.level, 4);
+        let mut count = 0;
+        runtime
+            .dungeon
+            .draw(&mut |Positioned(cd, tile)| {
+                if tile != Surface::NONE {
+                    assert_eq!(floor.field.get_p(cd).surface.tile(), tile);
+                    count += 1;
+                }
+                Ok(())
+            })
+            .unwrap();
+        assert!(count > 0);
+        assert_eq!(
+            format!("{}", floor.field),
+            format!("{}", dungeon.current_floor.field)
+        );
+        let items = |f: &Floor| f.items.keys().cloned().collect::<BTreeSet<_>>();
+        assert_eq!(items(&floor), items(&dungeon.current_floor));
+    }
+    #[test]
+    fn test_start_level() {
+        let mut config = GameConfig::from_json(CONFIG).unwrap();
+        config.start_level = 3;
+        let runtime = config.clone().build().unwrap();
+        let status = runtime.player_status();
+        assert_eq!(status.dungeon_level, 3);
+        assert!(runtime.history(&status).is_some());
+        // the game starts on the floor build_floor makes, without seed_per_level
+        assert!(!config.seed_per_level);
+        let floor = config.build_floor(config.seed.unwrap(), 3).unwrap();
+        let mut count = 0;
+        runtime
+            .dungeon
+            .draw(&mut |Positioned(cd, tile)| {
+                if tile != Surface::NONE {
+                    assert_eq!(floor.field.get_p(cd).surface.tile(), tile);
+                    count += 1;
+                }
+                Ok(())
+            })
+            .unwrap();
+        assert!(count > 0);
+        let dungeon = setup_dungeon(&config, 3);
+        assert_eq!(
+            format!("{}", floor.field),
+            format!("{}", dungeon.current_floor.field)
+        );
+        let items = |f: &Floor| f.items.keys().cloned().collect::<BTreeSet<_>>();
+        assert_eq!(items(&floor), items(&dungeon.current_floor));
+    }
+    #[test]
+    fn test_bsp_layout() {
+        let config = r#"{
+            "seed": 1,
+            "dungeon": { "style": "rogue", "room_layout": { "kind": "bsp" } }
+        }"#;
+        let config = GameConfig::from_json(config).unwrap();
+        let mut rooms = BTreeSet::new();
+        for level in 1..10 {
+            let floor = config.build_floor(1, level).unwrap();
+            rooms.insert(floor.rooms.len());
+        }
+        assert!(rooms.len() > 1);
+    }
+    #[test]
+    fn test_branch() {
+        let mut config = GameConfig::from_json(CONFIG).unwrap();
+        if let DungeonStyle::Rogue(ref mut c) = config.dungeon {
+            let branch = r#"{ "entry_level": 1, "depth": 2 }"#;
+            c.branches.push(serde_json::from_str(branch).unwrap());
+        }
+        let global = config.to_global().unwrap();
+        let mut item = ItemHandler::new(config.item.clone(), global.seed);
+        let mut enemies = config.enemies.clone().build(global.seed);
+        let mut dungeon = setup_dungeon(&config, 1);
+        let info = GameInfo::new();
+        let stair = |floor: &Floor, side: bool| {
+            RectRange::zero_start(32, 16)
+                .unwrap()
+                .into_iter()
+                .map(Coord::from)
+                .find(|&cd| {
+                    floor.field.get_p(cd).surface == Surface::Stair
+                        && floor.branch_stair(cd).is_some() == side
+                })
+                .unwrap()
+        };
+        let mut go_down = |dungeon: &mut Dungeon, side: bool| {
+            let cd = stair(&dungeon.current_floor, side);
+            let path = DungeonPath::from(dungeon.address(cd));
+            assert!(dungeon.is_downstair(&path));
+            dungeon
+                .new_level(&path, &info, &mut item, &mut enemies)
+                .unwrap();
+            (dungeon.level, dungeon.branch)
+        };
+        assert_eq!(dungeon.current_floor.branch_stairs.len(), 1);
+        assert_eq!(go_down(&mut dungeon.clone(), false), (2, 0));
+        assert_eq!(go_down(&mut dungeon, true), (2, 1));
+        let path = dungeon.select_cell(false).unwrap();
+        assert_eq!(path.0.len(), 4);
+        assert_eq!(Address::from_path(&path).branch, 1);
+        let main_floor = config.build_floor(5, 2).unwrap();
+        assert_ne!(
+            format!("{}", main_floor.field),
+            format!("{}", dungeon.current_floor.field)
+        );
+        assert_eq!(go_down(&mut dungeon, false), (3, 1));
+        assert_eq!(go_down(&mut dungeon, false), (4, 0));
+    }
+    #[test]
+    fn test_light_radius() {
+        let max_dist = |light_radius: u32| {
+            let mut config = GameConfig::from_json(CONFIG).unwrap();
+            if let DungeonStyle::Rogue(ref mut c) = config.dungeon {
+                // all rooms are dark and not maze
+                c.dark_level = 1;
+                c.maze_rate_inv = 10000;
+                c.light_radius = light_radius;
+            }
+            let mut enemies = config.enemies.clone().build(0);
+            let mut dungeon = setup_dungeon(&config, 1);
+            let player = dungeon.select_cell(true).unwrap();
+            dungeon.enter_room(&player, &mut enemies).unwrap();
+            let cd = dungeon.path_to_cd(&player);
+            let fov = &dungeon.current_floor.fov;
+            fov.iter().map(|c| c.move_dist(cd)).max().unwrap()
+        };
+        assert_eq!(max_dist(1), 1);
+        assert!(max_dist(3) > 1);
+    }
+    #[test]
+    fn test_travel() {
+        let mut config = GameConfig::from_json(CONFIG).unwrap();
+        config.enemies.enemies.clear();
+        let mut runtime = config.build().unwrap();
+        let mut travel = |target| {
+            let res = runtime
+                .react_to_input(InputCode::Act(Action::Travel(target)))
+                .unwrap();
+            let pos = runtime.player.pos.clone();
+            (res, runtime.dungeon.is_downstair(&pos))
+        };
+        let (res, _) = travel(TravelTarget::Cell(Coord::new(0, 0)));
+        assert!(res.contains(&Reaction::Notify(GameMsg::NoRoute)));
+        for _ in 0..200 {
+            let (res, on_stair) = travel(TravelTarget::DownStair);
+            if on_stair {
+                return;
+            }
+            if res.contains(&Reaction::Notify(GameMsg::NoRoute)) {
+                travel(TravelTarget::Unexplored);
+            }
+        }
+        panic!("Couldn't reach the downstair");
+    }
+    #[test]
+    fn test_explore() {
+        let mut config = GameConfig::from_json(CONFIG).unwrap();
+        config.enemies.enemies.clear();
+        let mut runtime = config.build().unwrap();
+        let start = runtime.player.pos.clone();
+        for _ in 0..50 {
+            runtime
+                .react_to_input(InputCode::Act(Action::Explore))
+                .unwrap();
+            let pos = runtime.player.pos.clone();
+            if runtime
+                .dungeon
+                .find_route(&pos, TravelTarget::DownStair)
+                .is_some()
+            {
+                assert_ne!(pos, start);
+                return;
+            }
+        }
+        panic!("Couldn't find the downstair");
+    }
+    #[test]
+    fn test_objects_out_of_sight() {
+        let mut config = GameConfig {
+            seed: Some(1),
+            ..Default::default()
+        };
+        config.enemies.enemies.clear();
+        let mut runtime = config.build().unwrap();
+        let (w, h) = runtime.screen_size();
+        let cells: Vec<_> = RectRange::zero_start(w.0, h.0)
+            .unwrap()
+            .into_iter()
+            .map(|cd| DungeonPath::from(Address::new(1, Coord::from(cd))))
+            .collect();
+        let mut seen = BTreeSet::new();
+        let mut disappeared = 0;
+        for _ in 0..50 {
+            let rows = runtime.screen_rows().unwrap();
+            for path in &cells {
+                let item = match runtime.dungeon.get_item(path) {
+                    Some(item) => item.tile().to_byte(),
+                    None => continue,
+                };
+                let cd = runtime.dungeon.path_to_cd(path);
+                let on_screen = rows[cd.y.0 as usize].as_bytes()[cd.x.0 as usize] == item;
+                if on_screen {
+                    seen.insert(cd);
+                } else if seen.contains(&cd) {
+                    disappeared += 1;
+                }
+            }
+            runtime
+                .react_to_input(InputCode::Act(Action::Explore))
+                .unwrap();
+        }
+        assert!(!seen.is_empty());
+        assert!(disappeared > 0);
+    }
+    fn setup_dungeon(config: &GameConfig, level: u32) -> Dungeon {
+        let mut global = config.to_global().unwrap();
+        global.start_level = level;
+        let mut item = ItemHandler::new(config.item.clone(), global.seed);
+        let mut enemies = config.enemies.clone().build(global.seed);
+        let rogue_config = match config.dungeon {
+            DungeonStyle::Rogue(ref c) => c.clone(),
+            _ => unreachable!(),
+        };
+        let seed = global.seed;
+        Dungeon::new(
+            rogue_config,
+            &global,
+            &GameInfo::new(),
+            &mut item,
+            &mut enemies,
+            seed,
+        )
+        .unwrap()
+    }
 }
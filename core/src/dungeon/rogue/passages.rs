@@ -6,7 +6,9 @@ use fenwick::FenwickSet;
 use fixedbitset::FixedBitSet;
 use rect_iter::{IntoTuple2, RectRange};
 use rng::{RngHandle, SliceRandom};
+use std::cmp::{max, min};
 use std::collections::HashMap;
+use std::ops::Range;
 use tuple_map::TupleMap2;
 
 /// make passages between rooms
@@ -21,7 +23,47 @@ pub(super) fn dig_passges<F>(
 where
     F: FnMut(Positioned<Surface>) -> GameResult<()>,
 {
-    let mut graph = RoomGraph::new(xrooms, yrooms);
+    let graph = RoomGraph::new(xrooms, yrooms);
+    connect_rooms(
+        rooms,
+        graph,
+        rng,
+        max_extra_edges,
+        |room1, room2, d, rng| connect_2rooms(room1, room2, d, rng, &mut register),
+    )
+}
+
+/// make passages between rooms generated by binary space partitioning
+pub(super) fn dig_passges_bsp<F>(
+    rooms: &[Room],
+    rng: &mut RngHandle,
+    max_extra_edges: u32,
+    mut register: F,
+) -> GameResult<()>
+where
+    F: FnMut(Positioned<Surface>) -> GameResult<()>,
+{
+    let graph = RoomGraph::from_partitions(rooms);
+    connect_rooms(
+        rooms,
+        graph,
+        rng,
+        max_extra_edges,
+        |room1, room2, d, rng| connect_2partitions(room1, room2, d, rng, &mut register),
+    )
+}
+
+/// connect all rooms as a tree, and then add some edges
+fn connect_rooms<C>(
+    rooms: &[Room],
+    mut graph: RoomGraph,
+    rng: &mut RngHandle,
+    max_extra_edges: u32,
+    mut connect: C,
+) -> GameResult<()>
+where
+    C: FnMut(&Room, &Room, Direction, &mut RngHandle) -> GameResult<()>,
+{
     let num_rooms = rooms.len();
     let mut selected = FenwickSet::with_capacity(num_rooms);
     let mut cur_room = rng.range(0..num_rooms);
@@ -35,13 +77,7 @@ where
         if let Some((nxt_room, direction)) = nxt {
             selected.insert(nxt_room);
             graph.coonect(cur_room, nxt_room);
-            connect_2rooms(
-                &rooms[cur_room],
-                &rooms[nxt_room],
-                direction,
-                rng,
-                &mut register,
-            )?;
+            connect(&rooms[cur_room], &rooms[nxt_room], direction, rng)?;
         } else {
             cur_room = selected.select(rng).unwrap();
         }
@@ -57,7 +93,7 @@ where
         });
         if let Some((room2, direction)) = selected {
             graph.coonect(room1, room2);
-            connect_2rooms(&rooms[room1], &rooms[room2], direction, rng, &mut register)?;
+            connect(&rooms[room1], &rooms[room2], direction, rng)?;
         }
     }
     Ok(())
@@ -129,6 +165,67 @@ where
         .chain_err(|| "passages::connect_2rooms")
 }
 
+/// connect 2 rooms in adjacent partitions.
+/// The passage goes through the last line of room1's partition and
+/// the first line of room2's partition, which are always empty.
+fn connect_2partitions<F>(
+    room1: &Room,
+    room2: &Room,
+    direction: Direction,
+    rng: &mut RngHandle,
+    register: &mut F,
+) -> GameResult<()>
+where
+    F: FnMut(Positioned<Surface>) -> GameResult<()>,
+{
+    let (room1, room2, direction) = match direction {
+        Direction::Up | Direction::Left => (room2, room1, direction.reverse()),
+        _ => (room1, room2, direction),
+    };
+    let start = select_start_or_end(room1, direction, rng);
+    let end = select_start_or_end(room2, direction.reverse(), rng);
+    register(Positioned(start, door_kind(room1)))?;
+    register(Positioned(end, door_kind(room2)))?;
+    let (area1, area2) = (&room1.assigned_area, &room2.assigned_area);
+    let overlap = |r1: &Range<i32>, r2: &Range<i32>| max(r1.start, r2.start)..min(r1.end, r2.end);
+    let corners = match direction {
+        Direction::Down => {
+            let x = rng.range(overlap(area1.get_x(), area2.get_x()));
+            let y = area2.get_y().start;
+            [
+                Coord::new(start.x, y - 1),
+                Coord::new(x, y - 1),
+                Coord::new(x, y),
+                Coord::new(end.x, y),
+            ]
+        }
+        Direction::Right => {
+            let y = rng.range(overlap(area1.get_y(), area2.get_y()));
+            let x = area2.get_x().start;
+            [
+                Coord::new(x - 1, start.y),
+                Coord::new(x - 1, y),
+                Coord::new(x, y),
+                Coord::new(x, end.y),
+            ]
+        }
+        _ => unreachable!(),
+    };
+    let mut path = Vec::new();
+    let mut cur = start;
+    for &next in corners.iter().chain(Some(&end)) {
+        while cur != next {
+            let diff = next - cur;
+            cur += Coord::new(diff.x.0.signum(), diff.y.0.signum());
+            path.push(cur);
+        }
+    }
+    path.pop();
+    path.into_iter()
+        .try_for_each(|cd| register(Positioned(cd, Surface::Passage)))
+        .chain_err(|| "passages::connect_2partitions")
+}
+
 fn door_kind(room: &Room) -> Surface {
     if room.is_normal() {
         Surface::Door
@@ -139,10 +236,12 @@ fn door_kind(room: &Room) -> Surface {
 
 fn select_start_or_end(room: &Room, direction: Direction, rng: &mut RngHandle) -> Coord {
     match room.kind {
-        RoomKind::Normal { ref range } => edges(range, direction, true)
-            .choose(rng)
-            .unwrap()
-            .to_owned(),
+        RoomKind::Normal { ref range } | RoomKind::Treasure { ref range } => {
+            edges(range, direction, true)
+                .choose(rng)
+                .unwrap()
+                .to_owned()
+        }
         RoomKind::Maze(ref maze) => {
             let mut range = maze.range.clone();
             while range.is_valid() {
@@ -231,6 +330,29 @@ impl RoomGraph {
             .collect();
         RoomGraph { inner }
     }
+    fn from_partitions(rooms: &[Room]) -> Self {
+        let num_rooms = rooms.len();
+        let inner = rooms
+            .iter()
+            .enumerate()
+            .map(|(i, room1)| {
+                let candidates = rooms
+                    .iter()
+                    .enumerate()
+                    .filter_map(|(j, room2)| {
+                        let d = adjacent_direction(&room1.assigned_area, &room2.assigned_area)?;
+                        Some((j, d))
+                    })
+                    .collect();
+                Node {
+                    connections: FixedBitSet::with_capacity(num_rooms),
+                    candidates,
+                    id: i,
+                }
+            })
+            .collect();
+        RoomGraph { inner }
+    }
     fn coonect(&mut self, node1: usize, node2: usize) {
         self.inner[node1].connections.insert(node2);
         self.inner[node2].connections.insert(node1);
@@ -266,9 +388,29 @@ impl Node {
     }
 }
 
+/// if 2 partitions share an edge, returns the direction from `a` to `b`
+fn adjacent_direction(a: &RectRange<i32>, b: &RectRange<i32>) -> Option<Direction> {
+    let overlaps = |r1: &Range<i32>, r2: &Range<i32>| max(r1.start, r2.start) < min(r1.end, r2.end);
+    let (ax, ay, bx, by) = (a.get_x(), a.get_y(), b.get_x(), b.get_y());
+    if overlaps(ax, bx) {
+        if ay.end == by.start {
+            return Some(Direction::Down);
+        } else if by.end == ay.start {
+            return Some(Direction::Up);
+        }
+    }
+    if overlaps(ay, by) {
+        if ax.end == bx.start {
+            return Some(Direction::Right);
+        } else if bx.end == ax.start {
+            return Some(Direction::Left);
+        }
+    }
+    None
+}
+
 #[test]
 fn test_inclusive_edges() {
-    use std::ops::Range;
     let range = RectRange::from_ranges(5..10, 6..9).unwrap();
     let edge_vec = |xfix, fix, range: Range<i32>| -> Vec<_> {
         if xfix {
@@ -303,25 +445,29 @@ mod test {
         let rooms = rooms::test::gen(10);
         let mut buffer = rooms::test::draw_to_buffer(&rooms);
         let mut rng = RngHandle::new();
-        dig_passges(
-            &rooms,
-            X(3),
-            Y(3),
-            &mut rng,
-            5,
-            |Positioned(cd, surface)| {
-                buffer
-                    .try_get_mut_p(cd)
-                    .and_then(|buf| {
-                        *buf = surface;
-                        Ok(())
-                    })
-                    .into_chained(|| "passages::test::to_buffer")
-            },
-        )
+        dig_passges(&rooms, X(3), Y(3), &mut rng, 5, |p| {
+            register(&mut buffer, p)
+        })
         .unwrap();
         buffer
     }
+    fn to_buffer_bsp() -> Vec<Vec<Surface>> {
+        let rooms = rooms::test::gen_bsp(10);
+        let mut buffer = rooms::test::draw_to_buffer(&rooms);
+        let mut rng = RngHandle::new();
+        dig_passges_bsp(&rooms, &mut rng, 5, |p| register(&mut buffer, p)).unwrap();
+        buffer
+    }
+    fn register(buffer: &mut Vec<Vec<Surface>>, p: Positioned<Surface>) -> GameResult<()> {
+        let Positioned(cd, surface) = p;
+        buffer
+            .try_get_mut_p(cd)
+            .and_then(|buf| {
+                *buf = surface;
+                Ok(())
+            })
+            .into_chained(|| "passages::test::to_buffer")
+    }
     #[test]
     #[ignore]
     fn print_passages() {
@@ -337,9 +483,25 @@ mod test {
         }
     }
     #[test]
+    #[ignore]
+    fn print_passages_bsp() {
+        let buffer = to_buffer_bsp();
+        print_impl(&buffer);
+    }
+    #[test]
     fn connectivity() {
         for _ in 0..1000 {
-            let buffer = to_buffer();
+            check_connectivity(to_buffer());
+        }
+    }
+    #[test]
+    fn connectivity_bsp() {
+        for _ in 0..1000 {
+            check_connectivity(to_buffer_bsp());
+        }
+    }
+    fn check_connectivity(buffer: Vec<Vec<Surface>>) {
+        {
             let (xlen, ylen) = (buffer[0].len(), buffer.len());
             let start = RectRange::zero_start(xlen, ylen)
                 .unwrap()
@@ -348,7 +510,7 @@ mod test {
                 .map(|t| Coord::new(t.0 as i32, t.1 as i32));
             let start = match start {
                 Some(s) => s,
-                None => continue, // all floor is maze
+                None => return, // all floor is maze
             };
             let mut visited = vec![vec![false; xlen]; ylen];
             *visited.get_mut_p(start) = true;
@@ -16,6 +16,8 @@ pub enum RoomKind {
     Maze(Box<maze::Maze>),
     /// passage only(gone room)
     Empty { up_left: Coord },
+    /// normal room packed with gold and sleeping monsters
+    Treasure { range: RectRange<i32> },
 }
 
 /// A data structure representing a room in the dungeon
@@ -60,7 +62,7 @@ impl Room {
         R: FnMut(Positioned<Surface>) -> GameResult<()>,
     {
         match self.kind {
-            RoomKind::Normal { ref range } => range
+            RoomKind::Normal { ref range } | RoomKind::Treasure { ref range } => range
                 .iter()
                 .try_for_each(|cd| {
                     let surface = if range.is_horiz_edge(cd) {
@@ -83,7 +85,7 @@ impl Room {
     /// Returns the 'room' range
     pub fn range(&self) -> Option<&RectRange<i32>> {
         match self.kind {
-            RoomKind::Normal { ref range } => Some(range),
+            RoomKind::Normal { ref range } | RoomKind::Treasure { ref range } => Some(range),
             RoomKind::Maze(ref maze) => Some(&maze.range),
             _ => None,
         }
@@ -114,12 +116,24 @@ impl Room {
             false
         }
     }
+    /// a room with walls, including treasure rooms
     pub fn is_normal(&self) -> bool {
         match self.kind {
-            RoomKind::Normal { .. } => true,
+            RoomKind::Normal { .. } | RoomKind::Treasure { .. } => true,
             _ => false,
         }
     }
+    pub fn is_treasure(&self) -> bool {
+        matches!(self.kind, RoomKind::Treasure { .. })
+    }
+    /// change a normal room into a treasure room
+    pub(super) fn make_treasure(&mut self) {
+        let range = match self.kind {
+            RoomKind::Normal { ref range } => range.clone(),
+            _ => return,
+        };
+        self.kind = RoomKind::Treasure { range };
+    }
     pub fn is_empty(&self) -> bool {
         match self.kind {
             RoomKind::Empty { .. } => true,
@@ -146,7 +160,7 @@ impl Room {
 
 fn gen_empty_cells(kind: &RoomKind) -> FenwickSet {
     match kind {
-        RoomKind::Normal { range } => {
+        RoomKind::Normal { range } | RoomKind::Treasure { range } => {
             let len = range.len();
             let mut set = FenwickSet::with_capacity(len);
             range.iter().enumerate().for_each(|(i, cd)| {
@@ -210,6 +224,89 @@ pub(super) fn gen_rooms(
         .collect()
 }
 
+/// generate rooms by binary space partitioning
+pub(super) fn gen_rooms_bsp(
+    level: u32,
+    config: &Config,
+    min_partition: Coord,
+    max_partition: Coord,
+    width: X,
+    height: Y,
+    rng: &mut RngHandle,
+) -> GameResult<Vec<Room>> {
+    // a partition needs margins around the room, so that passages can go around it
+    let min_size = config.min_room_size + Coord::new(3, 3);
+    if min_partition.x < min_size.x || min_partition.y < min_size.y {
+        return Err(ErrorId::InvalidSetting
+            .into_with(|| "[rooms::gen_rooms_bsp] min_partition is too small for min_room_size"));
+    }
+    // the first and the last lines are used for messages and status
+    let whole = RectRange::from_ranges(0..width.0, 1..height.0 - 1)
+        .filter(|r| r.xlen() >= min_partition.x.0 && r.ylen() >= min_partition.y.0)
+        .ok_or_else(|| {
+            ErrorId::InvalidSetting
+                .into_with(|| "[rooms::gen_rooms_bsp] min_partition is larger than the screen")
+        })?;
+    let mut partitions = Vec::new();
+    split_space(whole, min_partition, max_partition, rng, &mut partitions);
+    let room_num = partitions.len();
+    let empty_rooms: FixedBitSet = {
+        let empty_num = ::std::cmp::min(rng.range(0..=config.max_empty_rooms), room_num as u32 - 1);
+        rng.select(0..room_num).take(empty_num as usize).collect()
+    };
+    partitions
+        .into_iter()
+        .enumerate()
+        .map(|(i, area)| {
+            let lower_left = Coord::from(area.lower_left()) + Coord::new(1, 1);
+            let room_size = Coord::new(area.xlen() - 2, area.ylen() - 2);
+            let is_empty = empty_rooms.contains(i);
+            let mut room = make_room(is_empty, room_size, lower_left, i, config, level, rng)?;
+            room.assigned_area = area;
+            Ok(room)
+        })
+        .collect()
+}
+
+/// divide `area` recursively and push the partitions to `res`
+fn split_space(
+    area: RectRange<i32>,
+    min: Coord,
+    max: Coord,
+    rng: &mut RngHandle,
+    res: &mut Vec<RectRange<i32>>,
+) {
+    let (w, h) = (area.xlen(), area.ylen());
+    let (can_split_x, can_split_y) = (w >= min.x.0 * 2, h >= min.y.0 * 2);
+    let (too_wide, too_high) = (w > max.x.0, h > max.y.0);
+    if !(can_split_x || can_split_y) || (!too_wide && !too_high && rng.does_happen(2)) {
+        res.push(area);
+        return;
+    }
+    let split_x = match (can_split_x, can_split_y) {
+        (true, true) if too_wide != too_high => too_wide,
+        // divide the longer side, where lengths are normalized by the minimum size
+        (true, true) => w * min.y.0 >= h * min.x.0,
+        (x, _) => x,
+    };
+    let (xr, yr) = (area.get_x().clone(), area.get_y().clone());
+    let (area1, area2) = if split_x {
+        let x = xr.start + rng.range(min.x.0..=w - min.x.0);
+        (
+            RectRange::from_ranges(xr.start..x, yr.clone()),
+            RectRange::from_ranges(x..xr.end, yr),
+        )
+    } else {
+        let y = yr.start + rng.range(min.y.0..=h - min.y.0);
+        (
+            RectRange::from_ranges(xr.clone(), yr.start..y),
+            RectRange::from_ranges(xr, y..yr.end),
+        )
+    };
+    split_space(area1.unwrap(), min, max, rng, res);
+    split_space(area2.unwrap(), min, max, rng, res);
+}
+
 /// generata a room
 pub(super) fn make_room(
     is_empty: bool,
@@ -281,6 +378,24 @@ pub(super) mod test {
         let mut rng = RngHandle::new();
         gen_rooms(level, &config, w, h, &mut rng).unwrap()
     }
+    pub fn gen_bsp(level: u32) -> Vec<Room> {
+        let config = Config {
+            maze_rate_inv: 5,
+            ..Config::default()
+        };
+        let (w, h) = (X(80), Y(24));
+        let mut rng = RngHandle::new();
+        gen_rooms_bsp(
+            level,
+            &config,
+            Coord::new(8, 7),
+            Coord::new(30, 12),
+            w,
+            h,
+            &mut rng,
+        )
+        .unwrap()
+    }
     pub fn draw_to_buffer(rooms: &[Room]) -> Vec<Vec<Surface>> {
         let mut buffer = vec![vec![Surface::None; 80]; 24];
         for room in rooms {
@@ -305,6 +420,30 @@ pub(super) mod test {
         }
     }
     #[test]
+    fn bsp_partitions() {
+        for i in 0..100 {
+            let rooms = gen_bsp(i % 20);
+            assert!(rooms.len() >= 2);
+            let area: usize = rooms.iter().map(|r| r.assigned_area.len()).sum();
+            assert_eq!(area, 80 * 22);
+            for (i, room1) in rooms.iter().enumerate() {
+                if let Some(range) = room1.range() {
+                    let inner = &room1.assigned_area;
+                    assert!(inner.get_x().start < range.get_x().start);
+                    assert!(inner.get_y().start < range.get_y().start);
+                    assert!(range.get_x().end < inner.get_x().end);
+                    assert!(range.get_y().end < inner.get_y().end);
+                }
+                for room2 in rooms.iter().skip(i + 1) {
+                    assert!(room1
+                        .assigned_area
+                        .intersection(&room2.assigned_area)
+                        .is_none());
+                }
+            }
+        }
+    }
+    #[test]
     fn pos_check() {
         let (xrooms, yrooms) = (3, 3);
         use enum_iterator::IntoEnumIterator;
@@ -0,0 +1,110 @@
+//! route finding on a field, shared by dungeons made of `Surface`
+use super::rogue::Surface;
+use super::{Cell, Coord, Direction, Field};
+use enum_iterator::IntoEnumIterator;
+use ndarray::Array2;
+use rect_iter::{Get2D, GetMut2D};
+use std::collections::VecDeque;
+
+/// make the distance map from `from`, moving between cells when `can_move` allows.
+/// if `known_only` is true, only the cells the player knows are used.
+pub(super) fn make_dist_map<F>(
+    field: &Field<Surface>,
+    from: Coord,
+    known_only: bool,
+    can_move: F,
+) -> Array2<u32>
+where
+    F: Fn(Coord, Direction) -> bool,
+{
+    let (w, h) = (field.width(), field.height());
+    let inf = u32::MAX;
+    let mut dist = Array2::from_elem([h.0 as usize, w.0 as usize], inf);
+    let mut queue = VecDeque::new();
+    if let Ok(d) = dist.try_get_mut_p(from) {
+        *d = 0;
+        queue.push_back(from);
+    }
+    while let Some(current) = queue.pop_front() {
+        let cdist = *dist.get_p(current);
+        for d in Direction::into_enum_iter().take(8) {
+            let next = current + d.to_cd();
+            if let Ok(ndist) = dist.try_get_mut_p(next) {
+                if *ndist != inf || !can_move(current, d) {
+                    continue;
+                }
+                if known_only && !field.get_p(next).is_obj_visible() {
+                    continue;
+                }
+                queue.push_back(next);
+                *ndist = cdist + 1;
+            }
+        }
+    }
+    dist
+}
+
+/// the nearest cell which the player knows and satisfies `pred`
+pub(super) fn nearest_known<F>(
+    field: &Field<Surface>,
+    dist_map: &Array2<u32>,
+    pred: F,
+) -> Option<Coord>
+where
+    F: Fn(Coord, &Cell<Surface>) -> bool,
+{
+    field
+        .size()
+        .into_iter()
+        .map(Coord::from)
+        .filter(|&cd| {
+            let cell = field.get_p(cd);
+            cell.is_obj_visible() && pred(cd, cell)
+        })
+        .filter_map(|cd| match *dist_map.get_p(cd) {
+            u32::MAX => None,
+            dist => Some((dist, cd)),
+        })
+        .min_by_key(|t| t.0)
+        .map(|t| t.1)
+}
+
+/// judge if the player can explore more from `cd`
+pub(super) fn is_frontier(field: &Field<Surface>, cd: Coord) -> bool {
+    let cell = field.get_p(cd);
+    if !cell.surface.can_walk() || cell.is_visited() {
+        return false;
+    }
+    let range = field.size();
+    Direction::into_enum_iter().take(8).any(|d| {
+        let next = cd + d.to_cd();
+        range.contains(next) && !field.get_p(next).is_obj_visible()
+    })
+}
+
+/// trace `dist_map` from `from` to its origin, moving when `can_move` allows
+pub(super) fn trace_route<F>(
+    from: Coord,
+    dist_map: &Array2<u32>,
+    can_move: F,
+) -> Option<Vec<Direction>>
+where
+    F: Fn(Coord, Direction) -> bool,
+{
+    let mut dist = *dist_map.try_get_p(from).ok()?;
+    if dist == u32::MAX {
+        return None;
+    }
+    let mut route = Vec::with_capacity(dist as usize);
+    let mut current = from;
+    while dist > 0 {
+        let d = Direction::into_enum_iter().take(8).find(|&d| {
+            let next = current + d.to_cd();
+            dist_map.try_get_p(next).ok() == Some(&(dist - 1)) && can_move(current, d)
+        })?;
+        route.push(d);
+        current += d.to_cd();
+        dist -= 1;
+    }
+    Some(route)
+}
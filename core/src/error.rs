@@ -78,6 +78,8 @@ pub enum ErrorId {
     InvalidSetting,
     #[fail(display = "Json parsing error: {}", _0)]
     Json(JsonError),
+    #[fail(display = "Replay doesn't match the game")]
+    ReplayMismatch,
     #[fail(display = "Invalid conversion")]
     InvalidConversion,
     #[fail(display = "Maybe software bug")]
@@ -0,0 +1,226 @@
+//! line-delimited JSON log of an episode
+use character::{fight::Hit, player::Status, Exp};
+use error::*;
+use input::InputCode;
+use item::ItemKind;
+use observer::{EventContext, GameObserver};
+use serde_json;
+use std::fs::File;
+use std::io::{LineWriter, Write};
+use std::mem;
+use std::path::Path;
+use {GameConfig, Reaction, RunTime, SmallStr};
+
+/// one line of the event log, like `{"step": {...}}`
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LogEntry {
+    /// first line of an episode, with the config to play the same game
+    Start {
+        seed: u128,
+        config: Box<GameConfig>,
+        status: Status,
+    },
+    /// an input and what happened by it
+    Step {
+        step: usize,
+        turn: u32,
+        input: InputCode,
+        reactions: Vec<Reaction>,
+        status: Status,
+        events: Vec<GameEvent>,
+    },
+}
+
+/// key events happened in a step
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum GameEvent {
+    DamageDealt(Hit),
+    DamageTaken(Hit),
+    Killed { name: SmallStr, exp: Exp },
+    ItemPickup { kind: ItemKind, num: u32 },
+    LevelChange { from: u32, to: u32 },
+    Death { cause: String },
+}
+
+/// observer which writes a `LogEntry` per input
+pub struct EventLogger<W: Write> {
+    writer: W,
+    step: usize,
+    events: Vec<GameEvent>,
+}
+
+impl EventLogger<LineWriter<File>> {
+    /// log to a file
+    pub fn create(runtime: &RunTime, path: impl AsRef<Path>) -> GameResult<Self> {
+        let path = path.as_ref();
+        let file = File::create(path)
+            .into_chained(|| format!("EventLogger::create: failed to open {:?}", path))?;
+        EventLogger::start(runtime, LineWriter::new(file))
+    }
+}
+
+impl<W: Write> EventLogger<W> {
+    /// write the start entry of the episode and return a logger
+    pub fn start(runtime: &RunTime, writer: W) -> GameResult<Self> {
+        let mut logger = EventLogger {
+            writer,
+            step: 0,
+            events: vec![],
+        };
+        let entry = LogEntry::Start {
+            seed: runtime.seed(),
+            config: Box::new(GameConfig::resolved(runtime)),
+            status: runtime.player_status(),
+        };
+        logger.write(&entry)?;
+        Ok(logger)
+    }
+    fn write(&mut self, entry: &LogEntry) -> GameResult<()> {
+        serde_json::to_writer(&mut self.writer, entry)
+            .into_chained(|| "EventLogger::write: failed to serialize")?;
+        writeln!(self.writer).into_chained(|| "EventLogger::write: failed to write")
+    }
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+impl<W: Write> GameObserver for EventLogger<W> {
+    fn on_damage_dealt(&mut self, _ctx: &EventContext, hit: &Hit) {
+        self.events.push(GameEvent::DamageDealt(hit.clone()));
+    }
+    fn on_damage_taken(&mut self, _ctx: &EventContext, hit: &Hit) {
+        self.events.push(GameEvent::DamageTaken(hit.clone()));
+    }
+    fn on_kill(&mut self, _ctx: &EventContext, name: &str, exp: Exp) {
+        let name = SmallStr::from_str(name);
+        self.events.push(GameEvent::Killed { name, exp });
+    }
+    fn on_item_pickup(&mut self, _ctx: &EventContext, kind: &ItemKind, num: u32) {
+        let kind = kind.clone();
+        self.events.push(GameEvent::ItemPickup { kind, num });
+    }
+    fn on_level_change(&mut self, _ctx: &EventContext, from: u32, to: u32) {
+        self.events.push(GameEvent::LevelChange { from, to });
+    }
+    fn on_death(&mut self, _ctx: &EventContext, cause: &str) {
+        let cause = cause.to_owned();
+        self.events.push(GameEvent::Death { cause });
+    }
+    fn on_step(&mut self, ctx: &EventContext) {
+        self.step += 1;
+        let entry = LogEntry::Step {
+            step: self.step,
+            turn: ctx.turn,
+            input: ctx.input,
+            reactions: ctx.reactions.to_vec(),
+            status: ctx.runtime.player_status(),
+            events: mem::take(&mut self.events),
+        };
+        if let Err(e) = self.write(&entry) {
+            warn!("[EventLogger::on_step] {}", e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use input::Key;
+    use std::cell::RefCell;
+    use std::io;
+    use std::rc::Rc;
+    use GameConfig;
+    #[derive(Clone, Default)]
+    struct Shared(Rc<RefCell<Vec<u8>>>);
+    impl Write for Shared {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.borrow_mut().write(buf)
+        }
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+    #[test]
+    fn jsonl() {
+        let json = r#"
+{
+    "seed": 5,
+    "dungeon": {
+        "style": "custom",
+        "maps": [
+            [
+                "------",
+                "|@E.%|",
+                "------"
+            ],
+            [
+                "-----",
+                "|.@.|",
+                "-----"
+            ]
+        ]
+    }
+}
+"#;
+        let mut runtime = GameConfig::from_json(json).unwrap().build().unwrap();
+        let buf = Shared::default();
+        let logger = EventLogger::start(&runtime, buf.clone()).unwrap();
+        runtime.add_observer(Box::new(logger));
+        let mut inputs = 0;
+        let killed = |rt: &RunTime| rt.messages().iter().any(|m| m.text.contains("defeated"));
+        while !killed(&runtime) {
+            runtime.react_to_key(Key::Char('l')).unwrap();
+            inputs += 1;
+        }
+        for _ in 0..3 {
+            runtime.react_to_key(Key::Char('l')).unwrap();
+            inputs += 1;
+        }
+        runtime.react_to_key(Key::Char('>')).unwrap();
+        let buf = buf.0.borrow();
+        let entries: Vec<LogEntry> = String::from_utf8_lossy(&buf)
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(entries.len(), inputs + 2);
+        match &entries[0] {
+            LogEntry::Start { seed, config, .. } => {
+                assert_eq!(*seed, 5);
+                // the same game can be played from the log
+                assert_eq!(**config, *runtime.game_config());
+                let given = GameConfig::from_json(json).unwrap();
+                given.check_same_game(config).unwrap();
+            }
+            e => panic!("unexpected entry {:?}", e),
+        }
+        let events: Vec<_> = entries
+            .iter()
+            .filter_map(|e| match e {
+                LogEntry::Step { events, .. } => Some(events.iter()),
+                _ => None,
+            })
+            .flatten()
+            .collect();
+        assert!(events.iter().any(|e| matches!(e, GameEvent::Killed { .. })));
+        assert_eq!(
+            **events.last().unwrap(),
+            GameEvent::LevelChange { from: 1, to: 2 }
+        );
+        match entries.last().unwrap() {
+            LogEntry::Step {
+                step,
+                input,
+                status,
+                ..
+            } => {
+                assert_eq!(*step, inputs + 1);
+                assert_eq!(*input, runtime.saved_inputs()[inputs]);
+                assert_eq!(status.dungeon_level, 2);
+            }
+            e => panic!("unexpected entry {:?}", e),
+        }
+    }
+}
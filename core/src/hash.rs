@@ -0,0 +1,164 @@
+//! deterministic hashing of the game state
+use serde::{de::Error, Deserialize, Deserializer, Serialize, Serializer};
+use std::fmt;
+use std::hash::{Hash, Hasher};
+
+const FNV_OFFSET: u64 = 0xcbf2_9ce4_8422_2325;
+const FNV_PRIME: u64 = 0x0100_0000_01b3;
+
+/// FNV-1a hasher, which gives the same result on every platform and compiler version.
+/// Integers are always written in little endian and `usize` as `u64`.
+pub struct StateHasher(u64);
+
+impl Default for StateHasher {
+    fn default() -> Self {
+        StateHasher(FNV_OFFSET)
+    }
+}
+
+impl Hasher for StateHasher {
+    fn write(&mut self, bytes: &[u8]) {
+        for &b in bytes {
+            self.0 ^= u64::from(b);
+            self.0 = self.0.wrapping_mul(FNV_PRIME);
+        }
+    }
+    fn write_u16(&mut self, i: u16) {
+        self.write(&i.to_le_bytes());
+    }
+    fn write_u32(&mut self, i: u32) {
+        self.write(&i.to_le_bytes());
+    }
+    fn write_u64(&mut self, i: u64) {
+        self.write(&i.to_le_bytes());
+    }
+    fn write_u128(&mut self, i: u128) {
+        self.write(&i.to_le_bytes());
+    }
+    fn write_usize(&mut self, i: usize) {
+        self.write_u64(i as u64);
+    }
+    fn write_i16(&mut self, i: i16) {
+        self.write_u16(i as u16);
+    }
+    fn write_i32(&mut self, i: i32) {
+        self.write_u32(i as u32);
+    }
+    fn write_i64(&mut self, i: i64) {
+        self.write_u64(i as u64);
+    }
+    fn write_i128(&mut self, i: i128) {
+        self.write_u128(i as u128);
+    }
+    fn write_isize(&mut self, i: isize) {
+        self.write_u64(i as u64);
+    }
+    fn finish(&self) -> u64 {
+        self.0
+    }
+}
+
+/// game state which can be hashed independently of memory layout and iteration order
+pub trait HashState {
+    fn hash_state(&self, hasher: &mut StateHasher);
+}
+
+impl<T: HashState + ?Sized> HashState for &T {
+    fn hash_state(&self, hasher: &mut StateHasher) {
+        (**self).hash_state(hasher);
+    }
+}
+
+impl HashState for u32 {
+    fn hash_state(&self, hasher: &mut StateHasher) {
+        hasher.write_u32(*self);
+    }
+}
+
+/// running hash of the floors the player has left, which never change after that.
+/// Each floor is hashed only once, so hashing the state doesn't get slower in deep levels.
+#[derive(Clone, Copy, Debug, Default)]
+pub(crate) struct PastFloorsHash(u64);
+
+impl PastFloorsHash {
+    pub(crate) fn push(&mut self, floor: &impl HashState) {
+        let mut hasher = StateHasher::default();
+        hasher.write_u64(self.0);
+        floor.hash_state(&mut hasher);
+        self.0 = hasher.finish();
+    }
+}
+
+impl HashState for PastFloorsHash {
+    fn hash_state(&self, hasher: &mut StateHasher) {
+        hasher.write_u64(self.0);
+    }
+}
+
+/// hash elements of a `HashSet` in sorted order
+pub(crate) fn hash_set<'a, T: 'a + Ord + Hash>(
+    set: impl IntoIterator<Item = &'a T>,
+    hasher: &mut StateHasher,
+) {
+    let mut elements: Vec<_> = set.into_iter().collect();
+    elements.sort();
+    hasher.write_usize(elements.len());
+    elements.into_iter().for_each(|t| t.hash(hasher));
+}
+
+/// hash entries of a `HashMap` in the order of keys
+pub(crate) fn hash_map<K: Ord + Hash, V: HashState>(
+    entries: impl Iterator<Item = (K, V)>,
+    hasher: &mut StateHasher,
+) {
+    let mut entries: Vec<_> = entries.collect();
+    entries.sort_by(|a, b| a.0.cmp(&b.0));
+    hasher.write_usize(entries.len());
+    entries.into_iter().for_each(|(k, v)| {
+        k.hash(hasher);
+        v.hash_state(hasher);
+    });
+}
+
+/// hash of the game state, serialized as a hex string
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, Hash)]
+pub struct StateHash(pub u64);
+
+impl fmt::Display for StateHash {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:016x}", self.0)
+    }
+}
+
+impl Serialize for StateHash {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.to_string())
+    }
+}
+
+impl<'de> Deserialize<'de> for StateHash {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        u64::from_str_radix(&s, 16)
+            .map(StateHash)
+            .map_err(|e| D::Error::custom(format!("invalid state hash {}: {}", s, e)))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use serde_json;
+    #[test]
+    fn fnv() {
+        let mut hasher = StateHasher::default();
+        hasher.write(b"a");
+        assert_eq!(hasher.finish(), 0xaf63_dc4c_8601_ec8c);
+        let mut hasher = StateHasher::default();
+        (1u32, 2usize, "rogue").hash(&mut hasher);
+        let hash = StateHash(hasher.finish());
+        let json = serde_json::to_string(&hash).unwrap();
+        assert_eq!(json.len(), 18);
+        assert_eq!(serde_json::from_str::<StateHash>(&json).unwrap(), hash);
+    }
+}
@@ -1,7 +1,9 @@
 //! a module for handling user input
-use character::Action;
-use dungeon::Direction;
+use character::{Action, ItemAction};
+use dungeon::{Direction, TravelTarget};
+use error::*;
 use regex::Regex;
+use serde::de::Error as DeError;
 use serde::de::{Deserialize, Deserializer, MapAccess, Visitor};
 use serde::ser::{Serialize, SerializeMap, Serializer};
 use std::collections::HashMap;
@@ -9,22 +11,93 @@ use std::fmt;
 use std::marker::PhantomData;
 use std::str;
 
-/// Mapping from Keyboard input to InputCode
+/// Mapping from Keyboard input(a key or a sequence of keys) to InputCode
 #[derive(Clone, Debug, Eq, PartialEq)]
 pub struct KeyMap {
-    inner: HashMap<Key, InputCode>,
+    inner: HashMap<Vec<Key>, InputCode>,
+}
+
+/// result of looking up a sequence of keys
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum KeyMatch {
+    Input(InputCode),
+    /// the keys are the beginning of some sequences
+    Prefix,
+    None,
 }
 
 impl KeyMap {
+    fn from_keys(map: Vec<(Key, InputCode)>) -> Self {
+        let inner = map.into_iter().map(|(k, i)| (vec![k], i)).collect();
+        KeyMap { inner }
+    }
     pub fn get(&self, key: Key) -> Option<InputCode> {
-        self.inner.get(&key).map(|code| code.to_owned())
+        self.inner.get(&[key][..]).map(|code| code.to_owned())
+    }
+    /// look up a sequence of keys
+    pub fn lookup(&self, keys: &[Key]) -> KeyMatch {
+        if let Some(code) = self.inner.get(keys) {
+            return KeyMatch::Input(*code);
+        }
+        if self
+            .inner
+            .keys()
+            .any(|seq| seq.len() > keys.len() && seq.starts_with(keys))
+        {
+            KeyMatch::Prefix
+        } else {
+            KeyMatch::None
+        }
+    }
+    /// add a binding, which mustn't conflict with others
+    pub fn insert(&mut self, keys: Vec<Key>, input: InputCode) -> GameResult<()> {
+        if let Some(conflict) = self.conflict(&keys) {
+            return Err(ErrorId::InvalidSetting.into_with(|| {
+                format!(
+                    "KeyMap::insert {} conflicts with {}",
+                    keys_to_str(&keys),
+                    keys_to_str(conflict)
+                )
+            }));
+        }
+        self.inner.insert(keys, input);
+        Ok(())
+    }
+    /// a registered sequence which is same as `keys` or a prefix of it, or vice versa
+    fn conflict(&self, keys: &[Key]) -> Option<&[Key]> {
+        self.inner
+            .keys()
+            .find(|seq| seq.starts_with(keys) || keys.starts_with(seq))
+            .map(|seq| &seq[..])
+    }
+}
+
+fn keys_to_str(keys: &[Key]) -> String {
+    if keys.len() == 1 {
+        return keys[0].to_str();
     }
+    let names: Vec<_> = keys
+        .iter()
+        .map(|k| match k {
+            Key::Char(' ') => "Space".to_owned(),
+            k => k.to_str(),
+        })
+        .collect();
+    names.join(" ")
+}
+
+fn keys_from_str(s: &str) -> Option<Vec<Key>> {
+    if let Some(key) = Key::from_str(s) {
+        return Some(vec![key]);
+    }
+    let keys: Option<Vec<_>> = s.split_whitespace().map(Key::from_str).collect();
+    keys.filter(|k| !k.is_empty())
 }
 
 impl Default for KeyMap {
     fn default() -> Self {
         use self::Direction::*;
-        let map = vec![
+        let mut map = vec![
             (Key::Char('l'), InputCode::Act(Action::Move(Right))),
             (Key::Char('k'), InputCode::Act(Action::Move(Up))),
             (Key::Char('j'), InputCode::Act(Action::Move(Down))),
@@ -56,6 +129,10 @@ impl Default for KeyMap {
             (Key::Char('s'), InputCode::Act(Action::Search)),
             (Key::Char('.'), InputCode::Act(Action::NoOp)),
             (Key::Char('>'), InputCode::Act(Action::DownStair)),
+            (
+                Key::Char('_'),
+                InputCode::Act(Action::Travel(TravelTarget::DownStair)),
+            ),
             (Key::Up, InputCode::Act(Action::Move(Up))),
             (Key::Down, InputCode::Act(Action::Move(Down))),
             (Key::Left, InputCode::Act(Action::Move(Left))),
@@ -65,9 +142,38 @@ impl Default for KeyMap {
             (Key::Char('Q'), InputCode::Sys(System::Quit)),
             (Key::Char('i'), InputCode::Sys(System::Inventory)),
             (Key::Char(' '), InputCode::Sys(System::Continue)),
+            (Key::Ctrl('r'), InputCode::Sys(System::Redraw)),
+            (Key::Ctrl('p'), InputCode::Sys(System::Messages)),
+            (
+                Key::Char('w'),
+                InputCode::Act(Action::Item(ItemAction::Wield)),
+            ),
+            (
+                Key::Char('W'),
+                InputCode::Act(Action::Item(ItemAction::Wear)),
+            ),
         ];
-        let inner: HashMap<_, _> = map.into_iter().collect();
-        KeyMap { inner }
+        // o and a direction to open a door, Alt + direction to kick it.
+        // Ctrl + direction isn't used since terminals send Ctrl+h and Ctrl+j as other keys.
+        let directions = [
+            ('h', Left),
+            ('j', Down),
+            ('k', Up),
+            ('l', Right),
+            ('y', LeftUp),
+            ('u', RightUp),
+            ('b', LeftDown),
+            ('n', RightDown),
+        ];
+        for &(c, d) in &directions {
+            map.push((Key::Alt(c), InputCode::Act(Action::Kick(d))));
+        }
+        let mut keymap = KeyMap::from_keys(map);
+        for &(c, d) in &directions {
+            let keys = vec![Key::Char('o'), Key::Char(c)];
+            keymap.inner.insert(keys, InputCode::Act(Action::Open(d)));
+        }
+        keymap
     }
 }
 
@@ -94,9 +200,17 @@ impl KeyMap {
             (Key::Char('B'), InputCode::Act(Action::MoveUntil(LeftDown))),
             (Key::Char('s'), InputCode::Act(Action::Search)),
             (Key::Char('>'), InputCode::Act(Action::DownStair)),
+            (Key::Char('x'), InputCode::Act(Action::Explore)),
+            (
+                Key::Char('w'),
+                InputCode::Act(Action::Item(ItemAction::Wield)),
+            ),
+            (
+                Key::Char('W'),
+                InputCode::Act(Action::Item(ItemAction::Wear)),
+            ),
         ];
-        let inner: HashMap<_, _> = map.into_iter().collect();
-        KeyMap { inner }
+        KeyMap::from_keys(map)
     }
 }
 
@@ -108,7 +222,7 @@ impl Serialize for KeyMap {
         let mut map = serializer.serialize_map(Some(self.inner.len()))?;
         self.inner
             .iter()
-            .try_for_each(|(k, v)| map.serialize_entry(&k.to_str(), v))?;
+            .try_for_each(|(k, v)| map.serialize_entry(&keys_to_str(k), v))?;
         map.end()
     }
 }
@@ -134,15 +248,22 @@ impl<'de> Visitor<'de> for KeyMapVisitor {
     where
         M: MapAccess<'de>,
     {
-        let mut inner = HashMap::new();
-        while let Some((k, v)) = access.next_entry()? {
-            let key = match Key::from_str(k) {
-                Some(k) => k,
-                None => panic!("invalid key name {} in KeyMap", k),
-            };
-            inner.insert(key, v);
+        let mut keymap = KeyMap {
+            inner: HashMap::new(),
+        };
+        while let Some((k, v)) = access.next_entry::<&str, _>()? {
+            let keys = keys_from_str(k)
+                .ok_or_else(|| M::Error::custom(format!("invalid key name {} in KeyMap", k)))?;
+            if let Some(conflict) = keymap.conflict(&keys) {
+                return Err(M::Error::custom(format!(
+                    "{} conflicts with {} in KeyMap",
+                    k,
+                    keys_to_str(conflict)
+                )));
+            }
+            keymap.inner.insert(keys, v);
         }
-        Ok(KeyMap { inner })
+        Ok(keymap)
     }
 }
 
@@ -159,8 +280,16 @@ impl<'de> Deserialize<'de> for KeyMap {
 #[derive(Copy, Clone, Debug, Serialize, Deserialize, Hash, Eq, PartialEq)]
 pub enum InputCode {
     Act(Action),
-    Both { act: Action, sys: System },
+    Both {
+        act: Action,
+        sys: System,
+    },
     Sys(System),
+    /// do the action `count` times, until something interesting happens
+    Repeat {
+        count: u32,
+        act: Action,
+    },
 }
 
 /// System input
@@ -170,8 +299,13 @@ pub enum System {
     Continue,
     Enter,
     Inventory,
+    /// show previous messages
+    Messages,
     No,
+    Redraw,
     Save,
+    /// an item letter typed in a prompt
+    Select(char),
     Quit,
     Yes,
 }
@@ -224,9 +358,9 @@ impl Key {
     }
     fn from_str(s: &str) -> Option<Self> {
         use self::Key::*;
-        let f = Regex::new(r"F([0-9]+)").unwrap();
-        let alt = Regex::new(r"Alt\s*\+\s*(.+)").unwrap();
-        let ctrl = Regex::new(r"Ctrl\s*\+\s*(.+)").unwrap();
+        let f = Regex::new(r"^F([0-9]+)$").unwrap();
+        let alt = Regex::new(r"^Alt\s*\+\s*(\S)$").unwrap();
+        let ctrl = Regex::new(r"^Ctrl\s*\+\s*(\S)$").unwrap();
         match s {
             "Backspace" => Some(Backspace),
             "Left" => Some(Left),
@@ -241,7 +375,8 @@ impl Key {
             "Insert" => Some(Insert),
             "Null" => Some(Null),
             "Esc" => Some(Esc),
-            s if s.len() == 1 => Some(Char(s.chars().nth(0)?)),
+            "Space" => Some(Char(' ')),
+            s if s.chars().count() == 1 => Some(Char(s.chars().nth(0)?)),
             _ => {
                 if let Some(cap_f) = f.captures(s) {
                     let num = str::parse::<u8>(&cap_f[1]).ok()?;
@@ -277,6 +412,54 @@ mod keymap_test {
         assert_eq!(ctrl_a, Key::Ctrl('a'));
         let j = Key::from_str("j").unwrap();
         assert_eq!(j, Key::Char('j'));
+        assert_eq!(Key::from_str("Ctrl+ab"), None);
+        assert_eq!(Key::from_str("Space"), Some(Key::Char(' ')));
+    }
+    #[test]
+    fn sequence() {
+        let keymap: KeyMap =
+            json::from_str(r#"{"g g": {"Act": "Search"}, "g Space": {"Act": "NoOp"}, "Ctrl+x h": {"Act": {"Open": "Left"}}}"#)
+                .unwrap();
+        let g = Key::Char('g');
+        assert_eq!(keymap.lookup(&[g]), KeyMatch::Prefix);
+        assert_eq!(
+            keymap.lookup(&[g, g]),
+            KeyMatch::Input(InputCode::Act(Action::Search))
+        );
+        assert_eq!(
+            keymap.lookup(&[g, Key::Char(' ')]),
+            KeyMatch::Input(InputCode::Act(Action::NoOp))
+        );
+        assert_eq!(
+            keymap.lookup(&[Key::Ctrl('x'), Key::Char('h')]),
+            KeyMatch::Input(InputCode::Act(Action::Open(Direction::Left)))
+        );
+        assert_eq!(keymap.lookup(&[g, Key::Char('h')]), KeyMatch::None);
+        assert_eq!(keymap.get(g), None);
+        let ser = json::to_string(&keymap).unwrap();
+        let de: KeyMap = json::from_str(&ser).unwrap();
+        assert_eq!(de, keymap);
+    }
+    #[test]
+    fn conflict() {
+        let prefix =
+            json::from_str::<KeyMap>(r#"{"g": {"Act": "Search"}, "g g": {"Act": "NoOp"}}"#);
+        assert!(prefix.is_err());
+        let dup = json::from_str::<KeyMap>(
+            r#"{"Ctrl+a": {"Act": "Search"}, "Ctrl + a": {"Act": "NoOp"}}"#,
+        );
+        assert!(dup.is_err());
+        assert!(json::from_str::<KeyMap>(r#"{"Foo": {"Act": "Search"}}"#).is_err());
+        let mut keymap = KeyMap::ai();
+        let s = Key::Char('s');
+        assert!(keymap
+            .insert(vec![s, s], InputCode::Act(Action::NoOp))
+            .is_err());
+        let z = Key::Char('z');
+        keymap
+            .insert(vec![z, s], InputCode::Act(Action::NoOp))
+            .unwrap();
+        assert_eq!(keymap.lookup(&[z]), KeyMatch::Prefix);
     }
     #[test]
     fn serde() {
@@ -286,10 +469,29 @@ mod keymap_test {
         assert_eq!(de, keymap);
     }
     fn print_keymap(keymap: KeyMap, filename: &str) {
-        let json = json::to_string(&keymap).unwrap();
+        // via Value to sort keys
+        let value = json::to_value(&keymap).unwrap();
+        let mut buf = vec![];
+        let formatter = json::ser::PrettyFormatter::with_indent(b"    ");
+        let mut ser = json::Serializer::with_formatter(&mut buf, formatter);
+        value.serialize(&mut ser).unwrap();
         let path = Path::new("../data/keymaps").join(filename);
         let mut file = File::create(path).unwrap();
-        file.write_all(json.as_bytes()).unwrap();
+        file.write_all(&buf).unwrap();
+    }
+    #[test]
+    fn default_open_kick() {
+        let keymap = KeyMap::default();
+        let o = Key::Char('o');
+        assert_eq!(keymap.lookup(&[o]), KeyMatch::Prefix);
+        assert_eq!(
+            keymap.lookup(&[o, Key::Char('j')]),
+            KeyMatch::Input(InputCode::Act(Action::Open(Direction::Down)))
+        );
+        assert_eq!(
+            keymap.lookup(&[Key::Alt('h')]),
+            KeyMatch::Input(InputCode::Act(Action::Kick(Direction::Left)))
+        );
     }
     #[test]
     #[ignore]
@@ -303,6 +505,58 @@ mod keymap_test {
     }
 }
 
+#[cfg(test)]
+mod runtime_test {
+    use super::*;
+    use dungeon::custom::{test_config, TEST_MAP};
+    use dungeon::Coord;
+    use RunTime;
+    #[test]
+    fn count_prefix() {
+        let mut runtime: RunTime = test_config(&[TEST_MAP]).build().unwrap();
+        let pos = |runtime: &RunTime| runtime.dungeon.path_to_cd(&runtime.player.pos);
+        for c in "2l".chars() {
+            runtime.react_to_key(Key::Char(c)).unwrap();
+        }
+        assert_eq!(pos(&runtime), Coord::new(3, 2));
+        assert_eq!(
+            runtime.saved_inputs().last(),
+            Some(&InputCode::Repeat {
+                count: 2,
+                act: Action::Move(Direction::Right),
+            })
+        );
+        // picking up gold interrupts the repetition
+        for c in "10l".chars() {
+            runtime.react_to_key(Key::Char(c)).unwrap();
+        }
+        assert_eq!(pos(&runtime), Coord::new(4, 2));
+        assert_eq!(runtime.count_prefix(), None);
+    }
+    #[test]
+    fn key_sequence() {
+        let mut runtime: RunTime = test_config(&[TEST_MAP]).build().unwrap();
+        let pos = |runtime: &RunTime| runtime.dungeon.path_to_cd(&runtime.player.pos);
+        let (g, l) = (Key::Char('g'), Key::Char('l'));
+        runtime
+            .keymap
+            .insert(vec![g, l], InputCode::Act(Action::Move(Direction::Right)))
+            .unwrap();
+        assert_eq!(runtime.react_to_key(g).unwrap(), vec![]);
+        assert!(runtime.is_waiting_input());
+        assert_eq!(pos(&runtime), Coord::new(1, 2));
+        runtime.react_to_key(l).unwrap();
+        assert!(!runtime.is_waiting_input());
+        assert_eq!(pos(&runtime), Coord::new(2, 2));
+        // a broken sequence is discarded
+        runtime.react_to_key(g).unwrap();
+        assert!(runtime.react_to_key(Key::Char('h')).is_err());
+        assert!(runtime.pending_keys().is_empty());
+        runtime.react_to_key(l).unwrap();
+        assert_eq!(pos(&runtime), Coord::new(3, 2));
+    }
+}
+
 #[cfg(unix)]
 use termion::event::Key as TermionKey;
 
@@ -1,9 +1,11 @@
 use super::handler::{Handler, ItemInner, ItemStat};
 use super::{InitItem, Item, ItemAttr, ItemKind, ItemNum};
 use character::Defense;
+use hash::{HashState, StateHasher};
 use rng::{Parcent, RngHandle};
 use smallstr::SmallStr;
 use std::fmt;
+use std::hash::Hasher;
 
 /// Armor configuration
 #[derive(Clone, Debug, Serialize, Deserialize, Eq, PartialEq)]
@@ -94,6 +96,13 @@ pub struct Armor {
     pub(super) def_plus: Defense,
 }
 
+impl HashState for Armor {
+    fn hash_state(&self, hasher: &mut StateHasher) {
+        hasher.write_i32(self.def.0);
+        hasher.write_i32(self.def_plus.0);
+    }
+}
+
 impl Armor {
     pub fn name(&self) -> &str {
         self.name.as_ref()
@@ -19,8 +19,11 @@ impl Config {
         if !rng.does_happen(self.rate_inv) {
             return None;
         }
+        Some(self.gen_num(rng, level))
+    }
+    pub(super) fn gen_num(&self, rng: &mut RngHandle, level: u32) -> ItemNum {
         let num = rng.range(0..self.base + self.per_level * level) + self.minimum;
-        Some(ItemNum(num))
+        ItemNum(num)
     }
 }
 
@@ -1,7 +1,9 @@
 //! utility for managing character's items
 use super::{Item, ItemToken};
 use fenwick::FenwickSet;
+use hash::{HashState, StateHasher};
 use std::collections::BTreeMap;
+use std::hash::Hasher;
 
 #[derive(Clone, Debug)]
 pub struct ItemBox {
@@ -9,6 +11,16 @@ pub struct ItemBox {
     items: BTreeMap<usize, ItemToken>,
 }
 
+impl HashState for ItemBox {
+    fn hash_state(&self, hasher: &mut StateHasher) {
+        hasher.write_usize(self.items.len());
+        self.items.iter().for_each(|(&c, token)| {
+            hasher.write_usize(c);
+            token.hash_state(hasher);
+        });
+    }
+}
+
 impl ItemBox {
     pub fn with_capacity(max_len: usize) -> Self {
         ItemBox {
@@ -42,6 +54,17 @@ impl ItemBox {
     pub fn items(&self) -> impl Iterator<Item = &Item> {
         self.tokens().map(|t| t.get())
     }
+    /// items with the letters to select them
+    pub fn entries(&self) -> impl Iterator<Item = (char, &ItemToken)> {
+        self.items.iter().map(|(&i, t)| (index_to_char(i), t))
+    }
+    /// get the item by its letter
+    pub fn get_by_char(&self, c: char) -> Option<&ItemToken> {
+        if !c.is_ascii_lowercase() {
+            return None;
+        }
+        self.items.get(&((c as u8 - b'a') as usize))
+    }
     pub fn find_by(&self, mut query: impl FnMut(&Item) -> bool) -> Option<&ItemToken> {
         self.items
             .iter()
@@ -61,6 +84,10 @@ impl ItemBox {
     }
 }
 
+fn index_to_char(i: usize) -> char {
+    (b'a' + i as u8) as char
+}
+
 #[derive(Debug)]
 pub enum Entry<'a> {
     Merge(MergeEntry<'a>),
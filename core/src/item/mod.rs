@@ -14,11 +14,13 @@ pub use self::itembox::ItemBox;
 use self::weapon::{Weapon, WeaponStatus};
 use character::{Dice, HitPoint, Level};
 use error::*;
+use hash::{HashState, StateHasher};
 use rng::RngHandle;
 use smallstr::SmallStr;
 use std::cell::UnsafeCell;
 use std::collections::BTreeMap;
 use std::fmt;
+use std::hash::{Hash, Hasher};
 use std::ops::{Deref, DerefMut};
 use std::rc::{Rc, Weak};
 use tile::{Drawable, Tile};
@@ -144,12 +146,15 @@ impl ItemAttr {
     pub fn equip(&mut self) {
         self.0 |= ItemAttr::IS_EQUIPPED.0;
     }
-    fn is_equiped(&self) -> bool {
+    pub fn unequip(&mut self) {
+        self.0 &= !ItemAttr::IS_EQUIPPED.0;
+    }
+    pub fn is_equiped(&self) -> bool {
         (self.0 & ItemAttr::IS_EQUIPPED.0) != 0
     }
 }
 
-#[derive(Clone, Copy, Debug, Serialize, Deserialize, Eq, PartialEq, Ord, PartialOrd)]
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, Eq, PartialEq, Hash, Ord, PartialOrd)]
 pub struct ItemId(u32);
 
 impl ItemId {
@@ -224,6 +229,32 @@ pub struct Item {
     pub attr: ItemAttr,
 }
 
+impl HashState for Item {
+    fn hash_state(&self, hasher: &mut StateHasher) {
+        match self.kind {
+            ItemKind::Armor(ref armor) => {
+                hasher.write_u8(0);
+                armor.hash_state(hasher);
+            }
+            ItemKind::Food(ref food) => {
+                hasher.write_u8(1);
+                food.hash(hasher);
+            }
+            ItemKind::Gold => hasher.write_u8(2),
+            ItemKind::Potion => hasher.write_u8(3),
+            ItemKind::Ring => hasher.write_u8(4),
+            ItemKind::Scroll => hasher.write_u8(5),
+            ItemKind::Wand => hasher.write_u8(6),
+            ItemKind::Weapon(ref weapon) => {
+                hasher.write_u8(7);
+                weapon.hash_state(hasher);
+            }
+        }
+        hasher.write_u32(self.how_many.0);
+        hasher.write_u8(self.attr.0);
+    }
+}
+
 impl Item {
     pub fn new<N: Into<ItemNum>>(kind: ItemKind, num: N) -> Self {
         Item {
@@ -291,14 +322,9 @@ impl Drawable for Item {
     }
 }
 
-impl fmt::Display for Item {
+impl fmt::Display for ItemKind {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        if self.how_many == ItemNum(1) {
-            write!(f, "A ")?;
-        } else {
-            write!(f, "{} ", self.how_many.0)?;
-        }
-        match &self.kind {
+        match self {
             ItemKind::Armor(armor) => write!(f, "{}", armor),
             ItemKind::Food(food) => write!(f, "{}", food),
             ItemKind::Gold => write!(f, "golds"),
@@ -307,7 +333,18 @@ impl fmt::Display for Item {
             ItemKind::Scroll => write!(f, "scroll"), // STUB
             ItemKind::Wand => write!(f, "wand"),     // STUB
             ItemKind::Weapon(w) => write!(f, "{}", w),
-        }?;
+        }
+    }
+}
+
+impl fmt::Display for Item {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.how_many == ItemNum(1) {
+            write!(f, "A ")?;
+        } else {
+            write!(f, "{} ", self.how_many.0)?;
+        }
+        write!(f, "{}", self.kind)?;
         if self.attr.is_equiped() {
             write!(f, " [equipped]")?;
         }
@@ -352,6 +389,13 @@ impl ItemToken {
     }
 }
 
+impl HashState for ItemToken {
+    fn hash_state(&self, hasher: &mut StateHasher) {
+        hasher.write_u32(self.id.0);
+        self.get().hash_state(hasher);
+    }
+}
+
 /// generate and management all items
 pub struct ItemHandler {
     /// stores all items in the game
@@ -364,6 +408,14 @@ pub struct ItemHandler {
     next_id: ItemId,
 }
 
+impl HashState for ItemHandler {
+    fn hash_state(&self, hasher: &mut StateHasher) {
+        // items themselves are hashed where they are, on floors or in the player's pack
+        hasher.write_u32(self.next_id.0);
+        self.rng.hash_state(hasher);
+    }
+}
+
 impl ItemHandler {
     /// generate new ItemHandler
     pub fn new(config_: Config, seed: u128) -> Self {
@@ -397,6 +449,14 @@ impl ItemHandler {
         let num = self.config.gold.gen(&mut self.rng, level)?;
         Some(self.gen_item(ItemKind::Gold.numbered(num).many()))
     }
+    /// Generates gold without randomness of appearance
+    pub fn gen_gold(&mut self, level: u32) -> ItemToken {
+        let num = self.config.gold.gen_num(&mut self.rng, level);
+        self.gen_item(ItemKind::Gold.numbered(num).many())
+    }
+    pub(crate) fn rng(&mut self) -> &mut RngHandle {
+        &mut self.rng
+    }
     /// Sets up player items
     pub fn init_player_items(&mut self, pack: &mut ItemBox, items: &[InitItem]) -> GameResult<()> {
         items.iter().try_for_each(|item| {
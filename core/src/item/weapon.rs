@@ -1,9 +1,11 @@
 use super::handler::{Handler, ItemInner, ItemStat};
 use super::{InitItem, Item, ItemAttr, ItemKind, ItemNum};
 use crate::character::{Dice, HitPoint, Level};
+use crate::hash::{HashState, StateHasher};
 use crate::rng::{Parcent, RngHandle};
 use crate::SmallStr;
 use std::fmt;
+use std::hash::Hasher;
 use std::ops::Range;
 use tuple_map::TupleMap3;
 
@@ -92,6 +94,13 @@ pub struct Weapon {
     launcher: Option<SmallStr>,
 }
 
+impl HashState for Weapon {
+    fn hash_state(&self, hasher: &mut StateHasher) {
+        hasher.write_i64(self.hit_plus.0);
+        hasher.write_i64(self.dam_plus.0);
+    }
+}
+
 impl Weapon {
     pub fn name(&self) -> &str {
         self.name.as_str()
@@ -27,26 +27,39 @@ extern crate test;
 extern crate tuple_map;
 
 mod actions;
+use std::cmp;
 use std::fs::File;
+use std::hash::Hasher;
 use std::io::{self, Read};
+use std::mem;
+use std::path::Path;
 pub mod character;
 pub mod dungeon;
 pub mod error;
+pub mod event_log;
 mod fenwick;
+pub mod hash;
 pub mod input;
 pub mod item;
+pub mod message;
+pub mod observer;
+pub mod protocol;
+pub mod replay;
 mod rng;
 mod smallstr;
 pub mod symbol;
 pub mod tile;
 pub mod ui;
 
-use character::{enemies, player, EnemyHandler, Player};
+use character::{enemies, fight::Hit, player, Action, EnemyHandler, Exp, ItemAction, Player};
 use dungeon::{Direction, Dungeon, DungeonStyle, Positioned, X, Y};
 use error::*;
-use input::{InputCode, Key, KeyMap};
+use hash::{HashState, StateHash, StateHasher};
+use input::{InputCode, Key, KeyMap, KeyMatch};
 use item::{ItemHandler, ItemKind};
+use message::{Language, Locale, MessageLog};
 use ndarray::Array2;
+use observer::{GameObserver, ObserverId};
 pub use smallstr::SmallStr;
 use tile::{Drawable, Tile};
 use ui::{MordalKind, MordalMsg, UiState};
@@ -97,6 +110,21 @@ pub struct GameConfig {
     /// this setting is only for debugging and don't use it when you play game
     #[serde(default = "default_hide_dungeon")]
     pub hide_dungeon: bool,
+    /// the level where the game starts.
+    /// When it's deeper than 1, the game starts on the floor `build_floor` makes with the same seed.
+    #[serde(default = "default_start_level")]
+    #[serde(skip_serializing_if = "is_default_start_level")]
+    pub start_level: u32,
+    /// generate each level from its own seed derived from `seed`,
+    /// so that `build_floor` can reproduce any level without playing the previous ones.
+    /// Levels after the first differ from the ones generated without this option.
+    #[serde(default)]
+    #[serde(skip_serializing_if = "is_default")]
+    pub seed_per_level: bool,
+    /// language of messages
+    #[serde(default)]
+    #[serde(skip_serializing_if = "is_default")]
+    pub language: Language,
 }
 
 unsafe impl Send for GameConfig {}
@@ -128,6 +156,14 @@ const fn default_hide_dungeon() -> bool {
     true
 }
 
+const fn default_start_level() -> u32 {
+    1
+}
+
+fn is_default_start_level(l: &u32) -> bool {
+    cfg!(not(test)) && *l == default_start_level()
+}
+
 impl Default for GameConfig {
     fn default() -> Self {
         GameConfig {
@@ -141,6 +177,9 @@ impl Default for GameConfig {
             player: player::Config::default(),
             enemies: enemies::Config::default(),
             hide_dungeon: default_hide_dungeon(),
+            start_level: default_start_level(),
+            seed_per_level: false,
+            language: Language::default(),
         }
     }
 }
@@ -153,11 +192,25 @@ pub const MAX_HEIGHT: i32 = DEFAULT_HEIGHT * 2;
 pub const MIN_WIDTH: i32 = 32;
 pub const MIN_HEIGHT: i32 = 16;
 
+/// max count typed before a command
+pub const MAX_COUNT_PREFIX: u32 = 9999;
+
 impl GameConfig {
     /// construct Game configuration from json string
     pub fn from_json(json: &str) -> GameResult<Self> {
         serde_json::from_str(json).into_chained(|| "GameConfig::from_json")
     }
+    /// construct Game configuration from json file.
+    /// Relative paths of map files in it are resolved against the directory of the file.
+    pub fn from_file(name: &str) -> GameResult<Self> {
+        let json = read_file(name)
+            .into_chained(|| format!("GameConfig::from_file: failed to read {}", name))?;
+        let mut config = Self::from_json(&json)?;
+        if let Some(dir) = Path::new(name).parent() {
+            config.dungeon.resolve_paths(dir);
+        }
+        Ok(config)
+    }
     pub fn to_json(&self) -> GameResult<String> {
         serde_json::to_string_pretty(self).into_chained(|| "GameConfig::to_json")
     }
@@ -190,16 +243,59 @@ impl GameConfig {
         if h > MAX_HEIGHT {
             return Err(ErrorId::InvalidSetting.into_with(|| "screen height is too wide"));
         }
+        if self.start_level == 0 {
+            return Err(ErrorId::InvalidSetting.into_with(|| "start level must be larger than 0"));
+        }
         Ok(GlobalConfig {
             width: w.into(),
             height: h.into(),
             seed,
             hide_dungeon: self.hide_dungeon,
+            start_level: self.start_level,
+            seed_per_level: self.seed_per_level,
         })
     }
+    /// fail if this config would play a different game from `recorded`,
+    /// which is saved with the resolved seed in a replay or an event log.
+    /// If this config has no seed, the recorded one is used.
+    pub fn check_same_game(&self, recorded: &GameConfig) -> GameResult<()> {
+        let mut config = self.clone();
+        if config.seed.is_none() {
+            config.seed = recorded.seed;
+            config.seed_range = None;
+        }
+        if config != *recorded {
+            return Err(ErrorId::ReplayMismatch
+                .into_with(|| "the given config or seed differs from the recorded one"));
+        }
+        Ok(())
+    }
+    /// the config to play the same game as `runtime` again, with its seed resolved
+    pub(crate) fn resolved(runtime: &RunTime) -> GameConfig {
+        let mut config = runtime.game_config().clone();
+        config.seed = Some(runtime.seed());
+        config.seed_range = None;
+        config
+    }
+    /// build only the floor of the specified level, without starting a game.
+    /// Given the same seed, it's same as the floor the player reaches in the game
+    /// with `seed_per_level` enabled.
+    pub fn build_floor(&self, seed: u128, level: u32) -> GameResult<dungeon::rogue::Floor> {
+        const ERR_STR: &str = "GameConfig::build_floor";
+        let game_info = GameInfo::new();
+        let mut config = self.to_global().chain_err(|| ERR_STR)?;
+        config.seed = seed;
+        let mut item = ItemHandler::new(self.item.clone(), seed);
+        let mut enemies = self.enemies.clone().build(seed);
+        self.dungeon
+            .clone()
+            .build_floor(&config, &mut item, &mut enemies, &game_info, level)
+            .chain_err(|| ERR_STR)
+    }
     /// get runtime from config
     pub fn build(self) -> GameResult<RunTime> {
         const ERR_STR: &str = "GameConfig::build";
+        let game_config = self.clone();
         let game_info = GameInfo::new();
         let config = self.to_global().chain_err(|| ERR_STR)?;
         debug!("Building dungeon with seed {}", config.seed);
@@ -223,6 +319,7 @@ impl GameConfig {
         )
         .chain_err(|| ERR_STR)?;
         Ok(RunTime {
+            game_config,
             game_info,
             config,
             dungeon,
@@ -231,6 +328,14 @@ impl GameConfig {
             enemies,
             ui: UiState::Dungeon,
             saved_inputs: vec![],
+            state_hashes: vec![],
+            records_hashes: false,
+            count_prefix: None,
+            pending_keys: vec![],
+            messages: MessageLog::default(),
+            locale: self.language.locale(),
+            observers: vec![],
+            next_observer_id: 0,
             keymap: self.keymap,
         })
     }
@@ -238,6 +343,7 @@ impl GameConfig {
 
 /// API entry point of rogue core
 pub struct RunTime {
+    game_config: GameConfig,
     game_info: GameInfo,
     config: GlobalConfig,
     dungeon: Box<dyn Dungeon>,
@@ -245,6 +351,14 @@ pub struct RunTime {
     player: Player,
     ui: UiState,
     saved_inputs: Vec<InputCode>,
+    state_hashes: Vec<StateHash>,
+    records_hashes: bool,
+    count_prefix: Option<u32>,
+    pending_keys: Vec<Key>,
+    messages: MessageLog,
+    locale: Box<dyn Locale>,
+    observers: Vec<(ObserverId, Box<dyn GameObserver>)>,
+    next_observer_id: usize,
     enemies: EnemyHandler,
     pub keymap: KeyMap,
 }
@@ -263,6 +377,12 @@ impl RunTime {
                 self.ui = ui.clone();
                 Ok(vec![Reaction::UiTransition(ui)])
             }
+            System::Messages => {
+                let ui = UiState::Mordal(MordalKind::Messages);
+                self.ui = ui.clone();
+                Ok(vec![Reaction::UiTransition(ui)])
+            }
+            System::Redraw => Ok(vec![Reaction::Redraw, Reaction::StatusUpdated]),
             System::Save => Err(ErrorId::Unimplemented.into_with(|| {
                 "[rogue_gym_core::RunTime::check_interuppting] save command is unimplemented"
             })),
@@ -293,9 +413,38 @@ impl RunTime {
             Ok(())
         })
     }
+    /// the screen drawn by `draw_screen`, as a string per row
+    pub fn screen_rows(&self) -> GameResult<Vec<String>> {
+        let (w, h) = (self.config.width.0 as usize, self.config.height.0 as usize);
+        let mut screen = vec![vec![b' '; w]; h];
+        self.draw_screen(|Positioned(cd, tile)| {
+            let (x, y) = (cd.x.0 as usize, cd.y.0 as usize);
+            if x < w && y < h {
+                screen[y][x] = tile.to_byte();
+            }
+            Ok(())
+        })?;
+        let rows = screen
+            .into_iter()
+            .map(|row| String::from_utf8_lossy(&row).into_owned())
+            .collect();
+        Ok(rows)
+    }
     pub fn react_to_input(&mut self, input: InputCode) -> GameResult<Vec<Reaction>> {
         trace!("[react_to_input] input: {:?} ui: {:?}", input, self.ui);
         self.saved_inputs.push(input);
+        let res = self.process_input(input);
+        if self.records_hashes {
+            let hash = self.state_hash();
+            self.state_hashes.push(hash);
+        }
+        res
+    }
+    fn process_input(&mut self, input: InputCode) -> GameResult<Vec<Reaction>> {
+        let before = observer::Snapshot {
+            turn: self.player.turns(),
+            level: self.dungeon.level(),
+        };
         let (next_ui, res) =
             match self.ui {
                 UiState::Dungeon => match input {
@@ -308,6 +457,7 @@ impl RunTime {
                         &mut self.player,
                         &mut self.enemies,
                     )?,
+                    InputCode::Repeat { count, act } => self.repeat_action(count, act)?,
                 },
                 UiState::Mordal(ref mut kind) => match input {
                     InputCode::Sys(sys) | InputCode::Both { sys, .. } => {
@@ -320,23 +470,123 @@ impl RunTime {
                             MordalMsg::Save => bail!(ErrorId::Unimplemented
                                 .into_with(|| "Save command is unimplemented")),
                             MordalMsg::Quit => (None, vec![Reaction::Notify(GameMsg::Quit)]),
+                            MordalMsg::Act(act) => {
+                                let (ui, mut res) = actions::process_action(
+                                    act,
+                                    &mut self.game_info,
+                                    &mut *self.dungeon,
+                                    &mut self.item,
+                                    &mut self.player,
+                                    &mut self.enemies,
+                                )?;
+                                res.insert(0, Reaction::UiTransition(UiState::Dungeon));
+                                (Some(ui.unwrap_or(UiState::Dungeon)), res)
+                            }
                             MordalMsg::None => (None, vec![]),
                         }
                     }
-                    InputCode::Act(_) => bail!(ErrorId::IgnoredInput(input)),
+                    InputCode::Act(_) | InputCode::Repeat { .. } => {
+                        bail!(ErrorId::IgnoredInput(input))
+                    }
                 },
             };
         if let Some(next_ui) = next_ui {
             self.ui = next_ui;
         }
+        let turn = self.player.turns();
+        for r in &res {
+            if let Reaction::Notify(msg) = r {
+                if let Some(text) = self.locale.text(msg) {
+                    self.messages.push(turn, text);
+                }
+            }
+        }
+        if !self.observers.is_empty() {
+            let mut observers = mem::take(&mut self.observers);
+            observer::dispatch(&mut observers, self, input, before, &res);
+            self.observers = observers;
+        }
         Ok(res)
     }
+    /// repeat `act` until something other than its usual result happens
+    fn repeat_action(
+        &mut self,
+        count: u32,
+        act: Action,
+    ) -> GameResult<(Option<UiState>, Vec<Reaction>)> {
+        let mut out = vec![];
+        for _ in 0..count {
+            let (ui, res) = actions::process_action(
+                act,
+                &mut self.game_info,
+                &mut *self.dungeon,
+                &mut self.item,
+                &mut self.player,
+                &mut self.enemies,
+            )?;
+            let interrupted = res.iter().any(|r| match r {
+                Reaction::Notify(msg) => !actions::is_own_message(act, msg),
+                _ => false,
+            });
+            for r in res {
+                if !out.contains(&r) || r != Reaction::Redraw {
+                    out.push(r);
+                }
+            }
+            if ui.is_some() || interrupted {
+                return Ok((ui, out));
+            }
+        }
+        Ok((None, out))
+    }
     pub fn react_to_key(&mut self, key: Key) -> GameResult<Vec<Reaction>> {
-        match self.keymap.get(key) {
+        // in item prompts, letters select items
+        if let (UiState::Mordal(MordalKind::SelectItem(_)), Key::Char(c)) = (&self.ui, key) {
+            if !self.is_cancel(key).unwrap_or(false) {
+                return self.react_to_input(InputCode::Sys(input::System::Select(c)));
+            }
+        }
+        let mut keys = mem::take(&mut self.pending_keys);
+        keys.push(key);
+        let input = match self.keymap.lookup(&keys) {
+            KeyMatch::Input(i) => Some(i),
+            KeyMatch::Prefix => {
+                self.pending_keys = keys;
+                return Ok(vec![]);
+            }
+            KeyMatch::None => None,
+        };
+        // digits not in the keymap are count prefixes, as rogue
+        if let (None, UiState::Dungeon, Key::Char(c), 1) = (input, &self.ui, key, keys.len()) {
+            if let Some(digit) = c.to_digit(10) {
+                let count = self.count_prefix.unwrap_or(0) * 10 + digit;
+                self.count_prefix = Some(cmp::min(count, MAX_COUNT_PREFIX));
+                return Ok(vec![]);
+            }
+        }
+        let count = self.count_prefix.take().unwrap_or(0);
+        match input {
+            Some(InputCode::Act(act)) | Some(InputCode::Both { act, .. })
+                if count > 1 && self.ui == UiState::Dungeon =>
+            {
+                self.react_to_input(InputCode::Repeat { count, act })
+            }
             Some(i) => self.react_to_input(i),
             None => Err(ErrorId::InvalidInput(key).into()),
         }
     }
+    /// the count typed before the next command, if any
+    pub fn count_prefix(&self) -> Option<u32> {
+        self.count_prefix
+    }
+    /// keys of an unfinished key sequence
+    pub fn pending_keys(&self) -> &[Key] {
+        &self.pending_keys
+    }
+    /// whether the runtime is waiting for more keys to complete a command
+    pub fn is_waiting_input(&self) -> bool {
+        self.count_prefix.is_some() || !self.pending_keys.is_empty()
+    }
     pub fn is_cancel(&self, key: Key) -> GameResult<bool> {
         match self.keymap.get(key) {
             Some(i) => match i {
@@ -369,6 +619,30 @@ impl RunTime {
     pub fn saved_inputs(&self) -> &[InputCode] {
         &self.saved_inputs
     }
+    /// state hash after each input in `saved_inputs`, while recording them
+    pub fn state_hashes(&self) -> &[StateHash] {
+        &self.state_hashes
+    }
+    /// record the state hash after each input, which is needed to make a `Replay`.
+    /// off by default, because hashing every step slows down the game.
+    pub fn set_record_hashes(&mut self, record: bool) {
+        self.records_hashes = record;
+    }
+    /// hash of the current state: all visited floors, items on them, enemies, the player
+    /// and the states of the dungeon, item and enemy RNGs.
+    /// UI state, messages and the count prefix are not included.
+    pub fn state_hash(&self) -> StateHash {
+        let mut hasher = StateHasher::default();
+        self.dungeon.hash_state(&mut hasher);
+        self.item.hash_state(&mut hasher);
+        self.enemies.hash_state(&mut hasher);
+        self.player.hash_state(&mut hasher);
+        StateHash(hasher.finish())
+    }
+    /// the config this game was built from
+    pub fn game_config(&self) -> &GameConfig {
+        &self.game_config
+    }
     pub fn saved_inputs_as_json(&self) -> GameResult<String> {
         serde_json::to_string_pretty(&self.saved_inputs)
             .into_chained(|| "Runtime::saved_inputs_json: Failed to serialize")
@@ -376,6 +650,50 @@ impl RunTime {
     pub fn history(&self, player_stat: &player::Status) -> Option<Array2<bool>> {
         self.dungeon.get_history(&player_stat)
     }
+    /// text of the message in the current language
+    pub fn format_msg(&self, msg: &GameMsg) -> Option<String> {
+        self.locale.text(msg)
+    }
+    /// replace the message table
+    pub fn set_locale(&mut self, locale: Box<dyn Locale>) {
+        self.locale = locale;
+    }
+    /// register an observer which receives game events
+    pub fn add_observer(&mut self, observer: Box<dyn GameObserver>) -> ObserverId {
+        let id = ObserverId(self.next_observer_id);
+        self.next_observer_id += 1;
+        self.observers.push((id, observer));
+        id
+    }
+    /// unregister an observer and return it
+    pub fn remove_observer(&mut self, id: ObserverId) -> Option<Box<dyn GameObserver>> {
+        let idx = self.observers.iter().position(|(i, _)| *i == id)?;
+        Some(self.observers.remove(idx).1)
+    }
+    /// start writing the event log of this episode
+    pub fn start_event_log(&mut self, writer: impl io::Write + 'static) -> GameResult<ObserverId> {
+        let logger = event_log::EventLogger::start(self, writer)?;
+        Ok(self.add_observer(Box::new(logger)))
+    }
+    pub fn seed(&self) -> u128 {
+        self.config.seed
+    }
+    /// previous messages
+    pub fn messages(&self) -> &MessageLog {
+        &self.messages
+    }
+    /// items which can be selected in the current prompt
+    pub fn prompt_items(&self) -> Vec<(char, &item::ItemToken)> {
+        match self.ui {
+            UiState::Mordal(MordalKind::SelectItem(action)) => self
+                .player
+                .itembox
+                .entries()
+                .filter(|(_, token)| action.accepts(token))
+                .collect(),
+            _ => vec![],
+        }
+    }
     pub fn itembox(&self) -> &item::ItemBox {
         debug!("itembox {:?}", self.player.itembox);
         &self.player.itembox
@@ -387,7 +705,7 @@ pub fn json_to_inputs(json: &str) -> GameResult<Vec<InputCode>> {
 }
 
 /// Reaction to user input
-#[derive(Clone, Debug, PartialEq)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub enum Reaction {
     /// dungeon
     Redraw,
@@ -399,18 +717,42 @@ pub enum Reaction {
     Notify(GameMsg),
 }
 
-#[derive(Clone, Debug, PartialEq)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub enum GameMsg {
     CantMove(Direction),
     CantGetItem(ItemKind),
-    GotItem { kind: ItemKind, num: u32 },
-    HitTo(SmallStr),
-    HitFrom(SmallStr),
+    GotItem {
+        kind: ItemKind,
+        num: u32,
+    },
+    /// the player hit an enemy
+    HitTo(Hit),
+    /// an enemy hit the player
+    HitFrom(Hit),
     MissTo(SmallStr),
     MissFrom(SmallStr),
-    Killed(SmallStr),
+    Killed {
+        name: SmallStr,
+        exp: Exp,
+    },
     NoDownStair,
     SecretDoor,
+    TreasureRoom,
+    NoLockedDoor,
+    PickedLock,
+    FailedToPick,
+    KickedOpen,
+    KickFailed,
+    NoRoute,
+    WhichItem(ItemAction),
+    NothingAppropriate,
+    NoSuchItem(char),
+    CantUse(ItemAction),
+    AlreadyInUse,
+    Equipped {
+        action: ItemAction,
+        name: SmallStr,
+    },
     Quit,
 }
 
@@ -421,6 +763,22 @@ pub struct GlobalConfig {
     pub height: Y,
     pub seed: u128,
     pub hide_dungeon: bool,
+    pub start_level: u32,
+    pub seed_per_level: bool,
+}
+
+impl GlobalConfig {
+    /// the seed to generate the specified level with, if the level has its own seed.
+    /// The level where the game starts always has its own seed when it's deeper than 1,
+    /// so that the game starts on the same floor as `GameConfig::build_floor`.
+    pub(crate) fn level_seed(&self, branch: u32, level: u32) -> Option<u128> {
+        let is_deep_start = branch == 0 && level == self.start_level && level > 1;
+        if !self.seed_per_level && !is_deep_start {
+            return None;
+        }
+        let seed = rng::branch_seed(self.seed, branch);
+        Some(rng::level_seed(seed, level))
+    }
 }
 
 /// game information shared and able to be modified by each modules
@@ -468,3 +826,94 @@ mod config_test {
         assert_eq!(config, GameConfig::default());
     }
 }
+#[cfg(test)]
+mod runtime_test {
+    use super::*;
+    use dungeon::custom::{test_config, TEST_MAP};
+    use rand::RngCore;
+    #[test]
+    fn item_prompt() {
+        let mut runtime: RunTime = test_config(&[TEST_MAP]).build().unwrap();
+        let res = runtime.react_to_key(Key::Char('w')).unwrap();
+        let prompt = UiState::Mordal(MordalKind::SelectItem(ItemAction::Wield));
+        assert_eq!(
+            res,
+            vec![
+                Reaction::UiTransition(prompt.clone()),
+                Reaction::Notify(GameMsg::WhichItem(ItemAction::Wield)),
+            ]
+        );
+        let items: Vec<_> = runtime
+            .prompt_items()
+            .into_iter()
+            .map(|(c, t)| (c, t.attr.is_equiped()))
+            .collect();
+        assert!(items.len() >= 2);
+        // wield a weapon not equipped yet
+        let (c, _) = *items.iter().find(|(_, equipped)| !equipped).unwrap();
+        let res = runtime.react_to_key(Key::Char(c)).unwrap();
+        assert_eq!(res[0], Reaction::UiTransition(UiState::Dungeon));
+        assert!(matches!(res[1], Reaction::Notify(GameMsg::Equipped { .. })));
+        assert!(runtime.player.weapon().unwrap().attr.is_equiped());
+        assert_eq!(
+            runtime
+                .player
+                .itembox
+                .items()
+                .filter(|i| i.attr.is_equiped())
+                .count(),
+            2
+        );
+        // wrong items
+        runtime.react_to_key(Key::Char('W')).unwrap();
+        let res = runtime.react_to_key(Key::Char(c)).unwrap();
+        assert_eq!(res[1], Reaction::Notify(GameMsg::CantUse(ItemAction::Wear)));
+        runtime.react_to_key(Key::Char('w')).unwrap();
+        let res = runtime.react_to_key(Key::Char('z')).unwrap();
+        assert_eq!(res[1], Reaction::Notify(GameMsg::NoSuchItem('z')));
+        runtime.react_to_key(Key::Char('w')).unwrap();
+        runtime.react_to_key(Key::Esc).unwrap();
+        assert_eq!(runtime.ui, UiState::Dungeon);
+        // all messages are in the log
+        let log: Vec<_> = runtime.messages().iter().map(|m| m.text.as_str()).collect();
+        assert_eq!(log.len(), 7);
+        assert_eq!(log[0], "Which object do you want to wield?");
+        assert!(log[1].starts_with("You are now wielding"));
+        assert_eq!(log[3], "You can't wear that");
+        assert_eq!(log[5], "'z' is not a valid item");
+        assert_eq!(runtime.messages().last(1).next().unwrap().turn, 1);
+        let res = runtime.react_to_key(Key::Ctrl('p')).unwrap();
+        assert_eq!(
+            res,
+            vec![Reaction::UiTransition(UiState::Mordal(
+                MordalKind::Messages
+            ))]
+        );
+    }
+    #[test]
+    fn state_hash() {
+        let mut config = test_config(&[TEST_MAP, &["-----", "|@.%|", "-----"]]);
+        config.seed = Some(1);
+        let play = |keys: &str| {
+            let mut runtime: RunTime = config.clone().build().unwrap();
+            for c in keys.chars() {
+                runtime.react_to_key(Key::Char(c)).unwrap();
+            }
+            runtime
+        };
+        // the same floor and player, but a different way through the past floor
+        let (mut a, b) = (play("nllllllll>"), play("lnlllllll>"));
+        assert_eq!(a.player_status(), b.player_status());
+        assert_ne!(a.state_hash(), b.state_hash());
+        let rng_changed = |runtime: &mut RunTime, i: usize| {
+            let before = runtime.state_hash();
+            match i {
+                0 => runtime.item.rng().next_u32(),
+                _ => runtime.enemies.rng().next_u32(),
+            };
+            runtime.state_hash() != before
+        };
+        assert!(rng_changed(&mut a, 0));
+        assert!(rng_changed(&mut a, 1));
+    }
+}
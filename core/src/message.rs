@@ -0,0 +1,217 @@
+//! message texts and history
+use character::ItemAction;
+use item::ItemKind;
+use std::collections::VecDeque;
+use GameMsg;
+
+/// max number of messages kept in the history
+pub const MESSAGE_LOG_SIZE: usize = 100;
+
+/// table of message texts in a language
+pub trait Locale {
+    /// text shown to the player, or None if the message is silent
+    fn text(&self, msg: &GameMsg) -> Option<String>;
+}
+
+/// built-in languages
+#[derive(Clone, Copy, Debug, Default, Serialize, Deserialize, Eq, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Language {
+    #[default]
+    En,
+    Ja,
+}
+
+impl Language {
+    pub fn locale(self) -> Box<dyn Locale> {
+        match self {
+            Language::En => Box::new(English),
+            Language::Ja => Box::new(Japanese),
+        }
+    }
+}
+
+impl GameMsg {
+    /// text in English
+    pub fn text(&self) -> Option<String> {
+        English.text(self)
+    }
+}
+
+/// rogue style English messages
+#[derive(Clone, Copy, Debug, Default)]
+pub struct English;
+
+impl Locale for English {
+    fn text(&self, msg: &GameMsg) -> Option<String> {
+        let s = match msg {
+            GameMsg::CantMove(_) => return None,
+            GameMsg::CantGetItem(kind) => format!("You moved onto {}", kind),
+            GameMsg::NoDownStair => "Hmm... there seems to be no downstair".to_owned(),
+            GameMsg::GotItem {
+                kind: ItemKind::Gold,
+                num,
+            } => format!("You found {} gold pieces", num),
+            GameMsg::GotItem { kind, num } => format!("You now have {} {}", num, kind),
+            GameMsg::SecretDoor => "You found a secret door".to_owned(),
+            GameMsg::TreasureRoom => "You have found a treasure room!".to_owned(),
+            GameMsg::NoLockedDoor => "You see no locked door there".to_owned(),
+            GameMsg::PickedLock => "You succeed in picking the lock".to_owned(),
+            GameMsg::FailedToPick => "This door is locked".to_owned(),
+            GameMsg::KickedOpen => "WHAMM! The door crashes open!".to_owned(),
+            GameMsg::KickFailed => "WHAMM!!".to_owned(),
+            GameMsg::NoRoute => "You don't know the way".to_owned(),
+            GameMsg::HitTo(hit) => format!("You hit the {}", hit.name),
+            GameMsg::HitFrom(hit) => format!("The {} hits you", hit.name),
+            GameMsg::MissTo(s) => format!("You miss the {}", s),
+            GameMsg::MissFrom(s) => format!("The {} misses you", s),
+            GameMsg::Killed { name, .. } => format!("You defeated the {}", name),
+            GameMsg::WhichItem(a) => format!("Which object do you want to {}?", a),
+            GameMsg::NothingAppropriate => "You don't have anything appropriate".to_owned(),
+            GameMsg::NoSuchItem(c) => format!("'{}' is not a valid item", c),
+            GameMsg::CantUse(a) => format!("You can't {} that", a),
+            GameMsg::AlreadyInUse => "That's already in use".to_owned(),
+            GameMsg::Equipped { action, name } => format!("You are now {}ing {}", action, name),
+            GameMsg::Quit => "Thank you for playing!".to_owned(),
+        };
+        Some(s)
+    }
+}
+
+/// Japanese messages
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Japanese;
+
+impl Japanese {
+    fn verb(action: ItemAction) -> &'static str {
+        match action {
+            ItemAction::Wear => "身につける",
+            ItemAction::Wield => "構える",
+        }
+    }
+}
+
+impl Locale for Japanese {
+    fn text(&self, msg: &GameMsg) -> Option<String> {
+        let s = match msg {
+            GameMsg::CantMove(_) => return None,
+            GameMsg::CantGetItem(kind) => format!("{}の上に乗った", kind),
+            GameMsg::NoDownStair => "ここには下り階段がないようだ".to_owned(),
+            GameMsg::GotItem {
+                kind: ItemKind::Gold,
+                num,
+            } => format!("{}ゴールドを見つけた", num),
+            GameMsg::GotItem { kind, num } => format!("{}を{}個手に入れた", kind, num),
+            GameMsg::SecretDoor => "隠し扉を見つけた".to_owned(),
+            GameMsg::TreasureRoom => "宝物庫を見つけた!".to_owned(),
+            GameMsg::NoLockedDoor => "そこに鍵のかかった扉はない".to_owned(),
+            GameMsg::PickedLock => "鍵を開けた".to_owned(),
+            GameMsg::FailedToPick => "この扉には鍵がかかっている".to_owned(),
+            GameMsg::KickedOpen => "ドカン! 扉が壊れて開いた!".to_owned(),
+            GameMsg::KickFailed => "ドカン!!".to_owned(),
+            GameMsg::NoRoute => "道がわからない".to_owned(),
+            GameMsg::HitTo(hit) => format!("{}に攻撃が当たった", hit.name),
+            GameMsg::HitFrom(hit) => format!("{}の攻撃が当たった", hit.name),
+            GameMsg::MissTo(s) => format!("{}への攻撃は外れた", s),
+            GameMsg::MissFrom(s) => format!("{}の攻撃は外れた", s),
+            GameMsg::Killed { name, .. } => format!("{}を倒した", name),
+            GameMsg::WhichItem(a) => format!("どれを{}?", Self::verb(*a)),
+            GameMsg::NothingAppropriate => "使えるものを持っていない".to_owned(),
+            GameMsg::NoSuchItem(c) => format!("'{}'というアイテムはない", c),
+            GameMsg::CantUse(a) => format!("それを{}ことはできない", Self::verb(*a)),
+            GameMsg::AlreadyInUse => "それはもう使っている".to_owned(),
+            GameMsg::Equipped { action, name } => match action {
+                ItemAction::Wear => format!("{}を身につけた", name),
+                ItemAction::Wield => format!("{}を構えた", name),
+            },
+            GameMsg::Quit => "遊んでくれてありがとう!".to_owned(),
+        };
+        Some(s)
+    }
+}
+
+/// a message with the turn it was shown
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub struct LoggedMessage {
+    pub turn: u32,
+    pub text: String,
+}
+
+/// ring buffer of recent messages
+#[derive(Clone, Debug)]
+pub struct MessageLog {
+    inner: VecDeque<LoggedMessage>,
+    capacity: usize,
+}
+
+impl MessageLog {
+    pub fn new(capacity: usize) -> Self {
+        MessageLog {
+            inner: VecDeque::with_capacity(capacity),
+            capacity,
+        }
+    }
+    pub(crate) fn push(&mut self, turn: u32, text: String) {
+        if self.inner.len() == self.capacity {
+            self.inner.pop_front();
+        }
+        self.inner.push_back(LoggedMessage { turn, text });
+    }
+    pub fn len(&self) -> usize {
+        self.inner.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.inner.is_empty()
+    }
+    /// all messages from the oldest one
+    pub fn iter(&self) -> impl Iterator<Item = &LoggedMessage> {
+        self.inner.iter()
+    }
+    /// last `n` messages from the oldest one
+    pub fn last(&self, n: usize) -> impl Iterator<Item = &LoggedMessage> {
+        self.inner.iter().skip(self.inner.len().saturating_sub(n))
+    }
+}
+
+impl Default for MessageLog {
+    fn default() -> Self {
+        MessageLog::new(MESSAGE_LOG_SIZE)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use character::Exp;
+    use SmallStr;
+    #[test]
+    fn ring_buffer() {
+        let mut log = MessageLog::new(3);
+        for turn in 0..5 {
+            log.push(turn, format!("{}", turn));
+        }
+        assert_eq!(log.len(), 3);
+        let turns: Vec<_> = log.iter().map(|m| m.turn).collect();
+        assert_eq!(turns, vec![2, 3, 4]);
+        let last: Vec<_> = log.last(2).map(|m| m.turn).collect();
+        assert_eq!(last, vec![3, 4]);
+        assert_eq!(log.last(10).count(), 3);
+    }
+    #[test]
+    fn locales() {
+        let msg = GameMsg::Killed {
+            name: SmallStr::from_str("bat"),
+            exp: Exp(1),
+        };
+        assert_eq!(English.text(&msg).unwrap(), "You defeated the bat");
+        assert_eq!(Language::Ja.locale().text(&msg).unwrap(), "batを倒した");
+        let gold = GameMsg::GotItem {
+            kind: ItemKind::Gold,
+            num: 12,
+        };
+        assert_eq!(gold.text().unwrap(), "You found 12 gold pieces");
+        let silent = GameMsg::CantMove(::dungeon::Direction::Up);
+        assert_eq!(English.text(&silent), None);
+        assert_eq!(Japanese.text(&silent), None);
+    }
+}
@@ -0,0 +1,191 @@
+//! hooks for game events
+use character::{fight::Hit, Exp};
+use input::InputCode;
+use item::ItemKind;
+use ui::{MordalKind, UiState};
+use {GameMsg, Reaction, RunTime};
+
+/// context passed to observers with each event
+pub struct EventContext<'a> {
+    /// the runtime after the input is processed
+    pub runtime: &'a RunTime,
+    /// the input which caused the event
+    pub input: InputCode,
+    /// turns passed since the game started
+    pub turn: u32,
+    /// all reactions to the input
+    pub reactions: &'a [Reaction],
+}
+
+/// receiver of game events, registered by `RunTime::add_observer`.
+/// All methods do nothing by default.
+pub trait GameObserver {
+    /// called once per input which took one or more turns
+    fn on_turn_end(&mut self, _ctx: &EventContext, _turns_passed: u32) {}
+    /// the player hit an enemy
+    fn on_damage_dealt(&mut self, _ctx: &EventContext, _hit: &Hit) {}
+    /// an enemy hit the player
+    fn on_damage_taken(&mut self, _ctx: &EventContext, _hit: &Hit) {}
+    /// the player killed an enemy
+    fn on_kill(&mut self, _ctx: &EventContext, _name: &str, _exp: Exp) {}
+    /// the player picked up an item
+    fn on_item_pickup(&mut self, _ctx: &EventContext, _kind: &ItemKind, _num: u32) {}
+    /// the player moved to another level
+    fn on_level_change(&mut self, _ctx: &EventContext, _from: u32, _to: u32) {}
+    /// the player died
+    fn on_death(&mut self, _ctx: &EventContext, _cause: &str) {}
+    /// any message, with its text in the current language
+    fn on_message(&mut self, _ctx: &EventContext, _msg: &GameMsg, _text: Option<&str>) {}
+    /// called for every input, after all other callbacks
+    fn on_step(&mut self, _ctx: &EventContext) {}
+}
+
+/// handle to remove a registered observer
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash)]
+pub struct ObserverId(pub(crate) usize);
+
+/// game state before processing an input, to detect changes
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct Snapshot {
+    pub(crate) turn: u32,
+    pub(crate) level: u32,
+}
+
+pub(crate) fn dispatch(
+    observers: &mut [(ObserverId, Box<dyn GameObserver>)],
+    runtime: &RunTime,
+    input: InputCode,
+    before: Snapshot,
+    reactions: &[Reaction],
+) {
+    let turn = runtime.player.turns();
+    let ctx = EventContext {
+        runtime,
+        input,
+        turn,
+        reactions,
+    };
+    let level = runtime.dungeon.level();
+    for (_, observer) in observers.iter_mut() {
+        for r in reactions {
+            match r {
+                Reaction::Notify(msg) => {
+                    match msg {
+                        GameMsg::HitTo(hit) => observer.on_damage_dealt(&ctx, hit),
+                        GameMsg::HitFrom(hit) => observer.on_damage_taken(&ctx, hit),
+                        GameMsg::Killed { name, exp } => {
+                            observer.on_kill(&ctx, name.as_str(), *exp)
+                        }
+                        GameMsg::GotItem { kind, num } => observer.on_item_pickup(&ctx, kind, *num),
+                        _ => {}
+                    }
+                    let text = runtime.format_msg(msg);
+                    observer.on_message(&ctx, msg, text.as_deref());
+                }
+                Reaction::UiTransition(UiState::Mordal(MordalKind::Grave(cause))) => {
+                    observer.on_death(&ctx, cause)
+                }
+                _ => {}
+            }
+        }
+        if level != before.level {
+            observer.on_level_change(&ctx, before.level, level);
+        }
+        if turn > before.turn {
+            observer.on_turn_end(&ctx, turn - before.turn);
+        }
+        observer.on_step(&ctx);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use input::Key;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+    use GameConfig;
+    #[derive(Default)]
+    struct Counter {
+        turns: u32,
+        dealt: i64,
+        kills: Vec<String>,
+        golds: u32,
+        levels: Vec<(u32, u32)>,
+        messages: usize,
+    }
+    struct Observer(Rc<RefCell<Counter>>);
+    impl GameObserver for Observer {
+        fn on_turn_end(&mut self, _ctx: &EventContext, turns: u32) {
+            self.0.borrow_mut().turns += turns;
+        }
+        fn on_damage_dealt(&mut self, _ctx: &EventContext, hit: &Hit) {
+            self.0.borrow_mut().dealt += hit.damage.0;
+        }
+        fn on_kill(&mut self, _ctx: &EventContext, name: &str, _exp: Exp) {
+            self.0.borrow_mut().kills.push(name.to_owned());
+        }
+        fn on_item_pickup(&mut self, _ctx: &EventContext, kind: &ItemKind, num: u32) {
+            assert_eq!(*kind, ItemKind::Gold);
+            self.0.borrow_mut().golds += num;
+        }
+        fn on_level_change(&mut self, ctx: &EventContext, from: u32, to: u32) {
+            assert_eq!(ctx.runtime.player_status().dungeon_level, to);
+            self.0.borrow_mut().levels.push((from, to));
+        }
+        fn on_message(&mut self, _ctx: &EventContext, _msg: &GameMsg, text: Option<&str>) {
+            if text.is_some() {
+                self.0.borrow_mut().messages += 1;
+            }
+        }
+    }
+    const CONFIG: &str = r#"
+{
+    "width": 32,
+    "height": 16,
+    "hide_dungeon": false,
+    "dungeon": {
+        "style": "custom",
+        "maps": [
+            [
+                "---------",
+                "|@E.*..%|",
+                "---------"
+            ],
+            [
+                "------",
+                "|.@..|",
+                "------"
+            ]
+        ]
+    }
+}
+"#;
+    #[test]
+    fn observe() {
+        let mut runtime = GameConfig::from_json(CONFIG).unwrap().build().unwrap();
+        let counter = Rc::new(RefCell::new(Counter::default()));
+        let id = runtime.add_observer(Box::new(Observer(Rc::clone(&counter))));
+        while counter.borrow().kills.is_empty() {
+            runtime.react_to_key(Key::Char('l')).unwrap();
+        }
+        assert!(counter.borrow().dealt > 0);
+        let kill_turn = counter.borrow().turns;
+        assert_eq!(kill_turn, runtime.player.turns());
+        for _ in 0..6 {
+            runtime.react_to_key(Key::Char('l')).unwrap();
+        }
+        runtime.react_to_key(Key::Char('>')).unwrap();
+        {
+            let counter = counter.borrow();
+            assert_eq!(counter.kills, vec!["emu".to_owned()]);
+            assert!(counter.golds > 0);
+            assert_eq!(counter.levels, vec![(1, 2)]);
+            assert_eq!(counter.turns, kill_turn + 7);
+            assert_eq!(counter.messages, runtime.messages().len());
+        }
+        assert!(runtime.remove_observer(id).is_some());
+        runtime.react_to_key(Key::Char('l')).unwrap();
+        assert_eq!(counter.borrow().turns, kill_turn + 7);
+    }
+}
@@ -0,0 +1,103 @@
+//! requests and responses of rogue-gym-server, one JSON object per line
+use character::player::Status;
+use input::{InputCode, Key};
+use replay::Replay;
+use GameConfig;
+
+/// a request like `{"step": {"key": "h"}}` or `"observe"`
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Request {
+    /// start a new game, with the default config if omitted
+    New {
+        #[serde(default)]
+        config: GameConfig,
+    },
+    /// give a key or an input code to the game
+    Step {
+        #[serde(default)]
+        key: Option<KeyArg>,
+        #[serde(default)]
+        input: Option<InputCode>,
+    },
+    Observe,
+    /// get the replay of the current game
+    Save,
+    /// replay the game and continue from the end of it
+    Restore(Replay),
+}
+
+/// a key, where a single character like `"h"` means `Key::Char`
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum KeyArg {
+    Char(char),
+    Key(Key),
+}
+
+impl From<KeyArg> for Key {
+    fn from(arg: KeyArg) -> Key {
+        match arg {
+            KeyArg::Char(c) => Key::Char(c),
+            KeyArg::Key(k) => k,
+        }
+    }
+}
+
+/// id of a session in the multi-session server
+pub type SessionId = u64;
+
+/// a request to the multi-session server, like `{"session": {"id": 1, "request": "observe"}}`
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HubRequest {
+    /// start a game in a new session
+    Open {
+        #[serde(default)]
+        config: GameConfig,
+    },
+    /// send a request to a session
+    Session {
+        id: SessionId,
+        request: Request,
+    },
+    Close {
+        id: SessionId,
+    },
+    /// get ids of all sessions
+    List,
+    /// receive an observation each time the session advances, until it's closed
+    Watch {
+        id: SessionId,
+    },
+    /// close all sessions and stop the server
+    Shutdown,
+}
+
+/// a response like `{"observation": {...}}` or `{"error": "..."}`
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Response {
+    Observation(Observation),
+    Replay(Box<Replay>),
+    Error(String),
+    Opened(SessionId),
+    Closed(SessionId),
+    Sessions(Vec<SessionId>),
+    /// the server is shutting down
+    Bye,
+}
+
+/// what the player sees
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Observation {
+    /// the screen, as a string per row
+    pub map: Vec<String>,
+    pub status: Status,
+    /// messages shown by the last step
+    pub messages: Vec<String>,
+    /// the player died or quit the game
+    pub game_over: bool,
+    /// number of inputs given so far
+    pub steps: usize,
+}
@@ -0,0 +1,261 @@
+//! self-describing replay files
+use error::*;
+use hash::StateHash;
+use input::InputCode;
+use serde_json;
+use {GameConfig, RunTime};
+
+/// version of the replay format
+pub const REPLAY_VERSION: u32 = 1;
+/// version of this crate, recorded in replays
+pub const CRATE_VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// inputs of a game with everything needed to reproduce it
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct Replay {
+    pub version: u32,
+    pub crate_version: String,
+    /// the config with `seed` set to the resolved seed
+    pub config: GameConfig,
+    pub seed: u128,
+    pub inputs: Vec<InputCode>,
+    /// state hash after each input
+    pub hashes: Vec<StateHash>,
+}
+
+impl Replay {
+    /// make a replay of the game so far.
+    /// if `runtime` didn't record state hashes, the game is played again to get them.
+    pub fn from_runtime(runtime: &RunTime) -> GameResult<Self> {
+        let seed = runtime.seed();
+        let config = GameConfig::resolved(runtime);
+        let inputs = runtime.saved_inputs();
+        if runtime.state_hashes().len() != inputs.len() {
+            return Replay::record(config, inputs);
+        }
+        Ok(Replay {
+            version: REPLAY_VERSION,
+            crate_version: CRATE_VERSION.to_owned(),
+            config,
+            seed,
+            inputs: inputs.to_vec(),
+            hashes: runtime.state_hashes().to_vec(),
+        })
+    }
+    /// play `inputs` with `config` and make a replay of it
+    pub fn record(config: GameConfig, inputs: &[InputCode]) -> GameResult<Self> {
+        let mut runtime = config.build()?;
+        runtime.set_record_hashes(true);
+        for &input in inputs {
+            let _ = runtime.react_to_input(input);
+        }
+        Replay::from_runtime(&runtime)
+    }
+    pub fn from_json(json: &str) -> GameResult<Self> {
+        let replay: Replay = match serde_json::from_str(json) {
+            Ok(r) => r,
+            Err(e) => {
+                if json.trim_start().starts_with('[') {
+                    return Err(ErrorId::ReplayMismatch.into_with(|| {
+                        "Replay::from_json: this is an old replay without config and seed, \
+                         use Replay::record to convert it"
+                    }));
+                }
+                return Err(e).into_chained(|| "Replay::from_json");
+            }
+        };
+        replay.validate()?;
+        Ok(replay)
+    }
+    pub fn to_json(&self) -> GameResult<String> {
+        serde_json::to_string_pretty(self).into_chained(|| "Replay::to_json")
+    }
+    fn validate(&self) -> GameResult<()> {
+        if self.version != REPLAY_VERSION {
+            let version = self.version;
+            return Err(ErrorId::ReplayMismatch.into_with(move || {
+                format!(
+                    "replay version {} is not supported (expected {})",
+                    version, REPLAY_VERSION
+                )
+            }));
+        }
+        if self.config.seed != Some(self.seed) {
+            return Err(ErrorId::ReplayMismatch.into_with(|| "seed differs from the config"));
+        }
+        if self.hashes.len() != self.inputs.len() {
+            return Err(ErrorId::ReplayMismatch.into_with(|| "wrong number of state hashes"));
+        }
+        if self.crate_version != CRATE_VERSION {
+            warn!(
+                "replay was recorded by rogue-gym-core {}, not {}",
+                self.crate_version, CRATE_VERSION
+            );
+        }
+        Ok(())
+    }
+    /// fail if `config` would play a different game from this replay
+    pub fn check_config(&self, config: &GameConfig) -> GameResult<()> {
+        config
+            .check_same_game(&self.config)
+            .chain_err(|| "Replay::check_config")
+    }
+    /// start the game to replay
+    pub fn build_runtime(&self) -> GameResult<RunTime> {
+        self.validate()?;
+        self.config.clone().build()
+    }
+    /// fail if `runtime` diverged from this replay after `step` inputs
+    pub fn check_step(&self, step: usize, runtime: &RunTime) -> GameResult<()> {
+        let expected = match step.checked_sub(1).and_then(|i| self.hashes.get(i)) {
+            Some(h) => *h,
+            None => return Ok(()),
+        };
+        let actual = runtime.state_hash();
+        if actual != expected {
+            return Err(ErrorId::ReplayMismatch.into_with(move || {
+                format!(
+                    "state diverged at step {}: expected {}, got {}",
+                    step, expected, actual
+                )
+            }));
+        }
+        Ok(())
+    }
+    /// the first step where `other` has a different input or state from this replay
+    pub fn first_divergence(&self, other: &Replay) -> Option<usize> {
+        if self.seed != other.seed {
+            return Some(0);
+        }
+        let steps = self.inputs.iter().zip(&self.hashes);
+        let other_steps = other.inputs.iter().zip(&other.hashes);
+        let diverged = steps.zip(other_steps).position(|(s, o)| s != o);
+        match diverged {
+            Some(i) => Some(i + 1),
+            None if self.inputs.len() != other.inputs.len() => {
+                Some(self.inputs.len().min(other.inputs.len()) + 1)
+            }
+            None => None,
+        }
+    }
+    /// replay all inputs and return the first step where the state differs from the record
+    pub fn verify(&self) -> GameResult<Option<usize>> {
+        let mut runtime = self.build_runtime()?;
+        for (i, (&input, &hash)) in self.inputs.iter().zip(&self.hashes).enumerate() {
+            let _ = runtime.react_to_input(input);
+            if runtime.state_hash() != hash {
+                return Ok(Some(i + 1));
+            }
+        }
+        Ok(None)
+    }
+    /// replay all inputs and check every step
+    pub fn run(&self) -> GameResult<RunTime> {
+        let mut runtime = self.build_runtime()?;
+        // the restored game can be saved again without replaying it
+        runtime.set_record_hashes(true);
+        for (i, &input) in self.inputs.iter().enumerate() {
+            // errors like ignored inputs are recorded, too
+            let _ = runtime.react_to_input(input);
+            self.check_step(i + 1, &runtime)?;
+        }
+        Ok(runtime)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use dungeon::Coord;
+    use input::Key;
+    use json_to_inputs;
+    #[test]
+    fn roundtrip() {
+        let config = GameConfig::default();
+        let mut runtime = config.clone().build().unwrap();
+        for &c in b"hjjkkllx.s>" {
+            let _ = runtime.react_to_key(Key::Char(c as char));
+        }
+        assert!(runtime.state_hashes().is_empty());
+        let replay = Replay::from_runtime(&runtime).unwrap();
+        assert_eq!(replay.inputs.len(), replay.hashes.len());
+        let recorded = Replay::record(replay.config.clone(), &replay.inputs).unwrap();
+        assert_eq!(recorded, replay);
+        let json = replay.to_json().unwrap();
+        let loaded = Replay::from_json(&json).unwrap();
+        assert_eq!(loaded, replay);
+        loaded.check_config(&config).unwrap();
+        let replayed = loaded.run().unwrap();
+        assert_eq!(replayed.state_hash(), runtime.state_hash());
+        let mut other = config.clone();
+        other.seed = Some(replay.seed + 1);
+        assert!(loaded.check_config(&other).is_err());
+        let mut broken = replay.clone();
+        broken.seed += 1;
+        broken.config.seed = Some(broken.seed);
+        assert!(broken.run().is_err());
+        assert!(Replay::from_json("[]").is_err());
+        assert_eq!(loaded.verify().unwrap(), None);
+        assert_eq!(loaded.first_divergence(&replay), None);
+        let mut changed = replay.clone();
+        changed.hashes[3] = StateHash(0);
+        assert_eq!(changed.verify().unwrap(), Some(4));
+        assert_eq!(changed.first_divergence(&replay), Some(4));
+        changed.inputs.truncate(2);
+        changed.hashes.truncate(2);
+        assert_eq!(replay.first_divergence(&changed), Some(3));
+    }
+    #[test]
+    fn deterministic() {
+        let mut config = GameConfig {
+            seed: Some(1),
+            ..Default::default()
+        };
+        let mut a = config.clone().build().unwrap();
+        let mut b = config.clone().build().unwrap();
+        a.set_record_hashes(true);
+        b.set_record_hashes(true);
+        for &c in b"hjklyubnhjklllllljjjjjjhhhhhhkkkkkks.>"
+            .iter()
+            .cycle()
+            .take(500)
+        {
+            let _ = a.react_to_key(Key::Char(c as char));
+            let _ = b.react_to_key(Key::Char(c as char));
+        }
+        assert_eq!(a.state_hashes().len(), 500);
+        assert_eq!(a.state_hashes(), b.state_hashes());
+        let fresh = config.clone().build().unwrap().state_hash();
+        config.seed = Some(2);
+        assert_ne!(config.build().unwrap().state_hash(), fresh);
+    }
+    #[test]
+    fn learned() {
+        let json = include_str!("../../data/learned/ddqn-minidungeon/best-actions.json");
+        let config = include_str!("../../data/learned/ddqn-minidungeon/config.json");
+        assert!(Replay::from_json(json).is_err());
+        let config = GameConfig::from_json(config).unwrap();
+        let inputs = json_to_inputs(json).unwrap();
+        let replay = Replay::record(config.clone(), &inputs).unwrap();
+        let loaded = Replay::from_json(&replay.to_json().unwrap()).unwrap();
+        loaded.check_config(&config).unwrap();
+        // the agent takes 4 golds and goes down the stair, as it did when it was trained
+        let mut runtime = loaded.build_runtime().unwrap();
+        let mut milestones = vec![];
+        let mut last = (1, 0);
+        for (i, &input) in inputs.iter().enumerate() {
+            let _ = runtime.react_to_input(input);
+            loaded.check_step(i + 1, &runtime).unwrap();
+            let status = runtime.player_status();
+            let now = (status.dungeon_level, status.gold);
+            if now != last {
+                milestones.push((i + 1, now));
+                last = now;
+            }
+        }
+        assert_eq!(milestones, vec![(2, (1, 2)), (16, (1, 4)), (19, (2, 4))]);
+        // and the 2nd level is also same as before
+        let pos = runtime.dungeon.path_to_cd(&runtime.player.pos);
+        assert_eq!(pos, Coord::new(20, 10));
+    }
+}
@@ -1,4 +1,5 @@
 use fenwick::FenwickSet;
+use hash::{HashState, StateHasher};
 use num_traits::PrimInt;
 use rand::{
     distributions::uniform::SampleUniform, thread_rng, Error as RndError, RngCore, SeedableRng,
@@ -6,6 +7,7 @@ use rand::{
 pub(crate) use rand::{seq::SliceRandom, Rng};
 use rand_xorshift::XorShiftRng;
 use std::cmp;
+use std::hash::Hasher;
 use std::mem;
 use std::ops::{Bound, Range, RangeBounds};
 
@@ -43,6 +45,20 @@ pub fn gen_ranged_seed(start: u128, end: u128) -> u128 {
     rng.gen_range(start, end)
 }
 
+/// derive the seed used to generate the specified level from the game seed
+/// level 1 uses the game seed as is
+pub fn level_seed(seed: u128, level: u32) -> u128 {
+    const MUL: u128 = 0x9e37_79b9_7f4a_7c15_f39c_c060_5ced_c835;
+    seed ^ u128::from(level.saturating_sub(1)).wrapping_mul(MUL)
+}
+
+/// derive the seed used to generate a side branch from the game seed
+/// branch 0(the main dungeon) uses the game seed as is
+pub fn branch_seed(seed: u128, branch: u32) -> u128 {
+    const MUL: u128 = 0xc2b2_ae3d_27d4_eb4f_1656_67b1_9e37_79f9;
+    seed ^ u128::from(branch).wrapping_mul(MUL).rotate_left(64)
+}
+
 impl RngHandle {
     fn gen_seed(seed: u128) -> [u8; 16] {
         unsafe { mem::transmute::<_, [u8; 16]>(seed) }
@@ -52,6 +68,10 @@ impl RngHandle {
         let seed = Self::gen_seed(seed);
         RngHandle(XorShiftRng::from_seed(seed))
     }
+    /// reset the inner state by specified seed
+    pub fn reseed(&mut self, seed: u128) {
+        *self = Self::from_seed(seed);
+    }
     /// create new Rng by random seed
     pub fn new() -> Self {
         let seed: [u8; 16] = thread_rng().gen();
@@ -116,6 +136,14 @@ impl RngCore for RngHandle {
     }
 }
 
+impl HashState for RngHandle {
+    fn hash_state(&self, hasher: &mut StateHasher) {
+        // the next 4 outputs of xorshift128 are exactly its inner state
+        let mut rng = self.clone();
+        (0..4).for_each(|_| hasher.write_u32(rng.next_u32()));
+    }
+}
+
 /// Iterator for RngHandle::select
 pub struct RandomSelecter<'a, T: PrimInt> {
     offset: T,
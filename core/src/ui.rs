@@ -1,3 +1,4 @@
+use character::{Action, ItemAction};
 use input::System;
 
 /// A representation of Ui transition
@@ -18,6 +19,10 @@ impl UiState {
 pub enum MordalKind {
     Grave(Box<str>),
     Inventory,
+    /// previous messages
+    Messages,
+    /// ask which item to use
+    SelectItem(ItemAction),
     Quit,
 }
 
@@ -29,7 +34,12 @@ impl MordalKind {
                 System::Yes => MordalMsg::Quit,
                 _ => MordalMsg::None,
             },
-            MordalKind::Inventory => match input {
+            MordalKind::Inventory | MordalKind::Messages => match input {
+                System::Cancel | System::Continue | System::Enter => MordalMsg::Cancel,
+                _ => MordalMsg::None,
+            },
+            MordalKind::SelectItem(action) => match input {
+                System::Select(c) => MordalMsg::Act(Action::UseItem(*action, c)),
                 System::Cancel | System::Continue | System::Enter => MordalMsg::Cancel,
                 _ => MordalMsg::None,
             },
@@ -42,6 +52,8 @@ impl MordalKind {
 }
 
 pub enum MordalMsg {
+    /// close the mordal and do the action
+    Act(Action),
     Quit,
     Save,
     Cancel,
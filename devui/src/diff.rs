@@ -0,0 +1,219 @@
+//! find the first step where two episodes differ
+use error::*;
+use rogue_gym_core::character::player::Status;
+use rogue_gym_core::error::ErrorId;
+use rogue_gym_core::input::InputCode;
+use rogue_gym_core::replay::Replay;
+use rogue_gym_core::{json_to_inputs, GameConfig, RunTime};
+use std::fmt;
+
+/// a config and inputs to play
+#[derive(Clone, Debug)]
+pub struct Episode {
+    pub config: GameConfig,
+    pub inputs: Vec<InputCode>,
+}
+
+impl Episode {
+    /// load a replay file, or an old input list played with `config`
+    pub fn from_json(json: &str, config: &GameConfig) -> GameResult<Self> {
+        if json.trim_start().starts_with('[') {
+            let inputs = json_to_inputs(json)?;
+            let config = config.clone();
+            return Ok(Episode { config, inputs });
+        }
+        let replay = Replay::from_json(json)?;
+        Ok(Episode::from(replay))
+    }
+}
+
+impl From<Replay> for Episode {
+    fn from(replay: Replay) -> Self {
+        Episode {
+            config: replay.config,
+            inputs: replay.inputs,
+        }
+    }
+}
+
+/// what the player sees after a step
+#[derive(Clone, Debug, PartialEq)]
+pub struct Frame {
+    pub map: Vec<String>,
+    pub status: Status,
+}
+
+impl Frame {
+    fn new(runtime: &RunTime) -> GameResult<Self> {
+        let map = runtime
+            .screen_rows()?
+            .into_iter()
+            .map(|row| row.trim_end().to_owned())
+            .collect();
+        let status = runtime.player_status();
+        Ok(Frame { map, status })
+    }
+}
+
+/// the first step where two episodes differ
+#[derive(Clone, Debug)]
+pub struct Divergence {
+    /// number of inputs processed, 0 means the initial state
+    pub step: usize,
+    pub inputs: (Option<InputCode>, Option<InputCode>),
+    pub frames: (Frame, Frame),
+}
+
+impl fmt::Display for Divergence {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let (left, right) = &self.frames;
+        writeln!(f, "diverged at step {}", self.step)?;
+        writeln!(f, "input  left: {:?}", self.inputs.0)?;
+        writeln!(f, "input right: {:?}", self.inputs.1)?;
+        writeln!(f, "status  left: {}", left.status)?;
+        writeln!(f, "status right: {}", right.status)?;
+        let width = left.map.iter().map(|s| s.len()).max().unwrap_or(0);
+        let rows = left.map.len().max(right.map.len());
+        for i in 0..rows {
+            let l = left.map.get(i).map_or("", |s| s.as_str());
+            let r = right.map.get(i).map_or("", |s| s.as_str());
+            let mark = if l == r { '|' } else { '*' };
+            writeln!(f, "{:w$} {} {}", l, mark, r, w = width)?;
+        }
+        Ok(())
+    }
+}
+
+/// result of comparing two episodes
+#[derive(Clone, Debug)]
+pub enum DiffResult {
+    Diverged(Box<Divergence>),
+    /// screens and status were the same in all `steps`
+    Same {
+        steps: usize,
+        lengths: (usize, usize),
+    },
+}
+
+impl fmt::Display for DiffResult {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DiffResult::Diverged(d) => write!(f, "{}", d),
+            DiffResult::Same { steps, lengths } => {
+                writeln!(f, "no difference in {} steps", steps)?;
+                if lengths.0 != lengths.1 {
+                    writeln!(
+                        f,
+                        "but left has {} inputs and right has {}",
+                        lengths.0, lengths.1
+                    )?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+/// process an input, where errors like ignored inputs are recorded, too
+fn react(runtime: &mut RunTime, input: InputCode) -> GameResult<()> {
+    match runtime.react_to_input(input) {
+        Ok(_) => Ok(()),
+        Err(e) => match e.find_root_cause().downcast_ref::<ErrorId>() {
+            Some(id) if id.can_allow() => Ok(()),
+            _ => Err(e),
+        },
+    }
+}
+
+/// play two episodes side by side and find the first step where the screen or status differ
+pub fn diff_episodes(left: &Episode, right: &Episode) -> GameResult<DiffResult> {
+    let mut runtimes = (left.config.clone().build()?, right.config.clone().build()?);
+    let steps = left.inputs.len().min(right.inputs.len());
+    for step in 0..=steps {
+        let inputs = if step == 0 {
+            (None, None)
+        } else {
+            let inputs = (left.inputs[step - 1], right.inputs[step - 1]);
+            react(&mut runtimes.0, inputs.0).chain_err(|| format!("left step {}", step))?;
+            react(&mut runtimes.1, inputs.1).chain_err(|| format!("right step {}", step))?;
+            (Some(inputs.0), Some(inputs.1))
+        };
+        let frames = (Frame::new(&runtimes.0)?, Frame::new(&runtimes.1)?);
+        if frames.0 != frames.1 {
+            let divergence = Divergence {
+                step,
+                inputs,
+                frames,
+            };
+            return Ok(DiffResult::Diverged(Box::new(divergence)));
+        }
+    }
+    Ok(DiffResult::Same {
+        steps,
+        lengths: (left.inputs.len(), right.inputs.len()),
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use rogue_gym_core::input::Key;
+    fn episode(keys: &str) -> Episode {
+        let config = GameConfig {
+            seed: Some(1),
+            ..Default::default()
+        };
+        let mut runtime = config.clone().build().unwrap();
+        for c in keys.chars() {
+            let _ = runtime.react_to_key(Key::Char(c));
+        }
+        let inputs = runtime.saved_inputs().to_vec();
+        Episode { config, inputs }
+    }
+    #[test]
+    fn identical() {
+        let left = episode("hjklyubn");
+        match diff_episodes(&left, &left.clone()).unwrap() {
+            DiffResult::Same { steps, lengths } => {
+                assert_eq!(steps, 8);
+                assert_eq!(lengths, (8, 8));
+            }
+            DiffResult::Diverged(d) => panic!("unexpected divergence: {}", d),
+        }
+    }
+    #[test]
+    fn diverged_at_step() {
+        let (left, right) = (episode("jjjjl"), episode("jjjkl"));
+        match diff_episodes(&left, &right).unwrap() {
+            DiffResult::Diverged(d) => {
+                assert_eq!(d.step, 4);
+                assert_ne!(d.inputs.0, d.inputs.1);
+                assert_ne!(d.frames.0, d.frames.1);
+            }
+            DiffResult::Same { .. } => panic!("the episodes should differ"),
+        }
+    }
+    #[test]
+    fn different_length() {
+        let (left, right) = (episode("hjkl"), episode("hj"));
+        match diff_episodes(&left, &right).unwrap() {
+            DiffResult::Same { steps, lengths } => {
+                assert_eq!(steps, 2);
+                assert_eq!(lengths, (4, 2));
+            }
+            DiffResult::Diverged(d) => panic!("unexpected divergence: {}", d),
+        }
+        let seed = GameConfig {
+            seed: Some(2),
+            ..Default::default()
+        };
+        let other = Episode {
+            config: seed,
+            inputs: vec![],
+        };
+        match diff_episodes(&left, &other).unwrap() {
+            DiffResult::Diverged(d) => assert_eq!(d.step, 0),
+            DiffResult::Same { .. } => panic!("different seeds should differ"),
+        }
+    }
+}
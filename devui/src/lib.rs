@@ -7,13 +7,17 @@ extern crate fern;
 extern crate log;
 extern crate rogue_gym_core;
 extern crate rogue_gym_uilib;
+extern crate serde;
+extern crate serde_json;
 extern crate termion;
 extern crate tuple_map;
 
+pub mod diff;
 pub mod error;
 pub mod screen;
+pub mod watch;
 use error::*;
-use rogue_gym_core::input::InputCode;
+use rogue_gym_core::replay::Replay;
 use rogue_gym_core::{GameConfig, RunTime};
 use rogue_gym_uilib::{process_reaction, Screen, Transition};
 use screen::{RawTerm, TermScreen};
@@ -40,9 +44,11 @@ fn setup_screen(
     Ok((screen, runtime))
 }
 
-pub fn play_game(config: GameConfig, is_default: bool) -> GameResult<RunTime> {
+/// play the game in the terminal. If `debug` is true, combat details are shown with messages.
+pub fn play_game(config: GameConfig, is_default: bool, debug: bool) -> GameResult<RunTime> {
     debug!("devui::play_game config: {:?}", config);
     let (mut screen, mut runtime) = setup_screen(config, is_default)?;
+    screen.debug = debug;
     let stdin = io::stdin();
     // let's receive keyboard inputs(our main loop)
     let mut pending = false;
@@ -78,18 +84,26 @@ pub fn play_game(config: GameConfig, is_default: bool) -> GameResult<RunTime> {
     Ok(runtime)
 }
 
-pub fn show_replay(config: GameConfig, replay: Vec<InputCode>, interval_ms: u64) -> GameResult<()> {
-    debug!("devui::show_replay config: {:?}", config);
+/// show the replay, stopping with an error if the game diverges from it
+pub fn show_replay(replay: Replay, interval_ms: u64) -> GameResult<()> {
+    debug!("devui::show_replay config: {:?}", replay.config);
     let (tx, rx) = mpsc::channel();
     let replay_thread = thread::spawn(move || {
-        let res = show_replay_(config, replay, interval_ms, rx);
+        let res = show_replay_(replay, interval_ms, rx);
         if let Err(e) = res {
             eprintln!("Error in viewer: {}", e);
         }
     });
+    wait_keys(tx)?;
+    replay_thread.join().unwrap();
+    Ok(())
+}
+
+/// send keys to the viewer thread until q or e is pressed
+fn wait_keys(tx: mpsc::Sender<ReplayInst>) -> GameResult<()> {
     let stdin = io::stdin();
     for key in stdin.keys() {
-        let key = key.into_chained(|| "in show_replay")?;
+        let key = key.into_chained(|| "in wait_keys")?;
         let mut end = false;
         let res = match key {
             Key::Char('E') | Key::Char('Q') | Key::Char('e') | Key::Char('q') | Key::Esc => {
@@ -107,26 +121,24 @@ pub fn show_replay(config: GameConfig, replay: Vec<InputCode>, interval_ms: u64)
             break;
         }
     }
-    replay_thread.join().unwrap();
     Ok(())
 }
 
 #[derive(Clone, Copy, Debug)]
-enum ReplayInst {
+pub(crate) enum ReplayInst {
     Pause,
     Start,
     End,
 }
 
 fn show_replay_(
-    config: GameConfig,
-    mut replay: Vec<InputCode>,
+    replay: Replay,
     interval_ms: u64,
     rx: mpsc::Receiver<ReplayInst>,
 ) -> GameResult<()> {
-    let (mut screen, mut runtime) = setup_screen(config, false)?;
+    let (mut screen, mut runtime) = setup_screen(replay.config.clone(), false)?;
     let mut sleeping = false;
-    replay.reverse();
+    let mut inputs = replay.inputs.iter();
     loop {
         match rx.try_recv() {
             Ok(ReplayInst::Start) => sleeping = false,
@@ -139,11 +151,16 @@ fn show_replay_(
         if sleeping {
             continue;
         }
-        let input = match replay.pop() {
-            Some(x) => x,
+        let input = match inputs.next() {
+            Some(&x) => x,
             None => continue,
         };
         let res = runtime.react_to_input(input);
+        let step = runtime.saved_inputs().len();
+        if let Err(e) = replay.check_step(step, &runtime) {
+            screen.clear_screen()?;
+            return Err(e);
+        }
         let res = match res {
             Ok(r) => r,
             Err(e) => {
@@ -151,11 +168,11 @@ fn show_replay_(
                 continue;
             }
         };
-        let left_turns = replay.len();
+        let left_turns = inputs.len();
         if left_turns == 0 {
             screen.message(format!("--Press q or e to exit--"))?;
         } else {
-            screen.message(format!("{} turns left", replay.len()))?;
+            screen.message(format!("{} turns left", left_turns))?;
         }
         for reaction in res {
             let result = process_reaction(&mut screen, &mut runtime, reaction)
@@ -11,11 +11,14 @@ use std::fs::{File, OpenOptions};
 use std::io::prelude::*;
 
 use clap::ArgMatches;
-use rogue_gym_core::{json_to_inputs, read_file, GameConfig};
+use rogue_gym_core::{read_file, replay::Replay, GameConfig};
+use rogue_gym_devui::diff::{diff_episodes, Episode};
 use rogue_gym_devui::error::*;
+use rogue_gym_devui::watch::{watch_game, Feed};
 use rogue_gym_devui::{play_game, show_replay};
 
 const DEFAULT_INTERVAL_MS: u64 = 500;
+const DEFAULT_WATCH_INTERVAL_MS: u64 = 100;
 
 fn main() {
     if let Err(err) = main_() {
@@ -31,25 +34,74 @@ fn main() {
 fn main_() -> GameResult<()> {
     let args = parse_args();
     let (mut config, is_default) = get_config(&args)?;
-    if let Some(seed) = args.value_of("seed") {
+    let seed = args.value_of("seed");
+    if let Some(seed) = seed {
         config.seed = Some(seed.parse().into_chained(|| "Failed to parse seed!")?);
     }
     setup_logger(&args)?;
     if let Some(replay_arg) = args.subcommand_matches("replay") {
         let fname = replay_arg.value_of("file").unwrap();
-        let replay = read_file(fname).into_chained(|| "Failed to read replay file!")?;
-        let replay = json_to_inputs(&replay)?;
+        let json = read_file(fname).into_chained(|| "Failed to read replay file!")?;
+        let replay = if json.trim_start().starts_with('[') {
+            // an old input list, played with the given config
+            let episode = Episode::from_json(&json, &config)?;
+            Replay::record(episode.config, &episode.inputs)?
+        } else {
+            let replay = Replay::from_json(&json)?;
+            // replays have their own config, but we check the given one is the same
+            if !is_default || seed.is_some() {
+                replay.check_config(&config)?;
+            }
+            replay
+        };
         let mut interval = DEFAULT_INTERVAL_MS;
         if let Some(inter) = replay_arg.value_of("interval") {
             interval = inter
                 .parse()
                 .into_chained(|| "Failed to parse 'interval' arg!")?;
         }
-        show_replay(config, replay, interval)
+        show_replay(replay, interval)
+    } else if let Some(watch_arg) = args.subcommand_matches("watch") {
+        let feed = get_feed(watch_arg)?;
+        let mut interval = DEFAULT_WATCH_INTERVAL_MS;
+        if let Some(inter) = watch_arg.value_of("interval") {
+            interval = inter
+                .parse()
+                .into_chained(|| "Failed to parse 'interval' arg!")?;
+        }
+        // games in the log have their own config, but we check the given one is the same
+        let check = if !is_default || seed.is_some() {
+            Some(config)
+        } else {
+            None
+        };
+        watch_game(feed, check, interval)
+    } else if let Some(diff_arg) = args.subcommand_matches("diff") {
+        let fname = diff_arg.value_of("file").unwrap();
+        let json = read_file(fname).into_chained(|| "Failed to read replay file!")?;
+        let left = Episode::from_json(&json, &config)?;
+        let right = match diff_arg.value_of("other") {
+            Some(other) => {
+                let json = read_file(other).into_chained(|| "Failed to read replay file!")?;
+                Episode::from_json(&json, &config)?
+            }
+            // play the same inputs with the given config
+            None => {
+                if config.seed.is_none() {
+                    config.seed = left.config.seed;
+                }
+                Episode {
+                    config,
+                    inputs: left.inputs.clone(),
+                }
+            }
+        };
+        print!("{}", diff_episodes(&left, &right)?);
+        Ok(())
     } else {
-        let runtime = play_game(config, is_default)?;
+        let runtime = play_game(config, is_default, args.is_present("debug"))?;
         if let Some(save_file) = args.value_of("save") {
-            let s = runtime.saved_inputs_as_json()?;
+            let s = Replay::from_runtime(&runtime)?.to_json()?;
             let mut file = File::create(save_file)?;
             file.write_all(s.as_bytes())?;
         }
@@ -69,8 +121,30 @@ fn get_config(args: &ArgMatches) -> GameResult<(GameConfig, bool)> {
             ErrorID::InvalidArg.into_with(|| "Only .json file is allowed as configuration file")
         );
     }
-    let f = read_file(file_name).into_chained(|| "in get_config")?;
-    Ok((GameConfig::from_json(&f)?, false))
+    Ok((GameConfig::from_file(file_name)?, false))
+}
+
+fn get_feed(args: &ArgMatches) -> GameResult<Feed> {
+    if let Some(log) = args.value_of("log") {
+        return Ok(Feed::Log(log.into()));
+    }
+    let id = match args.value_of("session") {
+        Some(id) => id.parse().into_chained(|| "Failed to parse session id!")?,
+        None => {
+            return Err(ErrorID::InvalidArg.into_with(|| "--session is needed to watch a server"))
+        }
+    };
+    if let Some(port) = args.value_of("tcp") {
+        let port = port.parse().into_chained(|| "Failed to parse port!")?;
+        return Ok(Feed::Tcp(port, id));
+    }
+    #[cfg(unix)]
+    {
+        if let Some(path) = args.value_of("unix") {
+            return Ok(Feed::Unix(path.into(), id));
+        }
+    }
+    Err(ErrorID::InvalidArg.into_with(|| "One of --log, --tcp or --unix is needed"))
 }
 
 fn parse_args<'a>() -> ArgMatches<'a> {
@@ -110,6 +184,11 @@ fn parse_args<'a>() -> ArgMatches<'a> {
                 .help("Set seed")
                 .takes_value(true),
         )
+        .arg(
+            clap::Arg::with_name("debug")
+                .long("debug")
+                .help("Show damages and exp with messages"),
+        )
         .arg(
             clap::Arg::with_name("save")
                 .long("save")
@@ -139,6 +218,69 @@ fn parse_args<'a>() -> ArgMatches<'a> {
                         .takes_value(true),
                 ),
         )
+        .subcommand(
+            clap::SubCommand::with_name("diff")
+                .about("Show the first step where two replays differ")
+                .version("0.1")
+                .arg(
+                    clap::Arg::with_name("file")
+                        .short("f")
+                        .long("file")
+                        .required(true)
+                        .value_name("FILE")
+                        .help("replay json file")
+                        .takes_value(true),
+                )
+                .arg(
+                    clap::Arg::with_name("other")
+                        .short("o")
+                        .long("other")
+                        .value_name("OTHER")
+                        .help("replay json file to compare (if omitted, replay FILE with the given config)")
+                        .takes_value(true),
+                ),
+        )
+        .subcommand(
+            clap::SubCommand::with_name("watch")
+                .about("Watch a game still running, by an event log or rogue-gym-server")
+                .version("0.1")
+                .arg(
+                    clap::Arg::with_name("log")
+                        .long("log")
+                        .value_name("LOG")
+                        .help("JSONL event log written by the game")
+                        .takes_value(true),
+                )
+                .arg(
+                    clap::Arg::with_name("tcp")
+                        .long("tcp")
+                        .value_name("PORT")
+                        .help("localhost port of rogue-gym-server")
+                        .takes_value(true),
+                )
+                .arg(
+                    clap::Arg::with_name("unix")
+                        .long("unix")
+                        .value_name("PATH")
+                        .help("Unix socket of rogue-gym-server")
+                        .takes_value(true),
+                )
+                .arg(
+                    clap::Arg::with_name("session")
+                        .long("session")
+                        .value_name("ID")
+                        .help("session id to watch in rogue-gym-server")
+                        .takes_value(true),
+                )
+                .arg(
+                    clap::Arg::with_name("interval")
+                        .short("i")
+                        .long("interval")
+                        .value_name("INTERVAL")
+                        .help("Interval to check the log in milliseconds")
+                        .takes_value(true),
+                ),
+        )
         .get_matches()
 }
 
@@ -17,6 +17,24 @@ pub struct TermScreen<T> {
     width: u16,
     height: u16,
     pub(crate) pending_messages: VecDeque<String>,
+    /// show combat details with messages
+    pub debug: bool,
+}
+
+/// check the terminal is large enough for a `w` x `h` screen, returning the terminal size
+fn check_size(w: i32, h: i32) -> GameResult<(u16, u16)> {
+    let (width, height) =
+        terminal_size().into_chained(|| "[Screen::check_size] attempt to get terminal size")?;
+    let (w, h) = (w, h).map(|i| i as u16);
+    if width < w {
+        return Err(ErrorID::InvalidScreenSize(width, height)
+            .into_with(|| format!("Screen width must be larger than {} characters", w)));
+    }
+    if height < h {
+        return Err(ErrorID::InvalidScreenSize(width, height)
+            .into_with(|| format!("Screen height must be larger than {} characters", h)));
+    }
+    Ok((width, height))
 }
 
 impl TermScreen<RawTerm> {
@@ -26,48 +44,37 @@ impl TermScreen<RawTerm> {
         let term = stdout
             .into_raw_mode()
             .into_chained(|| "[Screen::from_stdout] attempt to get raw mode terminal")?;
-        let (width, height) = terminal_size()
-            .into_chained(|| "[Screen::from_stdout] attempt to get terminal size")?;
-        let (w, h) = (w, h).map(|i| i as u16);
-        if width < w {
-            return Err(ErrorID::InvalidScreenSize(width, height)
-                .into_with(|| format!("Screen width must be larger than {} characters", w)));
-        }
-        if height < h {
-            return Err(ErrorID::InvalidScreenSize(width, height)
-                .into_with(|| format!("Screen height must be larger than {} characters", h)));
-        }
+        check_size(w, h)?;
         Ok(TermScreen {
             term,
             has_notification: false,
-            width: w,
-            height: h,
+            width: w as u16,
+            height: h as u16,
             pending_messages: VecDeque::new(),
+            debug: false,
         })
     }
+    /// change the size for a game with another config
+    pub fn resize(&mut self, w: i32, h: i32) -> GameResult<()> {
+        check_size(w, h)?;
+        self.width = w as u16;
+        self.height = h as u16;
+        Ok(())
+    }
 }
 
 impl TermScreen<Stdout> {
     /// raw terminal screen(for python API)
     pub fn from_stdout(w: i32, h: i32) -> GameResult<Self> {
         let stdout = io::stdout();
-        let (width, height) = terminal_size()
-            .into_chained(|| "[Screen::from_stdout] attempt to get terminal size")?;
-        let (w, h) = (w, h).map(|i| i as u16);
-        if width < w {
-            return Err(ErrorID::InvalidScreenSize(width, height)
-                .into_with(|| format!("Screen width must be larger than {} characters", w)));
-        }
-        if height < h {
-            return Err(ErrorID::InvalidScreenSize(width, height)
-                .into_with(|| format!("Screen height must be larger than {} characters", h)));
-        }
+        let (width, height) = check_size(w, h)?;
         Ok(TermScreen {
             term: stdout,
             has_notification: false,
             width,
             height,
             pending_messages: VecDeque::new(),
+            debug: false,
         })
     }
 }
@@ -127,6 +134,15 @@ impl<T: Write> Screen for TermScreen<T> {
         self.pending_messages.push_back(msg.as_ref().to_owned());
         Ok(())
     }
+    fn combat_detail<S: AsRef<str>>(&mut self, detail: S) -> GameResult<()> {
+        if !self.debug {
+            return Ok(());
+        }
+        if let Some(msg) = self.pending_messages.back_mut() {
+            msg.push_str(&format!(" [{}]", detail.as_ref()));
+        }
+        Ok(())
+    }
 }
 
 impl<T: Write> TermScreen<T> {
@@ -150,6 +166,14 @@ impl<T: Write> TermScreen<T> {
         .into_chained(|| "in Screen::default_config")?;
         self.flush().chain_err(|| "in Screen::default_config")
     }
+    /// show all pending messages in a line, without waiting for keys
+    pub fn display_all_msgs(&mut self) -> GameResult<()> {
+        if self.pending_messages.is_empty() {
+            return Ok(());
+        }
+        let msgs: Vec<_> = self.pending_messages.drain(..).collect();
+        self.message(msgs.join(" "))
+    }
     pub fn display_msg(&mut self) -> GameResult<bool> {
         if let Some(msg) = self.pending_messages.pop_front() {
             if self.pending_messages.is_empty() {
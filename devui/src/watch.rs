@@ -0,0 +1,307 @@
+//! spectator mode, which shows a game running somewhere else
+use error::*;
+use rogue_gym_core::dungeon::Coord;
+use rogue_gym_core::event_log::LogEntry;
+use rogue_gym_core::protocol::{HubRequest, Observation, Response, SessionId};
+use rogue_gym_core::{GameConfig, RunTime};
+use rogue_gym_uilib::{process_reaction, Screen, Transition};
+use screen::{RawTerm, TermScreen};
+use serde::de::DeserializeOwned;
+use serde_json;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::TcpStream;
+#[cfg(unix)]
+use std::os::unix::net::UnixStream;
+use std::path::PathBuf;
+use std::sync::mpsc;
+use std::thread;
+use std::time::Duration;
+use {wait_keys, ReplayInst};
+
+/// where the game comes from
+#[derive(Clone, Debug)]
+pub enum Feed {
+    /// an event log still written by the game
+    Log(PathBuf),
+    /// a session of rogue-gym-server on a localhost TCP port
+    Tcp(u16, SessionId),
+    /// a session of rogue-gym-server on a Unix domain socket
+    #[cfg(unix)]
+    Unix(PathBuf, SessionId),
+}
+
+/// reads JSON lines as they are written
+struct Lines<R> {
+    reader: R,
+    line: String,
+}
+
+impl<R: BufRead> Lines<R> {
+    fn new(reader: R) -> Self {
+        Lines {
+            reader,
+            line: String::new(),
+        }
+    }
+    /// the next value, or None if no whole line has been written yet
+    fn next<T: DeserializeOwned>(&mut self) -> GameResult<Option<T>> {
+        // a partial line is kept in `self.line` even if reading times out
+        match self.reader.read_line(&mut self.line) {
+            Ok(_) => {}
+            Err(ref e)
+                if e.kind() == io::ErrorKind::WouldBlock || e.kind() == io::ErrorKind::TimedOut => {
+            }
+            Err(e) => return Err(e).into_chained(|| "Lines::next: failed to read"),
+        }
+        if !self.line.ends_with('\n') {
+            return Ok(None);
+        }
+        let res = serde_json::from_str(&self.line);
+        self.line.clear();
+        res.map(Some)
+            .into_chained(|| "Lines::next: invalid JSON line")
+    }
+}
+
+/// show a running game until q is pressed.
+/// Games in an event log are played with the config recorded in it,
+/// and `check` is only compared with it, if given.
+pub fn watch_game(feed: Feed, check: Option<GameConfig>, interval_ms: u64) -> GameResult<()> {
+    let (tx, rx) = mpsc::channel();
+    let watch_thread = thread::spawn(move || {
+        let size = check.clone().unwrap_or_default();
+        let res = TermScreen::from_raw(size.width, size.height).and_then(|mut screen| {
+            let res = match feed {
+                Feed::Log(path) => watch_log(&mut screen, path, check, interval_ms, &rx),
+                Feed::Tcp(port, id) => TcpStream::connect(("127.0.0.1", port))
+                    .into_chained(|| "watch_game: failed to connect")
+                    .and_then(|s| watch_session(&mut screen, s, id, &rx)),
+                #[cfg(unix)]
+                Feed::Unix(path, id) => UnixStream::connect(&path)
+                    .into_chained(|| "watch_game: failed to connect")
+                    .and_then(|s| watch_session(&mut screen, s, id, &rx)),
+            };
+            screen.clear_screen()?;
+            res
+        });
+        if let Err(e) = res {
+            eprintln!("Error in viewer: {}", e);
+        }
+    });
+    wait_keys(tx)?;
+    watch_thread.join().unwrap();
+    Ok(())
+}
+
+/// check keys from the main thread, returning true if we should stop
+fn check_keys(rx: &mpsc::Receiver<ReplayInst>, paused: &mut bool) -> GameResult<bool> {
+    loop {
+        match rx.try_recv() {
+            Ok(ReplayInst::Start) => *paused = false,
+            Ok(ReplayInst::Pause) => *paused = true,
+            Ok(ReplayInst::End) => return Ok(true),
+            Err(mpsc::TryRecvError::Disconnected) => bail!("devui::watch_game disconnected!"),
+            Err(mpsc::TryRecvError::Empty) => return Ok(false),
+        }
+    }
+}
+
+fn watch_log(
+    screen: &mut TermScreen<RawTerm>,
+    path: PathBuf,
+    check: Option<GameConfig>,
+    interval_ms: u64,
+    rx: &mpsc::Receiver<ReplayInst>,
+) -> GameResult<()> {
+    let file =
+        File::open(&path).into_chained(move || format!("watch_log: failed to open {:?}", path))?;
+    let mut lines = Lines::new(BufReader::new(file));
+    let mut runtime: Option<RunTime> = None;
+    let mut paused = false;
+    screen.welcome()?;
+    while !check_keys(rx, &mut paused)? {
+        let entry = if paused { None } else { lines.next()? };
+        let entry = match entry {
+            Some(e) => e,
+            None => {
+                thread::sleep(Duration::from_millis(interval_ms));
+                continue;
+            }
+        };
+        match entry {
+            // a new episode
+            LogEntry::Start { config, .. } => {
+                if let Some(check) = check.as_ref() {
+                    check
+                        .check_same_game(&config)
+                        .chain_err(|| "watch_log: the config differs from the log")?;
+                }
+                screen.resize(config.width, config.height)?;
+                let mut rt = config.build()?;
+                screen.clear_dungeon()?;
+                screen.dungeon(&mut rt)?;
+                screen.status(&rt.player_status())?;
+                runtime = Some(rt);
+            }
+            LogEntry::Step {
+                step,
+                input,
+                status,
+                ..
+            } => {
+                let runtime = match runtime.as_mut() {
+                    Some(rt) => rt,
+                    // we'll start from the next episode
+                    None => continue,
+                };
+                let res = runtime.react_to_input(input).unwrap_or_default();
+                for reaction in res {
+                    match process_reaction(screen, runtime, reaction)? {
+                        Transition::Exit => break,
+                        Transition::None => {}
+                    }
+                }
+                screen.display_all_msgs()?;
+                if runtime.player_status() != status {
+                    let msg = format!("The game diverged from the log at step {}", step);
+                    screen.message(msg)?;
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+fn draw_observation(screen: &mut TermScreen<RawTerm>, obs: &Observation) -> GameResult<()> {
+    screen.clear_dungeon()?;
+    let rows = obs.map.len().min(screen.height().0 as usize);
+    // the first and last rows are for messages and status
+    for (y, row) in obs
+        .map
+        .iter()
+        .enumerate()
+        .take(rows.saturating_sub(1))
+        .skip(1)
+    {
+        screen.write_str(Coord::new(0, y as i32), row.trim_end())?;
+    }
+    screen.status(&obs.status)?;
+    if obs.game_over {
+        screen.message("--Game Over--")
+    } else if !obs.messages.is_empty() {
+        screen.message(obs.messages.join(" "))
+    } else {
+        Ok(())
+    }
+}
+
+fn watch_session<S>(
+    screen: &mut TermScreen<RawTerm>,
+    stream: S,
+    id: SessionId,
+    rx: &mpsc::Receiver<ReplayInst>,
+) -> GameResult<()>
+where
+    S: io::Read + Write + Timeout,
+{
+    let mut stream = stream;
+    let req = serde_json::to_string(&HubRequest::Watch { id })
+        .into_chained(|| "watch_session: failed to serialize")?;
+    writeln!(stream, "{}", req).into_chained(|| "watch_session: failed to send")?;
+    // not to block checking keys
+    stream.set_timeout(Duration::from_millis(100))?;
+    let mut lines = Lines::new(BufReader::new(stream));
+    let mut paused = false;
+    let mut closed = false;
+    while !check_keys(rx, &mut paused)? {
+        if paused || closed {
+            thread::sleep(Duration::from_millis(100));
+            continue;
+        }
+        match lines.next()? {
+            Some(Response::Observation(obs)) => draw_observation(screen, &obs)?,
+            Some(Response::Closed(_)) => {
+                screen.message(format!("Session {} is closed. Press q to exit", id))?;
+                closed = true;
+            }
+            Some(Response::Error(e)) => bail!("rogue-gym-server: {}", e),
+            Some(res) => bail!("watch_session: unexpected response {:?}", res),
+            None => {}
+        }
+    }
+    Ok(())
+}
+
+/// streams with read timeouts
+trait Timeout {
+    fn set_timeout(&self, dur: Duration) -> GameResult<()>;
+}
+
+impl Timeout for TcpStream {
+    fn set_timeout(&self, dur: Duration) -> GameResult<()> {
+        self.set_read_timeout(Some(dur))
+            .into_chained(|| "failed to set timeout")
+    }
+}
+
+#[cfg(unix)]
+impl Timeout for UnixStream {
+    fn set_timeout(&self, dur: Duration) -> GameResult<()> {
+        self.set_read_timeout(Some(dur))
+            .into_chained(|| "failed to set timeout")
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::collections::VecDeque;
+    /// a reader returning chunks one by one, where `Err` is returned as it is
+    struct Chunks(VecDeque<io::Result<&'static [u8]>>);
+    impl io::Read for Chunks {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            match self.0.pop_front() {
+                Some(Ok(chunk)) => {
+                    buf[..chunk.len()].copy_from_slice(chunk);
+                    Ok(chunk.len())
+                }
+                Some(Err(e)) => Err(e),
+                None => Ok(0),
+            }
+        }
+    }
+    fn lines(chunks: Vec<io::Result<&'static [u8]>>) -> Lines<BufReader<Chunks>> {
+        Lines::new(BufReader::new(Chunks(chunks.into_iter().collect())))
+    }
+    fn error(kind: io::ErrorKind) -> io::Result<&'static [u8]> {
+        Err(io::Error::new(kind, "test"))
+    }
+    #[test]
+    fn partial_lines() {
+        let mut lines = lines(vec![
+            Ok(b"[1, "),
+            error(io::ErrorKind::WouldBlock),
+            Ok(b"2"),
+            error(io::ErrorKind::TimedOut),
+            Ok(b"]\n[3]\n"),
+        ]);
+        // the partial line is kept while reading times out
+        assert_eq!(lines.next::<Vec<u32>>().unwrap(), None);
+        assert_eq!(lines.next::<Vec<u32>>().unwrap(), None);
+        assert_eq!(lines.next::<Vec<u32>>().unwrap(), Some(vec![1, 2]));
+        assert_eq!(lines.next::<Vec<u32>>().unwrap(), Some(vec![3]));
+        // nothing is written yet
+        assert_eq!(lines.next::<Vec<u32>>().unwrap(), None);
+    }
+    #[test]
+    fn errors() {
+        let mut lines = self::lines(vec![Ok(b"[1]\n"), Ok(b"oops\n"), Ok(b"[2]\n")]);
+        assert_eq!(lines.next::<Vec<u32>>().unwrap(), Some(vec![1]));
+        assert!(lines.next::<Vec<u32>>().is_err());
+        // an invalid line is skipped
+        assert_eq!(lines.next::<Vec<u32>>().unwrap(), Some(vec![2]));
+        let mut lines = self::lines(vec![error(io::ErrorKind::BrokenPipe)]);
+        assert!(lines.next::<Vec<u32>>().is_err());
+    }
+}
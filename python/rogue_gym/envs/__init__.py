@@ -1,3 +1,3 @@
 from .parallel import ParallelRogueEnv
-from .rogue_env import DungeonType, ImageSetting, PlayerState, RogueEnv, StatusFlag
+from .rogue_env import DungeonType, ImageSetting, PlayerState, Repeat, RogueEnv, StatusFlag
 from .wrappers import FirstFloorEnv, StairRewardEnv, StairRewardParallel
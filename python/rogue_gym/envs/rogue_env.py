@@ -3,6 +3,7 @@ from enum import Enum, Flag
 import gym
 from gym import spaces
 import json
+import os
 import numpy as np
 from numpy import ndarray
 from typing import Dict, List, NamedTuple, Optional, Tuple, Union
@@ -10,6 +11,18 @@ from rogue_gym_python import _rogue_gym as rogue_gym_inner
 from rogue_gym_python._rogue_gym import GameState, PlayerState
 
 
+def _resolve_map_files(config: str, config_dir: str) -> str:
+    """Makes the paths of map files in the config relative to the config file"""
+    config_dict = json.loads(config)
+    dungeon = config_dict.get('dungeon', {})
+    if dungeon.get('style') != 'custom':
+        return config
+    for source in dungeon.get('maps', []):
+        if isinstance(source, dict) and 'file' in source:
+            source['file'] = os.path.join(config_dir, source['file'])
+    return json.dumps(config_dict)
+
+
 class StatusFlag(Flag):
     EMPTY         = 0b000_000_000
     DUNGEON_LEVEL = 0b000_000_001
@@ -94,6 +107,14 @@ class ImageSetting(NamedTuple):
                 return self.status.gray_image(state)
 
 
+class Repeat(NamedTuple):
+    """Repeats an action n times, like typing '20s' in rogue.
+    Repetition stops when something interesting happens.
+    """
+    n: int
+    action: Union[int, str]
+
+
 class RogueEnv(gym.Env):
     metadata = {'render.modes': ['human', 'ascii']}
 
@@ -123,6 +144,9 @@ class RogueEnv(gym.Env):
         'y': 'MOVE_LEFTUP',
         '>': 'DOWNSTAIR',
         's': 'SEARCH',
+        'x': 'EXPLORE',
+        'w': 'WIELD',
+        'W': 'WEAR',
     }
 
     ACTIONS = [
@@ -143,7 +167,7 @@ class RogueEnv(gym.Env):
         super().__init__()
         if config_path:
             with open(config_path, 'r') as f:
-                config = f.read()
+                config = _resolve_map_files(f.read(), os.path.dirname(config_path))
         else:
             config_dict.update(kwargs)
             config = json.dumps(config_dict)
@@ -170,6 +194,10 @@ class RogueEnv(gym.Env):
     def get_dungeon(self) -> List[str]:
         return self.result.dungeon
 
+    def get_messages(self, n: Optional[int] = None) -> List[str]:
+        """Returns last n messages(or all messages if n is None)"""
+        return self.game.messages(n)
+
     def get_config(self) -> dict:
         config = self.game.dump_config()
         return json.loads(config)
@@ -179,9 +207,25 @@ class RogueEnv(gym.Env):
             f.write(self.game.dump_config())
 
     def save_actions(self, fname: str) -> None:
+        """Saves the replay of the current episode, with config and seed"""
         with open(fname, 'w') as f:
             f.write(self.game.dump_history())
 
+    def load_actions(self, fname: str) -> PlayerState:
+        """Restarts the game from a replay saved by save_actions.
+        Raises RuntimeError if the replay was recorded with another config or diverges.
+        """
+        with open(fname, 'r') as f:
+            self.game.load_history(f.read())
+        self.__cache()
+        return self.result
+
+    def start_event_log(self, fname: str) -> None:
+        """Writes JSONL event log of the current episode to fname, until reset is called.
+        Use rogue_gym.event_log.load_event_log to read it.
+        """
+        self.game.start_event_log(fname)
+
     def replay(self, interval_ms: int = 100) -> None:
         if not hasattr(rogue_gym_inner, 'replay'):
             raise RuntimeError('Currently replay is only supported on UNIX')
@@ -210,14 +254,24 @@ class RogueEnv(gym.Env):
             self.game.react(ord(act))
         return len(actions)
 
-    def step(self, action: Union[int, str]) -> Tuple[PlayerState, float, bool, dict]:
+    def step(
+            self,
+            action: Union[int, str, Repeat],
+    ) -> Tuple[PlayerState, float, bool, dict]:
         """
         Do action.
         @param actions(string):
              key board inputs to rogue(e.g. "hjk" or "hh>")
         """
         gold_before = self.result.gold
-        if isinstance(action, str):
+        if isinstance(action, Repeat):
+            key = action.action
+            if not isinstance(key, str):
+                key = self.ACTIONS[key]
+            if action.n < 1 or len(key) != 1:
+                raise ValueError("Invalid action: {}".format(action))
+            self.__step_str(str(action.n) + key)
+        elif isinstance(action, str):
             self.__step_str(action)
         else:
             try:
@@ -0,0 +1,37 @@
+"""Loader of JSONL event logs written by RogueEnv.start_event_log"""
+import json
+from typing import List, NamedTuple
+
+
+class EventLog(NamedTuple):
+    """An episode in the event log.
+    Each step is a dict with 'step', 'turn', 'input', 'reactions', 'status' and 'events'.
+    """
+    seed: int
+    config: dict
+    initial_status: dict
+    steps: List[dict]
+
+    def events(self, kind: str) -> List[dict]:
+        """Returns all events of the kind(e.g. 'killed' or 'damage_taken')"""
+        return [ev for step in self.steps for ev in step['events'] if ev['event'] == kind]
+
+
+def load_event_log(fname: str) -> EventLog:
+    seed, config, initial_status, steps = None, {}, {}, []
+    with open(fname, 'r') as f:
+        for line in f:
+            if not line.strip():
+                continue
+            entry = json.loads(line)
+            if 'start' in entry:
+                seed = entry['start']['seed']
+                config = entry['start']['config']
+                initial_status = entry['start']['status']
+            elif 'step' in entry:
+                steps.append(entry['step'])
+            else:
+                raise ValueError('Invalid event log entry: {}'.format(line))
+    if seed is None:
+        raise ValueError('{} has no start entry'.format(fname))
+    return EventLog(seed, config, initial_status, steps)
@@ -30,7 +30,7 @@ impl MessageFlagInner {
             GameMsg::HitFrom(_) => add(Self::HIT_FROM),
             GameMsg::MissTo(_) => add(Self::MISS_TO),
             GameMsg::MissFrom(_) => add(Self::MISS_FROM),
-            GameMsg::Killed(_) => add(Self::KILLED),
+            GameMsg::Killed { .. } => add(Self::KILLED),
             GameMsg::SecretDoor => add(Self::SECRET_DOOR),
             GameMsg::NoDownStair => add(Self::NO_DOWNSTAIR),
             _ => (),
@@ -38,6 +38,35 @@ impl MessageFlagInner {
     }
 }
 
+/// damages and exp in a step, for reward shaping
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub(crate) struct CombatInner {
+    pub damage_dealt: i64,
+    pub damage_taken: i64,
+    pub kill_exp: u32,
+}
+
+impl CombatInner {
+    pub fn reset(&mut self) {
+        *self = Self::default();
+    }
+    pub fn append(&mut self, msg: &GameMsg) {
+        match msg {
+            GameMsg::HitTo(hit) => self.damage_dealt += hit.damage.0,
+            GameMsg::HitFrom(hit) => self.damage_taken += hit.damage.0,
+            GameMsg::Killed { exp, .. } => self.kill_exp += exp.0,
+            _ => (),
+        }
+    }
+    pub fn to_dict_vec(&self) -> Vec<(&'static str, i64)> {
+        vec![
+            ("damage_dealt", self.damage_dealt),
+            ("damage_taken", self.damage_taken),
+            ("kill_exp", i64::from(self.kill_exp)),
+        ]
+    }
+}
+
 #[derive(Copy, Clone, Debug)]
 pub(crate) struct StatusFlagInner(pub u32);
 
@@ -12,7 +12,7 @@ mod fearures;
 mod state_impls;
 mod thread_impls;
 
-use fearures::{MessageFlagInner, StatusFlagInner};
+use fearures::{CombatInner, MessageFlagInner, StatusFlagInner};
 use ndarray::{Array2, Axis, Zip};
 use numpy::PyArray3;
 use pyo3::{
@@ -23,6 +23,8 @@ use pyo3::{
 use rect_iter::{Get2D, GetMut2D, RectRange};
 use rogue_gym_core::character::player::Status;
 use rogue_gym_core::dungeon::{Positioned, X, Y};
+use rogue_gym_core::event_log::EventLogger;
+use rogue_gym_core::replay::Replay;
 use rogue_gym_core::{error::*, symbol, GameConfig, RunTime};
 use state_impls::GameStateImpl;
 use std::collections::HashMap;
@@ -47,6 +49,7 @@ struct PlayerState {
     status: Status,
     symbols: u8,
     message: MessageFlagInner,
+    combat: CombatInner,
     is_terminal: bool,
 }
 
@@ -59,6 +62,7 @@ impl PlayerState {
             status: Status::default(),
             symbols,
             message: MessageFlagInner::new(),
+            combat: CombatInner::default(),
             is_terminal: false,
         }
     }
@@ -66,6 +70,7 @@ impl PlayerState {
         self.status = runtime.player_status();
         self.draw_map(runtime)?;
         self.message = MessageFlagInner::new();
+        self.combat.reset();
         self.is_terminal = false;
         Ok(())
     }
@@ -160,6 +165,16 @@ impl PlayerState {
             .map(|(s, v)| (s.to_owned(), v))
             .collect())
     }
+    /// damages dealt and taken, and exp got by killing enemies in the last step
+    #[getter]
+    fn combat(&self) -> PyResult<HashMap<String, i64>> {
+        Ok(self
+            .combat
+            .to_dict_vec()
+            .into_iter()
+            .map(|(s, v)| (s.to_owned(), v))
+            .collect())
+    }
     #[getter]
     fn dungeon(&self) -> PyResult<Vec<String>> {
         Ok(self.dungeon_str().map(|s| s.to_string()).collect())
@@ -271,13 +286,19 @@ impl GameState {
     fn react(&mut self, input: u8) -> PyResult<()> {
         pyresult(self.inner.react(input))
     }
-    /// Returns action history as Json
+    /// Returns action history as Json replay, with config and seed
     fn dump_history(&self) -> PyResult<String> {
         pyresult_with(
-            self.inner.runtime.saved_inputs_as_json(),
+            Replay::from_runtime(&self.inner.runtime).and_then(|r| r.to_json()),
             "Error when getting history",
         )
     }
+    /// Restarts the game from Json replay, failing if it doesn't match the config
+    fn load_history(&mut self, json: String) -> PyResult<()> {
+        let replay = pyresult_with(Replay::from_json(&json), "Failed to load replay")?;
+        pyresult(replay.check_config(&self.config))?;
+        pyresult(self.inner.load_replay(&replay))
+    }
     /// Returns config as Json
     fn dump_config(&self) -> PyResult<String> {
         pyresult_with(self.config.to_json(), "Error when getting config")
@@ -285,6 +306,20 @@ impl GameState {
     fn symbols(&self) -> PyResult<usize> {
         Ok(self.inner.symbols())
     }
+    /// Writes JSONL event log of the current episode to the file
+    fn start_event_log(&mut self, path: String) -> PyResult<()> {
+        let runtime = &mut self.inner.runtime;
+        let logger = pyresult(EventLogger::create(runtime, &path))?;
+        runtime.add_observer(Box::new(logger));
+        Ok(())
+    }
+    /// Returns last n messages(or all messages if n is None)
+    fn messages(&self, n: Option<usize>) -> Vec<String> {
+        let log = self.inner.runtime.messages();
+        log.last(n.unwrap_or_else(|| log.len()))
+            .map(|msg| msg.text.clone())
+            .collect()
+    }
 }
 
 #[pyclass]
@@ -374,9 +409,8 @@ impl ParallelGameState {
 #[pyfunction]
 fn replay(game: &GameState, py: Python, interval_ms: u64) -> PyResult<()> {
     use rogue_gym_devui::show_replay;
-    let inputs = game.inner.runtime.saved_inputs().to_vec();
-    let config = game.config.clone();
-    let res = py.allow_threads(move || show_replay(config, inputs, interval_ms));
+    let replay = pyresult(Replay::from_runtime(&game.inner.runtime))?;
+    let res = py.allow_threads(move || show_replay(replay, interval_ms));
     pyresult(res)
 }
 
@@ -384,7 +418,7 @@ fn replay(game: &GameState, py: Python, interval_ms: u64) -> PyResult<()> {
 #[pyfunction]
 fn play_cli(game: &GameState) -> PyResult<()> {
     use rogue_gym_devui::play_game;
-    pyresult(play_game(game.config.clone(), false))?;
+    pyresult(play_game(game.config.clone(), false, false))?;
     Ok(())
 }
 
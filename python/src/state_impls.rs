@@ -1,6 +1,7 @@
 use rogue_gym_core::{
     error::GameResult,
     input::{Key, KeyMap},
+    replay::Replay,
     ui::{MordalKind, UiState},
     GameConfig, Reaction, RunTime,
 };
@@ -41,6 +42,18 @@ impl GameStateImpl {
         self.steps = 0;
         Ok(())
     }
+    /// restart the game and replay the inputs, failing if it diverges
+    pub(crate) fn load_replay(&mut self, replay: &Replay) -> GameResult<()> {
+        self.reset(replay.config.clone())?;
+        for (i, &input) in replay.inputs.iter().enumerate() {
+            // ignored inputs are recorded, too
+            let _ = self.runtime.react_to_input(input);
+            replay.check_step(i + 1, &self.runtime)?;
+        }
+        self.state.reset(&self.runtime)?;
+        self.steps = replay.inputs.len();
+        Ok(())
+    }
     pub(crate) fn state(&self) -> PlayerState {
         self.state.clone()
     }
@@ -52,7 +65,12 @@ impl GameStateImpl {
             return Ok(());
         }
         let res = self.runtime.react_to_key(Key::Char(input as char))?;
+        // count prefix doesn't consume a step
+        if self.runtime.is_waiting_input() {
+            return Ok(());
+        }
         self.state.message.reset();
+        self.state.combat.reset();
         let mut dead = false;
         for reaction in res {
             match reaction {
@@ -64,12 +82,17 @@ impl GameStateImpl {
                 }
                 Reaction::UiTransition(ui) => match ui {
                     UiState::Mordal(MordalKind::Grave(_)) => dead = true,
+                    // item prompts are answered by the next key
+                    UiState::Mordal(MordalKind::SelectItem(_)) | UiState::Dungeon => {}
                     _ => bail!(
                         "[rogue_gym_python::GameStateImpl] Invalid ui transition {:?}",
                         ui
                     ),
                 },
-                Reaction::Notify(msg) => self.state.message.append(&msg),
+                Reaction::Notify(msg) => {
+                    self.state.message.append(&msg);
+                    self.state.combat.append(&msg);
+                }
             }
         }
         self.steps += 1;
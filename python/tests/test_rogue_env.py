@@ -1,9 +1,12 @@
 """test for RogueEnv """
 from data import CMD_STR, SEED1_DUNGEON, SEED1_DUNGEON2
 import gym
+import json
+import pytest
 from gym import spaces
 import numpy as np
-from rogue_gym.envs import StatusFlag, RogueEnv
+from rogue_gym.envs import Repeat, StatusFlag, RogueEnv
+from rogue_gym.event_log import load_event_log
 
 CONFIG_NOENEM = {
     "seed": 1,
@@ -41,6 +44,67 @@ def test_max_steps():
     assert done
 
 
+def test_repeat():
+    env1 = RogueEnv(config_dict=CONFIG_NOENEM)
+    env2 = RogueEnv(config_dict=CONFIG_NOENEM)
+    res1, *_ = env1.step(Repeat(5, 's'))
+    res2, *_ = env2.step('sssss')
+    assert res1.dungeon == res2.dungeon
+    assert res1.status == res2.status
+
+
+def test_item_prompt():
+    env = RogueEnv(config_dict=CONFIG_NOENEM)
+    env.step('w')
+    assert env.get_messages(1) == ['Which object do you want to wield?']
+    env.step('e')
+    assert env.get_messages(1) == ['You are now wielding bow']
+    env.step('Wz')
+    assert env.get_messages() == [
+        'Which object do you want to wield?',
+        'You are now wielding bow',
+        'Which object do you want to wear?',
+        "'z' is not a valid item",
+    ]
+
+
+def test_combat():
+    env = RogueEnv(config_dict=CONFIG_NOENEM)
+    res, *_ = env.step('.')
+    assert res.combat == {'damage_dealt': 0, 'damage_taken': 0, 'kill_exp': 0}
+
+
+def test_event_log(tmpdir):
+    env = RogueEnv(config_dict=CONFIG_NOENEM)
+    fname = str(tmpdir.join('events.jsonl'))
+    env.start_event_log(fname)
+    state, *_ = env.step(CMD_STR)
+    env.reset()
+    log = load_event_log(fname)
+    assert log.seed == 1
+    assert log.config['seed'] == 1
+    assert len(log.steps) == len(CMD_STR)
+    assert log.steps[-1]['status']['gold'] == state.gold
+    assert [s['step'] for s in log.steps] == list(range(1, len(CMD_STR) + 1))
+    assert log.events('killed') == []
+
+
+def test_replay(tmpdir):
+    env = RogueEnv(seed=1)
+    state, *_ = env.step(CMD_STR)
+    fname = str(tmpdir.join('replay.json'))
+    env.save_actions(fname)
+    with open(fname) as f:
+        replay = json.load(f)
+    assert replay['seed'] == 1
+    assert len(replay['inputs']) == len(replay['hashes']) == len(CMD_STR)
+    env2 = RogueEnv(seed=1)
+    assert env2.load_actions(fname).dungeon == state.dungeon
+    env3 = RogueEnv(seed=2)
+    with pytest.raises(RuntimeError):
+        env3.load_actions(fname)
+
+
 def test_kwargs_setting():
     env = RogueEnv(seed=1, width=48, height=24)
     assert env.screen_size() == (24, 48)
@@ -0,0 +1,240 @@
+//! many games shared by many clients
+use crate::protocol::{HubRequest, Observation, Request, Response, SessionId};
+use crate::Server;
+use failure::format_err;
+use rogue_gym_core::error::GameResult;
+use rogue_gym_core::GameConfig;
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Mutex, MutexGuard};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+enum Job {
+    Request(Box<Request>, Sender<Response>),
+    Watch(Sender<Observation>, Sender<Response>),
+}
+
+/// a thread running a game, since `RunTime` can't be sent to other threads
+struct Worker {
+    server: Server,
+    watchers: Vec<Sender<Observation>>,
+}
+
+impl Worker {
+    fn spawn(config: GameConfig) -> GameResult<(Sender<Job>, JoinHandle<()>)> {
+        let (tx, rx) = mpsc::channel();
+        let (res_tx, res_rx) = mpsc::channel();
+        let handle = thread::spawn(move || {
+            let mut worker = Worker {
+                server: Server::new(),
+                watchers: vec![],
+            };
+            let res = worker.server.handle(Request::New { config });
+            let failed = matches!(res, Response::Error(_));
+            let _ = res_tx.send(res);
+            if !failed {
+                // runs until the session is closed
+                rx.into_iter().for_each(|job| worker.run(job));
+            }
+        });
+        match res_rx.recv() {
+            Ok(Response::Error(e)) => Err(format_err!("failed to start a game: {}", e)),
+            Ok(_) => Ok((tx, handle)),
+            Err(_) => Err(format_err!("failed to start a game: the session panicked")),
+        }
+    }
+    fn run(&mut self, job: Job) {
+        match job {
+            Job::Request(request, tx) => {
+                let advanced = match *request {
+                    Request::Observe | Request::Save => false,
+                    Request::New { .. } | Request::Step { .. } | Request::Restore(_) => true,
+                };
+                let res = self.server.handle(*request);
+                if let (true, Response::Observation(obs)) = (advanced, &res) {
+                    self.watchers.retain(|tx| tx.send(obs.clone()).is_ok());
+                }
+                let _ = tx.send(res);
+            }
+            Job::Watch(watcher, tx) => {
+                let res = self.server.handle(Request::Observe);
+                if let Response::Observation(_) = res {
+                    self.watchers.push(watcher);
+                }
+                let _ = tx.send(res);
+            }
+        }
+    }
+}
+
+struct Entry {
+    jobs: Sender<Job>,
+    handle: JoinHandle<()>,
+    last_used: Instant,
+}
+
+/// sessions with ids, each of which runs in its own thread
+pub struct Hub {
+    sessions: Mutex<BTreeMap<SessionId, Entry>>,
+    next_id: AtomicU64,
+    timeout: Duration,
+    shutdown: AtomicBool,
+}
+
+impl Hub {
+    /// sessions not used for `timeout` are closed by `remove_expired`
+    pub fn new(timeout: Duration) -> Self {
+        Hub {
+            sessions: Mutex::new(BTreeMap::new()),
+            next_id: AtomicU64::new(1),
+            timeout,
+            shutdown: AtomicBool::new(false),
+        }
+    }
+    fn sessions(&self) -> MutexGuard<'_, BTreeMap<SessionId, Entry>> {
+        // the map is always consistent, even if some thread panicked
+        self.sessions.lock().unwrap_or_else(|e| e.into_inner())
+    }
+    /// send a job to the session and wait for the response
+    fn send(
+        &self,
+        id: SessionId,
+        job: impl FnOnce(Sender<Response>) -> Job,
+    ) -> GameResult<Response> {
+        let jobs = match self.sessions().get_mut(&id) {
+            Some(entry) => {
+                entry.last_used = Instant::now();
+                entry.jobs.clone()
+            }
+            None => return Err(format_err!("no session {}", id)),
+        };
+        let (tx, rx) = mpsc::channel();
+        jobs.send(job(tx))
+            .map_err(|_| format_err!("session {} is closed", id))?;
+        rx.recv()
+            .map_err(|_| format_err!("session {} is closed", id))
+    }
+    pub fn is_shutdown(&self) -> bool {
+        self.shutdown.load(Ordering::SeqCst)
+    }
+    /// handle a request except `Watch`, which needs a connection
+    pub fn handle(&self, request: HubRequest) -> Response {
+        match self.handle_(request) {
+            Ok(res) => res,
+            Err(e) => crate::error_response(&e),
+        }
+    }
+    fn handle_(&self, request: HubRequest) -> GameResult<Response> {
+        if self.is_shutdown() {
+            return Err(format_err!("the server is shutting down"));
+        }
+        match request {
+            HubRequest::Open { config } => self.open(config).map(Response::Opened),
+            HubRequest::Session { id, request } => {
+                self.send(id, |tx| Job::Request(Box::new(request), tx))
+            }
+            HubRequest::Close { id } => match self.sessions().remove(&id) {
+                Some(_) => Ok(Response::Closed(id)),
+                None => Err(format_err!("no session {}", id)),
+            },
+            HubRequest::List => Ok(Response::Sessions(
+                self.sessions().keys().cloned().collect(),
+            )),
+            HubRequest::Watch { .. } => Err(format_err!("watch is only available via connections")),
+            HubRequest::Shutdown => {
+                self.shutdown();
+                Ok(Response::Bye)
+            }
+        }
+    }
+    pub fn open(&self, config: GameConfig) -> GameResult<SessionId> {
+        if self.is_shutdown() {
+            return Err(format_err!("the server is shutting down"));
+        }
+        let (jobs, handle) = Worker::spawn(config)?;
+        let id = self.next_id.fetch_add(1, Ordering::SeqCst);
+        let entry = Entry {
+            jobs,
+            handle,
+            last_used: Instant::now(),
+        };
+        self.sessions().insert(id, entry);
+        Ok(id)
+    }
+    /// the current observation and a receiver of later ones, which ends when the session is closed
+    pub fn watch(&self, id: SessionId) -> GameResult<(Observation, Receiver<Observation>)> {
+        let (watcher, rx) = mpsc::channel();
+        match self.send(id, |tx| Job::Watch(watcher, tx))? {
+            Response::Observation(obs) => Ok((obs, rx)),
+            res => Err(format_err!("failed to observe session {}: {:?}", id, res)),
+        }
+    }
+    /// close sessions not used for the timeout and return their ids
+    pub fn remove_expired(&self) -> Vec<SessionId> {
+        let mut sessions = self.sessions();
+        let expired: Vec<_> = sessions
+            .iter()
+            .filter(|(_, entry)| entry.last_used.elapsed() >= self.timeout)
+            .map(|(&id, _)| id)
+            .collect();
+        for id in &expired {
+            sessions.remove(id);
+        }
+        expired
+    }
+    /// close all sessions, wait for their threads and refuse new requests
+    pub fn shutdown(&self) {
+        self.shutdown.store(true, Ordering::SeqCst);
+        let sessions = std::mem::take(&mut *self.sessions());
+        for entry in sessions.into_values() {
+            drop(entry.jobs);
+            let _ = entry.handle.join();
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::protocol::KeyArg;
+    #[test]
+    fn sessions() {
+        let hub = Hub::new(Duration::from_secs(3600));
+        let a = hub.open(GameConfig::default()).unwrap();
+        let b = hub.open(GameConfig::default()).unwrap();
+        assert_eq!(hub.handle(HubRequest::List), Response::Sessions(vec![a, b]));
+        let (first, rx) = hub.watch(a).unwrap();
+        assert_eq!(first.steps, 0);
+        let step = Request::Step {
+            key: Some(KeyArg::Char('s')),
+            input: None,
+        };
+        let res = hub.handle(HubRequest::Session {
+            id: a,
+            request: step,
+        });
+        assert_eq!(res, Response::Observation(rx.recv().unwrap()));
+        hub.handle(HubRequest::Session {
+            id: a,
+            request: Request::Observe,
+        });
+        assert!(rx.try_recv().is_err());
+        assert_eq!(hub.handle(HubRequest::Close { id: a }), Response::Closed(a));
+        assert!(rx.recv().is_err());
+        let res = hub.handle(HubRequest::Session {
+            id: a,
+            request: Request::Observe,
+        });
+        assert!(matches!(res, Response::Error(_)));
+        assert!(hub.remove_expired().is_empty());
+        let hub = Hub::new(Duration::from_secs(0));
+        let c = hub.open(GameConfig::default()).unwrap();
+        assert_eq!(hub.remove_expired(), vec![c]);
+        hub.open(GameConfig::default()).unwrap();
+        assert_eq!(hub.handle(HubRequest::Shutdown), Response::Bye);
+        assert!(hub.is_shutdown());
+        assert!(hub.open(GameConfig::default()).is_err());
+    }
+}
@@ -0,0 +1,170 @@
+//! headless game server speaking line-delimited JSON
+pub mod hub;
+pub mod net;
+pub mod session;
+
+use failure::format_err;
+use protocol::{Request, Response};
+use rogue_gym_core::error::{GameResult, ResultExt2};
+pub use rogue_gym_core::protocol;
+use session::Session;
+use std::io::{BufRead, Write};
+
+/// a server for a single game
+#[derive(Default)]
+pub struct Server {
+    session: Option<Session>,
+}
+
+impl Server {
+    pub fn new() -> Self {
+        Server::default()
+    }
+    pub fn handle(&mut self, request: Request) -> Response {
+        match self.handle_(request) {
+            Ok(res) => res,
+            Err(e) => error_response(&e),
+        }
+    }
+    fn handle_(&mut self, request: Request) -> GameResult<Response> {
+        if let Request::New { config } = request {
+            self.session = Some(Session::new(config)?);
+            return self.observe();
+        }
+        if let Request::Restore(replay) = request {
+            self.session = Some(Session::restore(&replay)?);
+            return self.observe();
+        }
+        let session = self.session_mut()?;
+        match request {
+            Request::Step { key, input } => {
+                match (key, input) {
+                    (Some(key), None) => session.step_key(key)?,
+                    (None, Some(input)) => session.step_input(input)?,
+                    _ => return Err(format_err!("step needs one of key or input")),
+                }
+                self.observe()
+            }
+            Request::Observe => self.observe(),
+            Request::Save => Ok(Response::Replay(Box::new(session.save()?))),
+            Request::New { .. } | Request::Restore(_) => unreachable!(),
+        }
+    }
+    fn session_mut(&mut self) -> GameResult<&mut Session> {
+        self.session
+            .as_mut()
+            .ok_or_else(|| format_err!("no game is running, send new or restore first"))
+    }
+    fn observe(&mut self) -> GameResult<Response> {
+        let obs = self.session_mut()?.observation()?;
+        Ok(Response::Observation(obs))
+    }
+    /// handle a request in JSON
+    pub fn handle_line(&mut self, line: &str) -> Response {
+        match serde_json::from_str(line) {
+            Ok(req) => self.handle(req),
+            Err(e) => Response::Error(format!("invalid request: {}", e)),
+        }
+    }
+}
+
+/// an error response with all causes of `err`
+pub fn error_response(err: &failure::Error) -> Response {
+    let causes: Vec<_> = err.iter_chain().map(|e| e.to_string()).collect();
+    Response::Error(causes.join(": "))
+}
+
+/// write a response as a line
+pub fn write_response(mut writer: impl Write, res: &Response) -> GameResult<()> {
+    serde_json::to_writer(&mut writer, res).into_chained(|| "write_response")?;
+    writeln!(writer).into_chained(|| "write_response")?;
+    writer.flush().into_chained(|| "write_response")
+}
+
+/// answer requests from `reader` until EOF
+pub fn serve(reader: impl BufRead, mut writer: impl Write) -> GameResult<()> {
+    let mut server = Server::new();
+    for line in reader.lines() {
+        let line = line.into_chained(|| "serve: failed to read a request")?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let res = server.handle_line(&line);
+        write_response(&mut writer, &res)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use protocol::Observation;
+    fn observation(res: Response) -> Observation {
+        match res {
+            Response::Observation(obs) => obs,
+            res => panic!("unexpected response {:?}", res),
+        }
+    }
+    #[test]
+    fn lines() {
+        let requests = [
+            r#""observe""#,
+            r#"{"new": {"config": {"seed": 3}}}"#,
+            r#"{"step": {"key": "j"}}"#,
+            r#"{"step": {"input": {"Act": {"Move": "Left"}}}}"#,
+            r#"{"step": {"key": "Esc"}}"#,
+            r#""observe""#,
+            r#""save""#,
+        ];
+        let input = requests.join("\n");
+        let mut out = vec![];
+        serve(input.as_bytes(), &mut out).unwrap();
+        let responses: Vec<Response> = String::from_utf8(out)
+            .unwrap()
+            .lines()
+            .map(|l| serde_json::from_str(l).unwrap())
+            .collect();
+        assert_eq!(responses.len(), requests.len());
+        assert!(matches!(responses[0], Response::Error(_)));
+        let obs = observation(responses[1].clone());
+        assert_eq!(obs.steps, 0);
+        assert_eq!(obs.status.dungeon_level, 1);
+        assert!(obs.map.iter().any(|row| row.contains('@')));
+        let last = observation(responses[5].clone());
+        let replay = match &responses[6] {
+            Response::Replay(r) => (**r).clone(),
+            res => panic!("unexpected response {:?}", res),
+        };
+        assert_eq!(replay.seed, 3);
+        assert_eq!(replay.inputs.len(), last.steps);
+        let mut server = Server::new();
+        let restored = observation(server.handle(Request::Restore(replay)));
+        assert_eq!(restored.map, last.map);
+        assert_eq!(restored.status, last.status);
+        let res = server.handle(Request::Step {
+            key: None,
+            input: None,
+        });
+        assert!(matches!(res, Response::Error(_)));
+    }
+    #[test]
+    fn save_without_replay() {
+        use rogue_gym_core::character::Action;
+        use rogue_gym_core::input::InputCode;
+        let recorded = |session: &Session| {
+            let runtime = session.runtime();
+            runtime.state_hashes().len() == runtime.saved_inputs().len()
+        };
+        let config = serde_json::from_str(r#"{"seed": 3}"#).unwrap();
+        let mut session = Session::new(config).unwrap();
+        for _ in 0..3 {
+            session.step_input(InputCode::Act(Action::Search)).unwrap();
+        }
+        assert!(recorded(&session));
+        let mut restored = Session::restore(&session.save().unwrap()).unwrap();
+        session.step_input(InputCode::Act(Action::Search)).unwrap();
+        restored.step_input(InputCode::Act(Action::Search)).unwrap();
+        assert!(recorded(&restored));
+        assert_eq!(restored.save().unwrap(), session.save().unwrap());
+    }
+}
@@ -0,0 +1,80 @@
+use clap::ArgMatches;
+use rogue_gym_core::error::{GameResult, ResultExt2};
+use rogue_gym_server::net::{Address, HubServer};
+use std::io;
+use std::time::Duration;
+
+const DEFAULT_TIMEOUT_SECS: u64 = 600;
+
+fn main() {
+    if let Err(err) = main_() {
+        eprintln!("Oops! Error occured in rogue-gym-server:");
+        let errs: Vec<_> = err.iter_chain().map(|e| format!("  {}", e)).collect();
+        for e in errs.into_iter().rev() {
+            eprintln!("{}", e);
+        }
+        ::std::process::exit(1);
+    }
+}
+
+fn main_() -> GameResult<()> {
+    let args = parse_args();
+    let address = match (args.value_of("tcp"), unix_path(&args)) {
+        (Some(port), _) => Address::Tcp(port.parse().into_chained(|| "Failed to parse port!")?),
+        (None, Some(address)) => address,
+        (None, None) => {
+            // a single game over stdin/stdout
+            let stdin = io::stdin();
+            let stdout = io::stdout();
+            return rogue_gym_server::serve(stdin.lock(), stdout.lock());
+        }
+    };
+    let timeout = match args.value_of("timeout") {
+        Some(t) => t.parse().into_chained(|| "Failed to parse timeout!")?,
+        None => DEFAULT_TIMEOUT_SECS,
+    };
+    let server = HubServer::bind(address, Duration::from_secs(timeout))?;
+    eprintln!("rogue-gym-server is listening on {:?}", server.address());
+    server.run()
+}
+
+#[cfg(unix)]
+fn unix_path(args: &ArgMatches) -> Option<Address> {
+    args.value_of("unix").map(|p| Address::Unix(p.into()))
+}
+
+#[cfg(not(unix))]
+fn unix_path(_args: &ArgMatches) -> Option<Address> {
+    None
+}
+
+fn parse_args<'a>() -> ArgMatches<'a> {
+    clap::App::new("rogue-gym server")
+        .version("0.1.0")
+        .author("Yuji Kanagawa <yuji.kngw.80s.revive@gmail.com>")
+        .about("play rogue-gym by JSON requests, via stdin/stdout or sockets")
+        .arg(
+            clap::Arg::with_name("unix")
+                .short("u")
+                .long("unix")
+                .value_name("PATH")
+                .help("Serve many sessions on a Unix domain socket")
+                .takes_value(true),
+        )
+        .arg(
+            clap::Arg::with_name("tcp")
+                .short("t")
+                .long("tcp")
+                .value_name("PORT")
+                .help("Serve many sessions on a localhost TCP port")
+                .takes_value(true),
+        )
+        .arg(
+            clap::Arg::with_name("timeout")
+                .long("timeout")
+                .value_name("SECS")
+                .help("Close sessions not used for SECS seconds (default: 600)")
+                .takes_value(true),
+        )
+        .get_matches()
+}
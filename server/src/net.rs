@@ -0,0 +1,298 @@
+//! serving a `Hub` over a Unix domain socket or localhost TCP
+use crate::hub::Hub;
+use crate::protocol::{HubRequest, Response, SessionId};
+use crate::write_response;
+use rogue_gym_core::error::{GameResult, ResultExt2};
+use std::collections::HashMap;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{Shutdown, TcpListener, TcpStream};
+#[cfg(unix)]
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+/// the server stops after `accept` fails this many times in a row
+const MAX_ACCEPT_ERRORS: u32 = 10;
+/// wait this much longer after each failure of `accept`
+const ACCEPT_BACKOFF: Duration = Duration::from_millis(100);
+
+/// where the server listens
+#[derive(Clone, Debug)]
+pub enum Address {
+    /// port of 127.0.0.1, where 0 means any free port
+    Tcp(u16),
+    #[cfg(unix)]
+    Unix(PathBuf),
+}
+
+trait Connection: Read + Write + Send {
+    fn try_clone_box(&self) -> io::Result<Box<dyn Connection>>;
+    /// stop reading, so that a blocked reader gets EOF
+    fn close_read(&self) -> io::Result<()>;
+}
+
+impl Connection for TcpStream {
+    fn try_clone_box(&self) -> io::Result<Box<dyn Connection>> {
+        Ok(Box::new(self.try_clone()?))
+    }
+    fn close_read(&self) -> io::Result<()> {
+        self.shutdown(Shutdown::Read)
+    }
+}
+
+#[cfg(unix)]
+impl Connection for UnixStream {
+    fn try_clone_box(&self) -> io::Result<Box<dyn Connection>> {
+        Ok(Box::new(self.try_clone()?))
+    }
+    fn close_read(&self) -> io::Result<()> {
+        self.shutdown(Shutdown::Read)
+    }
+}
+
+enum Listener {
+    Tcp(TcpListener),
+    #[cfg(unix)]
+    Unix(UnixListener),
+}
+
+impl Listener {
+    fn accept(&self) -> io::Result<Box<dyn Connection>> {
+        match self {
+            Listener::Tcp(l) => Ok(Box::new(l.accept()?.0)),
+            #[cfg(unix)]
+            Listener::Unix(l) => Ok(Box::new(l.accept()?.0)),
+        }
+    }
+}
+
+/// wake up the accepting thread by connecting to it
+fn wake(address: &Address) -> io::Result<()> {
+    match address {
+        Address::Tcp(port) => TcpStream::connect(("127.0.0.1", *port)).map(|_| ()),
+        #[cfg(unix)]
+        Address::Unix(path) => UnixStream::connect(path).map(|_| ()),
+    }
+}
+
+/// multi-session server which handles each connection in a thread
+pub struct HubServer {
+    listener: Listener,
+    address: Address,
+    hub: Arc<Hub>,
+}
+
+impl HubServer {
+    /// listen on `address`, with sessions closed after `timeout` of inactivity
+    pub fn bind(address: Address, timeout: Duration) -> GameResult<Self> {
+        let (listener, address) = match address {
+            Address::Tcp(port) => {
+                let l = TcpListener::bind(("127.0.0.1", port))
+                    .into_chained(|| format!("HubServer::bind: failed to bind port {}", port))?;
+                let port = l.local_addr().into_chained(|| "HubServer::bind")?.port();
+                (Listener::Tcp(l), Address::Tcp(port))
+            }
+            #[cfg(unix)]
+            Address::Unix(path) => {
+                let l = UnixListener::bind(&path)
+                    .into_chained(|| format!("HubServer::bind: failed to bind {:?}", path))?;
+                (Listener::Unix(l), Address::Unix(path))
+            }
+        };
+        Ok(HubServer {
+            listener,
+            address,
+            hub: Arc::new(Hub::new(timeout)),
+        })
+    }
+    /// the address actually listened, where the port is resolved
+    pub fn address(&self) -> &Address {
+        &self.address
+    }
+    pub fn hub(&self) -> Arc<Hub> {
+        Arc::clone(&self.hub)
+    }
+    /// serve until a `shutdown` request comes, then close all connections
+    pub fn run(self) -> GameResult<()> {
+        let HubServer {
+            listener,
+            address,
+            hub,
+        } = self;
+        let reaper = {
+            let hub = Arc::clone(&hub);
+            thread::spawn(move || {
+                while !hub.is_shutdown() {
+                    thread::sleep(Duration::from_millis(200));
+                    hub.remove_expired();
+                }
+            })
+        };
+        // open connections, which are removed by their threads when closed
+        let connections = Arc::new(Mutex::new(HashMap::new()));
+        let mut handles: Vec<JoinHandle<()>> = vec![];
+        let mut next_id = 0usize;
+        let mut accept_errors = 0;
+        let mut result = Ok(());
+        while !hub.is_shutdown() {
+            let conn = match listener.accept() {
+                Ok(c) => {
+                    accept_errors = 0;
+                    c
+                }
+                Err(e) => {
+                    eprintln!("[HubServer::run] failed to accept: {}", e);
+                    accept_errors += 1;
+                    if accept_errors >= MAX_ACCEPT_ERRORS {
+                        result = Err(e).into_chained(|| "HubServer::run: accept keeps failing");
+                        hub.shutdown();
+                        break;
+                    }
+                    thread::sleep(ACCEPT_BACKOFF * accept_errors);
+                    continue;
+                }
+            };
+            if hub.is_shutdown() {
+                break;
+            }
+            let clone = match conn.try_clone_box() {
+                Ok(c) => c,
+                Err(e) => {
+                    eprintln!("[HubServer::run] failed to clone a connection: {}", e);
+                    continue;
+                }
+            };
+            let id = next_id;
+            next_id += 1;
+            connections.lock().unwrap().insert(id, clone);
+            handles.retain(|h| !h.is_finished());
+            let (hub, address) = (Arc::clone(&hub), address.clone());
+            let connections = Arc::clone(&connections);
+            handles.push(thread::spawn(move || {
+                if let Err(e) = handle_connection(conn, &hub, &address) {
+                    eprintln!("[HubServer::run] {}", e);
+                }
+                connections.lock().unwrap().remove(&id);
+            }));
+        }
+        // unblock threads reading from clients, while they can still send responses
+        for conn in connections.lock().unwrap().values() {
+            let _ = conn.close_read();
+        }
+        for handle in handles {
+            let _ = handle.join();
+        }
+        let _ = reaper.join();
+        #[cfg(unix)]
+        {
+            if let Address::Unix(path) = &address {
+                let _ = std::fs::remove_file(path);
+            }
+        }
+        result
+    }
+}
+
+fn handle_connection(
+    mut conn: Box<dyn Connection>,
+    hub: &Hub,
+    address: &Address,
+) -> GameResult<()> {
+    let reader = BufReader::new(conn.try_clone_box()?);
+    for line in reader.lines() {
+        let line = match line {
+            Ok(l) => l,
+            // closed by the client or by shutdown
+            Err(_) => break,
+        };
+        if line.trim().is_empty() {
+            continue;
+        }
+        let request = match serde_json::from_str(&line) {
+            Ok(req) => req,
+            Err(e) => {
+                let res = Response::Error(format!("invalid request: {}", e));
+                write_response(&mut conn, &res)?;
+                continue;
+            }
+        };
+        match request {
+            HubRequest::Watch { id } => return watch(conn, hub, id),
+            HubRequest::Shutdown => {
+                write_response(&mut conn, &hub.handle(HubRequest::Shutdown))?;
+                return wake(address).into_chained(|| "handle_connection: failed to wake");
+            }
+            req => write_response(&mut conn, &hub.handle(req))?,
+        }
+    }
+    Ok(())
+}
+
+/// send observations of the session until it's closed
+fn watch(mut conn: Box<dyn Connection>, hub: &Hub, id: SessionId) -> GameResult<()> {
+    let (obs, rx) = match hub.watch(id) {
+        Ok(x) => x,
+        Err(e) => return write_response(&mut conn, &crate::error_response(&e)),
+    };
+    write_response(&mut conn, &Response::Observation(obs))?;
+    for obs in rx {
+        write_response(&mut conn, &Response::Observation(obs))?;
+    }
+    write_response(&mut conn, &Response::Closed(id))
+}
+
+#[cfg(all(test, unix))]
+mod test {
+    use super::*;
+    use crate::protocol::Observation;
+    use std::env;
+    use std::process;
+    fn request(reader: &mut impl BufRead, writer: &mut impl Write, req: &str) -> Response {
+        writeln!(writer, "{}", req).unwrap();
+        read(reader)
+    }
+    fn read(reader: &mut impl BufRead) -> Response {
+        let mut line = String::new();
+        reader.read_line(&mut line).unwrap();
+        serde_json::from_str(&line).unwrap()
+    }
+    fn observation(res: Response) -> Observation {
+        match res {
+            Response::Observation(obs) => obs,
+            res => panic!("unexpected response {:?}", res),
+        }
+    }
+    #[test]
+    fn unix_socket() {
+        let path = env::temp_dir().join(format!("rogue-gym-server-{}.sock", process::id()));
+        let _ = std::fs::remove_file(&path);
+        let server = HubServer::bind(Address::Unix(path.clone()), Duration::from_secs(60)).unwrap();
+        let handle = thread::spawn(move || server.run().unwrap());
+        let mut client = UnixStream::connect(&path).unwrap();
+        let mut reader = BufReader::new(client.try_clone().unwrap());
+        let id = match request(&mut reader, &mut client, r#"{"open": {}}"#) {
+            Response::Opened(id) => id,
+            res => panic!("unexpected response {:?}", res),
+        };
+        let spectator = UnixStream::connect(&path).unwrap();
+        let mut spectator_reader = BufReader::new(spectator.try_clone().unwrap());
+        writeln!(&spectator, r#"{{"watch": {{"id": {}}}}}"#, id).unwrap();
+        assert_eq!(observation(read(&mut spectator_reader)).steps, 0);
+        let req = format!(
+            r#"{{"session": {{"id": {}, "request": {{"step": {{"key": "s"}}}}}}}}"#,
+            id
+        );
+        let obs = observation(request(&mut reader, &mut client, &req));
+        assert_eq!(obs.steps, 1);
+        assert_eq!(observation(read(&mut spectator_reader)), obs);
+        assert_eq!(
+            request(&mut reader, &mut client, r#""shutdown""#),
+            Response::Bye
+        );
+        assert_eq!(read(&mut spectator_reader), Response::Closed(id));
+        handle.join().unwrap();
+        assert!(!path.exists());
+    }
+}
@@ -0,0 +1,78 @@
+//! a game driven by requests
+use crate::protocol::{KeyArg, Observation};
+use rogue_gym_core::error::GameResult;
+use rogue_gym_core::input::{InputCode, Key};
+use rogue_gym_core::replay::Replay;
+use rogue_gym_core::ui::{MordalKind, UiState};
+use rogue_gym_core::{GameConfig, GameMsg, Reaction, RunTime};
+
+/// a running game and what happened in the last step
+pub struct Session {
+    runtime: RunTime,
+    messages: Vec<String>,
+    game_over: bool,
+}
+
+impl Session {
+    pub fn new(config: GameConfig) -> GameResult<Self> {
+        Ok(Session::from_runtime(config.build()?))
+    }
+    /// replay the game, failing if it diverges from the record
+    pub fn restore(replay: &Replay) -> GameResult<Self> {
+        Ok(Session::from_runtime(replay.run()?))
+    }
+    fn from_runtime(mut runtime: RunTime) -> Self {
+        // record state hashes, not to play the whole game again for each save
+        runtime.set_record_hashes(true);
+        Session {
+            runtime,
+            messages: vec![],
+            game_over: false,
+        }
+    }
+    pub fn runtime(&self) -> &RunTime {
+        &self.runtime
+    }
+    pub fn step_key(&mut self, key: KeyArg) -> GameResult<()> {
+        self.messages.clear();
+        let res = self.runtime.react_to_key(Key::from(key))?;
+        self.process_reactions(res);
+        Ok(())
+    }
+    pub fn step_input(&mut self, input: InputCode) -> GameResult<()> {
+        self.messages.clear();
+        let res = self.runtime.react_to_input(input)?;
+        self.process_reactions(res);
+        Ok(())
+    }
+    fn process_reactions(&mut self, reactions: Vec<Reaction>) {
+        for reaction in reactions {
+            match reaction {
+                Reaction::UiTransition(UiState::Mordal(MordalKind::Grave(_))) => {
+                    self.game_over = true
+                }
+                Reaction::Notify(msg) => {
+                    if msg == GameMsg::Quit {
+                        self.game_over = true;
+                    }
+                    if let Some(text) = self.runtime.format_msg(&msg) {
+                        self.messages.push(text);
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+    pub fn observation(&self) -> GameResult<Observation> {
+        Ok(Observation {
+            map: self.runtime.screen_rows()?,
+            status: self.runtime.player_status(),
+            messages: self.messages.clone(),
+            game_over: self.game_over,
+            steps: self.runtime.saved_inputs().len(),
+        })
+    }
+    pub fn save(&self) -> GameResult<Replay> {
+        Replay::from_runtime(&self.runtime)
+    }
+}
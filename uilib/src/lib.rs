@@ -1,8 +1,9 @@
 //! UI abstraction for rogue-gym
+use rogue_gym_core::character::{player::Status, HitPoint};
 use rogue_gym_core::dungeon::{Coord, Positioned, X, Y};
 use rogue_gym_core::error::GameResult;
 use rogue_gym_core::ui::{MordalKind, UiState};
-use rogue_gym_core::{character::player::Status, tile::Tile, GameMsg, Reaction, RunTime};
+use rogue_gym_core::{tile::Tile, GameMsg, Reaction, RunTime};
 
 /// 0-indexed 2d screen for rogue-gym
 pub trait Screen {
@@ -43,6 +44,10 @@ pub trait Screen {
     fn pend_message<S: AsRef<str>>(&mut self, msg: S) -> GameResult<()> {
         self.message(msg)
     }
+    /// show details of attacks for debugging(does nothing by default)
+    fn combat_detail<S: AsRef<str>>(&mut self, _detail: S) -> GameResult<()> {
+        Ok(())
+    }
     fn status(&mut self, status: &Status) -> GameResult<()> {
         self.write_str(
             Coord::new(0, self.height() - 1.into()),
@@ -63,9 +68,32 @@ pub trait Screen {
         self.flush()
     }
     fn inventory(&mut self, runtime: &mut RunTime) -> GameResult<()> {
-        for (i, item) in runtime.itembox().items().enumerate() {
-            let num = (b'a' + i as u8) as char;
-            self.write_str(Coord::new(0, i as i32), format!("{}) {}", num, item))?;
+        for (i, (c, item)) in runtime.itembox().entries().enumerate() {
+            self.write_str(Coord::new(0, i as i32), format!("{}) {}", c, item.get()))?;
+        }
+        self.write_str(
+            Coord::new(0, self.height() - 1.into()),
+            "--Press space to continue--",
+        )
+    }
+    fn select_item(&mut self, runtime: &mut RunTime) -> GameResult<()> {
+        self.clear_dungeon()?;
+        for (i, (c, item)) in runtime.prompt_items().into_iter().enumerate() {
+            self.write_str(
+                Coord::new(0, i as i32 + 1),
+                format!("{}) {}", c, item.get()),
+            )?;
+        }
+        Ok(())
+    }
+    fn messages(&mut self, runtime: &mut RunTime) -> GameResult<()> {
+        self.clear_dungeon()?;
+        let rows = self.height().0 as usize - 2;
+        for (i, msg) in runtime.messages().last(rows).enumerate() {
+            self.write_str(
+                Coord::new(0, i as i32),
+                format!("{:>5}: {}", msg.turn, msg.text),
+            )?;
         }
         self.write_str(
             Coord::new(0, self.height() - 1.into()),
@@ -107,38 +135,44 @@ pub enum Transition {
     None,
 }
 
+/// damage, HP and exp carried by combat messages
+pub fn combat_detail(msg: &GameMsg) -> Option<String> {
+    let hp = |hp: Option<HitPoint>| hp.map_or_else(String::new, |hp| format!(", {} HP left", hp));
+    match msg {
+        GameMsg::HitTo(hit) | GameMsg::HitFrom(hit) => {
+            Some(format!("{} damage{}", hit.damage, hp(hit.hp_left)))
+        }
+        GameMsg::Killed { exp, .. } => Some(format!("+{} exp", exp.0)),
+        _ => None,
+    }
+}
+
 pub fn process_reaction<S: Screen>(
     screen: &mut S,
     runtime: &mut RunTime,
     reaction: Reaction,
 ) -> GameResult<Transition> {
     match reaction {
-        Reaction::Notify(msg) => match msg {
-            GameMsg::CantMove(_) => Ok(()),
-            GameMsg::CantGetItem(kind) => screen.pend_message(format!("You walk onto {:?}", kind)),
-            GameMsg::NoDownStair => {
-                screen.pend_message(format!("Hmm... there seems to be no downstair"))
+        Reaction::Notify(msg) => {
+            if let Some(text) = runtime.format_msg(&msg) {
+                screen.pend_message(text)?;
             }
-            GameMsg::GotItem { kind, num } => {
-                screen.pend_message(format!("You got {} {:?}", num, kind))
+            if let Some(detail) = combat_detail(&msg) {
+                screen.combat_detail(detail)?;
             }
-            GameMsg::SecretDoor => screen.pend_message(format!("You found a secret door")),
-            GameMsg::HitTo(s) => screen.pend_message(format!("You swings and hit {}", s)),
-            GameMsg::HitFrom(s) => screen.pend_message(format!("{} swings and hits you", s)),
-            GameMsg::MissTo(s) => screen.pend_message(format!("You swing and miss {}", s)),
-            GameMsg::MissFrom(s) => screen.pend_message(format!("{} swings and misses you", s)),
-            GameMsg::Killed(s) => screen.pend_message(format!("You defeated the {}", s)),
-            GameMsg::Quit => {
-                screen.pend_message(format!("Thank you for playing!"))?;
+            if msg == GameMsg::Quit {
                 return Ok(Transition::Exit);
             }
-        },
+            Ok(())
+        }
         Reaction::Redraw => screen.dungeon(runtime),
         Reaction::StatusUpdated => screen.status(&runtime.player_status()),
         Reaction::UiTransition(ui_state) => match ui_state {
             UiState::Mordal(kind) => match kind {
                 MordalKind::Quit => screen.message(format!("You really quit game?(y/n)")),
                 MordalKind::Inventory => screen.inventory(runtime),
+                MordalKind::Messages => screen.messages(runtime),
+                MordalKind::SelectItem(_) => screen.select_item(runtime),
                 MordalKind::Grave(msg) => screen.dying_msg(&*msg),
             },
             UiState::Dungeon => {